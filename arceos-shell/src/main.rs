@@ -5,7 +5,10 @@
 #[cfg(feature = "axstd")]
 extern crate axstd as std;
 
+#[cfg(feature = "plat-d3000m")]
 extern crate axplat_aarch64_d3000m_n80_laptop;
+#[cfg(feature = "plat-qemu-virt")]
+extern crate axplat_aarch64_qemu_virt;
 
 #[cfg(not(feature = "axstd"))]
 fn path_to_str(path: &impl AsRef<std::ffi::OsStr>) -> &str {
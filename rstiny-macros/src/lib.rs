@@ -0,0 +1,92 @@
+//! Procedural macros of rstiny.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{Error, ItemFn, LitInt, LitStr, ReturnType, parse_macro_input};
+
+/// Registers a function as a kernel test.
+///
+/// The function must take no arguments and return `()`; it fails by
+/// panicking, e.g. through `assert!`. A descriptor is placed in the `.ktest`
+/// linker section, where the runner in `crate::test` finds it at boot.
+///
+/// Arguments:
+///
+/// - `should_panic`: the test passes only if it panics;
+///   `should_panic = "text"` also requires the panic message to contain
+///   `text`.
+/// - `timeout_ms = N`: deadline of the test, instead of the runner default.
+///
+/// ```ignore
+/// #[ktest]
+/// fn vec_push() {
+///     let mut v = Vec::new();
+///     v.push(1);
+///     assert_eq!(v.len(), 1);
+/// }
+///
+/// #[ktest(should_panic = "index out of bounds", timeout_ms = 100)]
+/// fn vec_index() {
+///     let v: Vec<u8> = Vec::new();
+///     let _ = v[1];
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn ktest(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut should_panic = quote!(crate::test::ShouldPanic::No);
+    let mut timeout_ms = 0u64;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("should_panic") {
+            should_panic = if meta.input.peek(syn::Token![=]) {
+                let expected: LitStr = meta.value()?.parse()?;
+                quote!(crate::test::ShouldPanic::WithMessage(#expected))
+            } else {
+                quote!(crate::test::ShouldPanic::Yes)
+            };
+            Ok(())
+        } else if meta.path.is_ident("timeout_ms") {
+            let value: LitInt = meta.value()?.parse()?;
+            timeout_ms = value.base10_parse()?;
+            if timeout_ms == 0 {
+                return Err(meta.error("timeout_ms must be positive"));
+            }
+            Ok(())
+        } else {
+            Err(meta.error("expected `should_panic` or `timeout_ms`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+
+    let func = parse_macro_input!(item as ItemFn);
+    let sig = &func.sig;
+    if !sig.inputs.is_empty()
+        || !sig.generics.params.is_empty()
+        || sig.asyncness.is_some()
+        || !matches!(sig.output, ReturnType::Default)
+    {
+        return Error::new_spanned(
+            sig,
+            "#[ktest] functions must be `fn()` without a return value",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let ident = &sig.ident;
+    let name = ident.to_string();
+    quote! {
+        #func
+
+        const _: () = {
+            #[used]
+            #[unsafe(link_section = ".ktest")]
+            static TEST: crate::test::KTest = crate::test::KTest {
+                name: concat!(module_path!(), "::", #name),
+                func: #ident,
+                should_panic: #should_panic,
+                timeout_ms: #timeout_ms,
+            };
+        };
+    }
+    .into()
+}
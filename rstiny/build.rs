@@ -2,5 +2,9 @@
 fn main() {
     println!("cargo:rustc-link-arg=-T");
     println!("cargo:rustc-link-arg=rstiny/link.lds");
+    // QEMU virt 从 RAM 起始 + 2MB 处加载内核
+    if std::env::var_os("CARGO_FEATURE_PLAT_QEMU_VIRT").is_some() {
+        println!("cargo:rustc-link-arg=--defsym=BASE_ADDRESS=0xffff000040200000");
+    }
     println!("cargo:rustc-codegen-options=-C target-cpu=cortex-a76");
 }
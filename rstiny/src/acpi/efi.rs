@@ -0,0 +1,120 @@
+//! UEFI system table, to find the RSDP in its configuration table.
+//!
+//! The system table address is passed by the Linux-style UEFI stub in
+//! `/chosen/linux,uefi-system-table`, or configured with
+//! [`EFI_SYSTEM_TABLE_PADDR`]. The configuration table entries point to
+//! physical addresses, which stay valid after `ExitBootServices`.
+
+use super::{read_u32, read_u64};
+use crate::config::EFI_SYSTEM_TABLE_PADDR;
+use crate::fdt;
+use crate::utils::mem::{MemRegion, phys_to_virt, virt_to_phys};
+
+/// `IBI SYST`
+const SYSTEM_TABLE_SIGNATURE: u64 = 0x5453_5953_2049_4249;
+/// Offsets of the 64-bit `EFI_SYSTEM_TABLE` fields.
+const HEADER_SIZE: usize = 12;
+const NUMBER_OF_TABLE_ENTRIES: usize = 104;
+const CONFIGURATION_TABLE: usize = 112;
+const SYSTEM_TABLE_MIN_SIZE: usize = 120;
+/// Size of an `EFI_CONFIGURATION_TABLE` entry: a GUID and a pointer.
+const CONFIG_ENTRY_SIZE: usize = 24;
+/// Bound on the number of configuration table entries, against a corrupt table.
+const MAX_CONFIG_ENTRIES: usize = 256;
+
+const ACPI_20_TABLE_GUID: [u8; 16] = guid(
+    0x8868_e871,
+    0xe4f1,
+    0x11d3,
+    [0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81],
+);
+const ACPI_TABLE_GUID: [u8; 16] = guid(
+    0xeb9d_2d30,
+    0x2d88,
+    0x11d3,
+    [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
+);
+
+/// Lays out a GUID as stored by UEFI: the first three fields little-endian.
+const fn guid(a: u32, b: u16, c: u16, d: [u8; 8]) -> [u8; 16] {
+    let a = a.to_le_bytes();
+    let b = b.to_le_bytes();
+    let c = c.to_le_bytes();
+    [
+        a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d[0], d[1], d[2], d[3], d[4], d[5], d[6],
+        d[7],
+    ]
+}
+
+/// Returns the physical address of the UEFI system table, if known.
+pub fn system_table_paddr() -> Option<usize> {
+    let from_fdt = fdt::get()
+        .and_then(|fdt| fdt.chosen())
+        .and_then(|chosen| chosen.property("linux,uefi-system-table"))
+        .and_then(|value| Some(u64::from_be_bytes(value.get(..8)?.try_into().unwrap())));
+    from_fdt
+        .map(|paddr| paddr as usize)
+        .or(EFI_SYSTEM_TABLE_PADDR)
+}
+
+/// Returns the size of the system table at `paddr` and its configuration
+/// table entries, or `None` if there is no valid system table.
+///
+/// # Safety
+///
+/// `paddr` must be mapped in the linear mapping.
+unsafe fn configuration_table(paddr: usize) -> Option<(usize, &'static [u8])> {
+    let ptr = phys_to_virt(paddr) as *const u8;
+    let table = unsafe { core::slice::from_raw_parts(ptr, SYSTEM_TABLE_MIN_SIZE) };
+    let size = read_u32(table, HEADER_SIZE) as usize;
+    if read_u64(table, 0) != SYSTEM_TABLE_SIGNATURE || size < SYSTEM_TABLE_MIN_SIZE {
+        return None;
+    }
+    let count = read_u64(table, NUMBER_OF_TABLE_ENTRIES) as usize;
+    let entries = read_u64(table, CONFIGURATION_TABLE) as usize;
+    if count > MAX_CONFIG_ENTRIES || entries == 0 {
+        return Some((size, &[]));
+    }
+    let entries = unsafe {
+        core::slice::from_raw_parts(
+            phys_to_virt(entries) as *const u8,
+            count * CONFIG_ENTRY_SIZE,
+        )
+    };
+    Some((size, entries))
+}
+
+/// Finds the RSDP in the configuration table of the system table at
+/// `paddr`, preferring the ACPI 2.0 entry.
+///
+/// # Safety
+///
+/// `paddr` must be mapped in the linear mapping.
+pub unsafe fn find_rsdp(paddr: usize) -> Option<usize> {
+    let Some((_, entries)) = (unsafe { configuration_table(paddr) }) else {
+        early_println!("ACPI: no UEFI system table at {paddr:#x}");
+        return None;
+    };
+    let find = |wanted: &[u8; 16]| {
+        entries
+            .chunks_exact(CONFIG_ENTRY_SIZE)
+            .find(|entry| &entry[..16] == wanted)
+            .map(|entry| read_u64(entry, 16) as usize)
+    };
+    find(&ACPI_20_TABLE_GUID).or_else(|| find(&ACPI_TABLE_GUID))
+}
+
+/// Returns the memory taken by the system table at `paddr` and by its
+/// configuration table, which must not be handed out as free RAM.
+///
+/// # Safety
+///
+/// `paddr` must be mapped in the linear mapping.
+pub unsafe fn table_regions(paddr: usize) -> Option<[MemRegion; 2]> {
+    let (size, entries) = unsafe { configuration_table(paddr) }?;
+    let config = match entries {
+        [] => MemRegion::EMPTY,
+        _ => MemRegion::new(virt_to_phys(entries.as_ptr() as usize), entries.len()),
+    };
+    Some([MemRegion::new(paddr, size), config])
+}
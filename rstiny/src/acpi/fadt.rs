@@ -0,0 +1,72 @@
+//! Fixed ACPI Description Table (FADT).
+//!
+//! On Arm only a few fields matter: whether the platform is hardware-reduced,
+//! how to call PSCI, and where the DSDT is.
+
+use super::{Sdt, read_u32, read_u64};
+
+/// Offsets of FADT fields.
+const DSDT: usize = 40;
+const FLAGS: usize = 112;
+const ARM_BOOT_ARCH: usize = 129;
+const MINOR_VERSION: usize = 131;
+const X_DSDT: usize = 140;
+/// Length of an ACPI 5.1 table, the first with `ARM_BOOT_ARCH`.
+const FADT_MIN_LENGTH: usize = 268;
+
+/// `Flags`: no fixed hardware, everything is described in the DSDT.
+const HW_REDUCED_ACPI: u32 = 1 << 20;
+/// `ARM_BOOT_ARCH` bits.
+const PSCI_COMPLIANT: u16 = 1 << 0;
+const PSCI_USE_HVC: u16 = 1 << 1;
+
+/// How PSCI calls reach the firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsciConduit {
+    Smc,
+    Hvc,
+}
+
+/// The fields of the FADT used on Arm.
+#[derive(Debug, Clone, Copy)]
+pub struct Fadt {
+    /// ACPI version of the table, as `(major, minor)`.
+    pub version: (u8, u8),
+    pub hw_reduced: bool,
+    /// PSCI conduit, or `None` if the platform does not implement PSCI.
+    pub psci: Option<PsciConduit>,
+    /// Physical address of the DSDT.
+    pub dsdt_paddr: u64,
+}
+
+impl Fadt {
+    /// Parses a FADT. Returns `None` if the table predates ACPI 5.1.
+    pub fn parse(table: &Sdt) -> Option<Self> {
+        let bytes = table.bytes();
+        if table.signature() != b"FACP" || bytes.len() < FADT_MIN_LENGTH {
+            return None;
+        }
+        let boot_arch = u16::from_le_bytes([bytes[ARM_BOOT_ARCH], bytes[ARM_BOOT_ARCH + 1]]);
+        let psci = (boot_arch & PSCI_COMPLIANT != 0).then_some(if boot_arch & PSCI_USE_HVC != 0 {
+            PsciConduit::Hvc
+        } else {
+            PsciConduit::Smc
+        });
+        // X_DSDT 优先，为 0 时使用 32 位的 DSDT
+        let dsdt_paddr = match read_u64(bytes, X_DSDT) {
+            0 => read_u32(bytes, DSDT) as u64,
+            x_dsdt => x_dsdt,
+        };
+        Some(Self {
+            version: (table.revision(), bytes[MINOR_VERSION] & 0xf),
+            hw_reduced: read_u32(bytes, FLAGS) & HW_REDUCED_ACPI != 0,
+            psci,
+            dsdt_paddr,
+        })
+    }
+}
+
+/// Finds and parses the FADT.
+pub fn get() -> Option<Fadt> {
+    Fadt::parse(&super::find_table(b"FACP")?)
+}
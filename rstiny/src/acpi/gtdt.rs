@@ -0,0 +1,126 @@
+//! Generic Timer Description Table (GTDT).
+
+use super::{Sdt, read_u32, read_u64};
+
+/// Offsets of GTDT fields.
+const CNT_CONTROL_BASE: usize = 36;
+const SECURE_EL1_TIMER: usize = 48;
+const NON_SECURE_EL1_TIMER: usize = 56;
+const VIRTUAL_EL1_TIMER: usize = 64;
+const EL2_TIMER: usize = 72;
+const CNT_READ_BASE: usize = 80;
+const PLATFORM_TIMER_COUNT: usize = 88;
+const PLATFORM_TIMER_OFFSET: usize = 92;
+const VIRTUAL_EL2_TIMER: usize = 96;
+/// Length of a revision 2 table.
+const GTDT_MIN_LENGTH: usize = 96;
+/// Length of a revision 3 table, which adds the virtual EL2 timer.
+const GTDT_V3_LENGTH: usize = 104;
+/// Physical address meaning "not provided".
+const NO_ADDRESS: u64 = u64::MAX;
+
+/// Platform timer structure type of an SBSA generic watchdog.
+const TYPE_WATCHDOG: u8 = 1;
+const WATCHDOG_LENGTH: usize = 28;
+
+/// Timer interrupt flag bits.
+const FLAG_EDGE: u32 = 1 << 0;
+const FLAG_ACTIVE_LOW: u32 = 1 << 1;
+
+/// A timer interrupt and its trigger mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerIrq {
+    pub gsiv: u32,
+    pub edge: bool,
+    pub active_low: bool,
+}
+
+impl TimerIrq {
+    /// Reads a `(GSIV, flags)` pair. A GSIV of 0 means the timer is absent.
+    fn read(bytes: &[u8], offset: usize) -> Option<Self> {
+        let gsiv = read_u32(bytes, offset);
+        let flags = read_u32(bytes, offset + 4);
+        (gsiv != 0).then_some(Self {
+            gsiv,
+            edge: flags & FLAG_EDGE != 0,
+            active_low: flags & FLAG_ACTIVE_LOW != 0,
+        })
+    }
+}
+
+/// An SBSA generic watchdog.
+#[derive(Debug, Clone, Copy)]
+pub struct Watchdog {
+    pub refresh_frame: u64,
+    pub control_frame: u64,
+    pub gsiv: u32,
+}
+
+/// The architected timer interrupts and memory-mapped counter.
+#[derive(Debug, Clone, Copy)]
+pub struct Gtdt {
+    /// Physical address of the `CNTControlBase` frame, if provided.
+    pub cnt_control_base: Option<u64>,
+    /// Physical address of the `CNTReadBase` frame, if provided.
+    pub cnt_read_base: Option<u64>,
+    pub secure_el1: Option<TimerIrq>,
+    pub non_secure_el1: Option<TimerIrq>,
+    pub virtual_el1: Option<TimerIrq>,
+    pub el2: Option<TimerIrq>,
+    /// Virtual EL2 timer (revision 3 and later).
+    pub virtual_el2: Option<TimerIrq>,
+    bytes: &'static [u8],
+}
+
+impl Gtdt {
+    /// Parses a GTDT. Returns `None` if the table is too short.
+    pub fn parse(table: &Sdt) -> Option<Self> {
+        let bytes = table.bytes();
+        if table.signature() != b"GTDT" || bytes.len() < GTDT_MIN_LENGTH {
+            return None;
+        }
+        let address = |offset| Some(read_u64(bytes, offset)).filter(|&a| a != 0 && a != NO_ADDRESS);
+        let virtual_el2 = (table.revision() >= 3 && bytes.len() >= GTDT_V3_LENGTH)
+            .then(|| TimerIrq::read(bytes, VIRTUAL_EL2_TIMER))
+            .flatten();
+        Some(Self {
+            cnt_control_base: address(CNT_CONTROL_BASE),
+            cnt_read_base: address(CNT_READ_BASE),
+            secure_el1: TimerIrq::read(bytes, SECURE_EL1_TIMER),
+            non_secure_el1: TimerIrq::read(bytes, NON_SECURE_EL1_TIMER),
+            virtual_el1: TimerIrq::read(bytes, VIRTUAL_EL1_TIMER),
+            el2: TimerIrq::read(bytes, EL2_TIMER),
+            virtual_el2,
+            bytes,
+        })
+    }
+
+    /// Iterates over the SBSA generic watchdogs among the platform timers.
+    pub fn watchdogs(&self) -> impl Iterator<Item = Watchdog> + use<> {
+        let bytes = self.bytes;
+        let count = read_u32(bytes, PLATFORM_TIMER_COUNT) as usize;
+        let mut offset = read_u32(bytes, PLATFORM_TIMER_OFFSET) as usize;
+        (0..count)
+            .map_while(move |_| {
+                let header = bytes.get(offset..offset + 3)?;
+                let length = u16::from_le_bytes([header[1], header[2]]) as usize;
+                if length < 3 {
+                    return None;
+                }
+                let entry = bytes.get(offset..offset + length)?;
+                offset += length;
+                Some(entry)
+            })
+            .filter(|entry| entry[0] == TYPE_WATCHDOG && entry.len() >= WATCHDOG_LENGTH)
+            .map(|entry| Watchdog {
+                refresh_frame: read_u64(entry, 4),
+                control_frame: read_u64(entry, 12),
+                gsiv: read_u32(entry, 20),
+            })
+    }
+}
+
+/// Finds and parses the GTDT.
+pub fn get() -> Option<Gtdt> {
+    Gtdt::parse(&super::find_table(b"GTDT")?)
+}
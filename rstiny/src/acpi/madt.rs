@@ -0,0 +1,172 @@
+//! Multiple APIC Description Table (MADT): GIC layout and CPUs.
+
+use super::{SDT_HEADER_SIZE, Sdt, read_u32, read_u64};
+
+/// Offset of the first interrupt controller structure.
+const ENTRIES: usize = SDT_HEADER_SIZE + 8;
+
+/// Interrupt controller structure types.
+const TYPE_GICC: u8 = 0x0b;
+const TYPE_GICD: u8 = 0x0c;
+const TYPE_GIC_MSI_FRAME: u8 = 0x0d;
+const TYPE_GICR: u8 = 0x0e;
+const TYPE_GIC_ITS: u8 = 0x0f;
+
+/// Minimum lengths of the structures, as of ACPI 6.0.
+const GICC_MIN_LENGTH: usize = 76;
+const GICD_LENGTH: usize = 24;
+const GIC_MSI_FRAME_LENGTH: usize = 24;
+const GICR_LENGTH: usize = 16;
+const GIC_ITS_LENGTH: usize = 20;
+
+/// GICC flag: the processor can be used.
+const GICC_ENABLED: u32 = 1 << 0;
+/// GICC flag: the processor can be brought online later.
+const GICC_ONLINE_CAPABLE: u32 = 1 << 3;
+
+/// A GIC CPU interface: one per processor.
+#[derive(Debug, Clone, Copy)]
+pub struct Gicc {
+    /// ACPI processor UID, matching the processor device in the DSDT.
+    pub uid: u32,
+    /// `MPIDR_EL1` affinity fields of the processor.
+    pub mpidr: u64,
+    /// Whether the processor is enabled or can be enabled.
+    pub usable: bool,
+    /// Physical address of the GICv2 CPU interface, 0 for a GICv3.
+    pub gicc_base: u64,
+    /// Physical address of the redistributor of this processor, 0 if the
+    /// redistributors are described by [`MadtEntry::Gicr`] instead.
+    pub gicr_base: u64,
+    /// Performance monitoring interrupt.
+    pub pmu_gsiv: u32,
+    /// Virtual GIC maintenance interrupt.
+    pub vgic_maintenance_gsiv: u32,
+}
+
+/// An interrupt controller structure of the MADT.
+#[derive(Debug, Clone, Copy)]
+pub enum MadtEntry {
+    Gicc(Gicc),
+    /// The distributor, with its GIC version (0 if it must be read from the
+    /// hardware).
+    Gicd {
+        base: u64,
+        version: u8,
+    },
+    /// A GICv2m MSI frame.
+    GicMsiFrame {
+        base: u64,
+    },
+    /// A range of contiguous redistributors.
+    Gicr {
+        base: u64,
+        length: u32,
+    },
+    /// An interrupt translation service.
+    GicIts {
+        id: u32,
+        base: u64,
+    },
+    /// A structure not used on Arm, or too short, identified by its type.
+    Other(u8),
+}
+
+impl MadtEntry {
+    fn parse(kind: u8, entry: &[u8]) -> Self {
+        match kind {
+            TYPE_GICC if entry.len() >= GICC_MIN_LENGTH => {
+                let flags = read_u32(entry, 12);
+                Self::Gicc(Gicc {
+                    uid: read_u32(entry, 8),
+                    mpidr: read_u64(entry, 68),
+                    usable: flags & (GICC_ENABLED | GICC_ONLINE_CAPABLE) != 0,
+                    gicc_base: read_u64(entry, 32),
+                    gicr_base: read_u64(entry, 60),
+                    pmu_gsiv: read_u32(entry, 20),
+                    vgic_maintenance_gsiv: read_u32(entry, 56),
+                })
+            }
+            TYPE_GICD if entry.len() >= GICD_LENGTH => Self::Gicd {
+                base: read_u64(entry, 8),
+                version: entry[20],
+            },
+            TYPE_GIC_MSI_FRAME if entry.len() >= GIC_MSI_FRAME_LENGTH => Self::GicMsiFrame {
+                base: read_u64(entry, 8),
+            },
+            TYPE_GICR if entry.len() >= GICR_LENGTH => Self::Gicr {
+                base: read_u64(entry, 4),
+                length: read_u32(entry, 12),
+            },
+            TYPE_GIC_ITS if entry.len() >= GIC_ITS_LENGTH => Self::GicIts {
+                id: read_u32(entry, 4),
+                base: read_u64(entry, 8),
+            },
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// The MADT.
+#[derive(Debug, Clone, Copy)]
+pub struct Madt {
+    bytes: &'static [u8],
+}
+
+impl Madt {
+    /// Parses a MADT. Returns `None` if the table is too short.
+    pub fn parse(table: &Sdt) -> Option<Self> {
+        let bytes = table.bytes();
+        if table.signature() != b"APIC" || bytes.len() < ENTRIES {
+            return None;
+        }
+        Some(Self { bytes })
+    }
+
+    /// Iterates over the interrupt controller structures. Stops at the first
+    /// malformed one.
+    pub fn entries(&self) -> impl Iterator<Item = MadtEntry> + use<> {
+        let bytes = self.bytes;
+        let mut offset = ENTRIES;
+        core::iter::from_fn(move || {
+            let length = *bytes.get(offset + 1)? as usize;
+            if length < 2 {
+                return None;
+            }
+            let entry = bytes.get(offset..offset + length)?;
+            offset += length;
+            Some(MadtEntry::parse(entry[0], entry))
+        })
+    }
+
+    /// Iterates over the usable processors, in table order.
+    pub fn cpus(&self) -> impl Iterator<Item = Gicc> + use<> {
+        self.entries().filter_map(|entry| match entry {
+            MadtEntry::Gicc(gicc) if gicc.usable => Some(gicc),
+            _ => None,
+        })
+    }
+
+    /// Returns the distributor base address and GIC version. A version of 0
+    /// is resolved from the other structures: redistributors mean a GICv3.
+    pub fn gicd(&self) -> Option<(u64, u8)> {
+        let (base, version) = self.entries().find_map(|entry| match entry {
+            MadtEntry::Gicd { base, version } => Some((base, version)),
+            _ => None,
+        })?;
+        if version != 0 {
+            return Some((base, version));
+        }
+        let has_gicr = self.entries().any(|entry| match entry {
+            MadtEntry::Gicr { .. } => true,
+            MadtEntry::Gicc(gicc) => gicc.gicr_base != 0,
+            _ => false,
+        });
+        Some((base, if has_gicr { 3 } else { 2 }))
+    }
+}
+
+/// Finds and parses the MADT.
+pub fn get() -> Option<Madt> {
+    Madt::parse(&super::find_table(b"APIC")?)
+}
@@ -0,0 +1,65 @@
+//! PCI Express memory mapped configuration table (MCFG).
+
+use super::{SDT_HEADER_SIZE, Sdt, read_u64};
+
+/// Offset of the first allocation, after 8 reserved bytes.
+const ALLOCATIONS: usize = SDT_HEADER_SIZE + 8;
+const ALLOCATION_SIZE: usize = 16;
+/// Configuration space of one bus: 32 devices, 8 functions, 4 KiB each.
+const BUS_SIZE: u64 = 1 << 20;
+
+/// The ECAM region of a PCI segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ecam {
+    /// Physical address of bus 0 of the segment, even if `start_bus` is not 0.
+    pub base: u64,
+    pub segment: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+}
+
+impl Ecam {
+    /// Returns the `(address, size)` of the configuration space of the
+    /// decoded buses.
+    pub fn region(&self) -> (u64, u64) {
+        let buses = (self.end_bus as u64 + 1).saturating_sub(self.start_bus as u64);
+        (
+            self.base + self.start_bus as u64 * BUS_SIZE,
+            buses * BUS_SIZE,
+        )
+    }
+}
+
+/// The MCFG.
+#[derive(Debug, Clone, Copy)]
+pub struct Mcfg {
+    bytes: &'static [u8],
+}
+
+impl Mcfg {
+    /// Parses an MCFG. Returns `None` if the table is too short.
+    pub fn parse(table: &Sdt) -> Option<Self> {
+        let bytes = table.bytes();
+        if table.signature() != b"MCFG" || bytes.len() < ALLOCATIONS {
+            return None;
+        }
+        Some(Self { bytes })
+    }
+
+    /// Iterates over the ECAM regions, one per PCI segment.
+    pub fn ecams(&self) -> impl Iterator<Item = Ecam> + use<> {
+        self.bytes[ALLOCATIONS..]
+            .chunks_exact(ALLOCATION_SIZE)
+            .map(|entry| Ecam {
+                base: read_u64(entry, 0),
+                segment: u16::from_le_bytes([entry[8], entry[9]]),
+                start_bus: entry[10],
+                end_bus: entry[11],
+            })
+    }
+}
+
+/// Finds and parses the MCFG.
+pub fn get() -> Option<Mcfg> {
+    Mcfg::parse(&super::find_table(b"MCFG")?)
+}
@@ -0,0 +1,493 @@
+//! ACPI table discovery.
+//!
+//! The RSDP is taken, in order of preference, from:
+//!
+//! 1. `acpi_rsdp=<paddr>` on the command line;
+//! 2. the configuration table of the UEFI system table, see [`efi`];
+//! 3. the [`ACPI_RSDP_PADDR`] configured at build time.
+//!
+//! Tables are accessed in place through the linear mapping; every table is
+//! checksum-validated before it is handed out. [`init`] records the memory
+//! taken by the tables, and by the UEFI system table, so that
+//! [`free_ram_regions`](crate::utils::mem::free_ram_regions) keeps it out of
+//! the allocators. Once the heap is up,
+//! [`register_devices`] adds the devices described by the static tables
+//! (GIC, timers, watchdogs, console, PCI host bridges) to the
+//! [device registry](crate::drivers::registry). Devices that only appear in
+//! the DSDT are not discovered: there is no AML interpreter.
+
+pub mod efi;
+pub mod fadt;
+pub mod gtdt;
+pub mod madt;
+pub mod mcfg;
+pub mod spcr;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use lazyinit::LazyInit;
+
+use madt::MadtEntry;
+use spcr::InterfaceType;
+
+use crate::config::ACPI_RSDP_PADDR;
+use crate::drivers::registry::{self, Device, DeviceSource};
+use crate::utils::mem::{MemRegion, MemRegionList, phys_to_virt, virt_to_phys};
+
+/// Size of the common system description table header.
+pub const SDT_HEADER_SIZE: usize = 36;
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+/// Size of the ACPI 1.0 part of the RSDP covered by the first checksum.
+const RSDP_V1_SIZE: usize = 20;
+/// Size of the ACPI 2.0+ RSDP.
+const RSDP_V2_SIZE: usize = 36;
+/// Maximum number of memory ranges taken by the firmware tables.
+const MAX_TABLE_REGIONS: usize = 64;
+
+/// Errors found while validating ACPI tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    /// The RSDP signature is wrong.
+    BadRsdpSignature,
+    /// A table checksum does not add up to zero.
+    BadChecksum([u8; 4]),
+    /// A table is shorter than its header requires.
+    BadLength([u8; 4]),
+}
+
+fn checksum_ok(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) == 0
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Root System Description Pointer.
+#[derive(Debug, Clone, Copy)]
+pub struct Rsdp {
+    revision: u8,
+    length: usize,
+    rsdt_paddr: u32,
+    xsdt_paddr: u64,
+}
+
+impl Rsdp {
+    /// Validates the RSDP at physical address `paddr`.
+    ///
+    /// # Safety
+    ///
+    /// `paddr` must be mapped in the linear mapping.
+    pub unsafe fn from_paddr(paddr: usize) -> Result<Self, AcpiError> {
+        let ptr = phys_to_virt(paddr) as *const u8;
+        let v1 = unsafe { core::slice::from_raw_parts(ptr, RSDP_V1_SIZE) };
+        if &v1[..8] != RSDP_SIGNATURE {
+            return Err(AcpiError::BadRsdpSignature);
+        }
+        if !checksum_ok(v1) {
+            return Err(AcpiError::BadChecksum(*b"RSDP"));
+        }
+        let revision = v1[15];
+        let rsdt_paddr = read_u32(v1, 16);
+        let mut length = RSDP_V1_SIZE;
+        let mut xsdt_paddr = 0;
+        if revision >= 2 {
+            let v2 = unsafe { core::slice::from_raw_parts(ptr, RSDP_V2_SIZE) };
+            length = (read_u32(v2, 20) as usize).max(RSDP_V2_SIZE);
+            let full = unsafe { core::slice::from_raw_parts(ptr, length) };
+            if !checksum_ok(full) {
+                return Err(AcpiError::BadChecksum(*b"RSDP"));
+            }
+            xsdt_paddr = read_u64(v2, 24);
+        }
+        Ok(Self {
+            revision,
+            length,
+            rsdt_paddr,
+            xsdt_paddr,
+        })
+    }
+
+    /// Returns the ACPI revision of the RSDP.
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    /// Returns the size of the RSDP in bytes.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Returns the root table: the XSDT if present, otherwise the RSDT.
+    fn root_table(&self) -> Result<Sdt, AcpiError> {
+        if self.xsdt_paddr != 0 {
+            unsafe { Sdt::from_paddr(self.xsdt_paddr as usize) }
+        } else {
+            unsafe { Sdt::from_paddr(self.rsdt_paddr as usize) }
+        }
+    }
+
+    /// Iterates over the physical addresses of the tables listed in the root table.
+    pub fn table_addresses(&self) -> impl Iterator<Item = usize> {
+        let root = self.root_table().ok();
+        let entry_size = if self.xsdt_paddr != 0 { 8 } else { 4 };
+        let entries = root.map_or(&[][..], |root| &root.bytes()[SDT_HEADER_SIZE..]);
+        entries.chunks_exact(entry_size).map(move |entry| match entry_size {
+            8 => read_u64(entry, 0) as usize,
+            _ => read_u32(entry, 0) as usize,
+        })
+    }
+
+    /// Finds the first valid table with the given signature.
+    pub fn find_table(&self, signature: &[u8; 4]) -> Option<Sdt> {
+        self.table_addresses()
+            .filter_map(|paddr| unsafe { Sdt::from_paddr(paddr) }.ok())
+            .find(|table| table.signature() == signature)
+    }
+}
+
+/// A checksum-validated system description table.
+#[derive(Debug, Clone, Copy)]
+pub struct Sdt {
+    bytes: &'static [u8],
+}
+
+impl Sdt {
+    /// Validates the table at physical address `paddr`.
+    ///
+    /// # Safety
+    ///
+    /// `paddr` must be mapped in the linear mapping.
+    pub unsafe fn from_paddr(paddr: usize) -> Result<Self, AcpiError> {
+        let ptr = phys_to_virt(paddr) as *const u8;
+        let header = unsafe { core::slice::from_raw_parts(ptr, SDT_HEADER_SIZE) };
+        let signature = header[..4].try_into().unwrap();
+        let length = read_u32(header, 4) as usize;
+        if length < SDT_HEADER_SIZE {
+            return Err(AcpiError::BadLength(signature));
+        }
+        let bytes = unsafe { core::slice::from_raw_parts(ptr, length) };
+        if !checksum_ok(bytes) {
+            return Err(AcpiError::BadChecksum(signature));
+        }
+        Ok(Self { bytes })
+    }
+
+    /// Returns the 4-byte table signature.
+    pub fn signature(&self) -> &[u8; 4] {
+        self.bytes[..4].try_into().unwrap()
+    }
+
+    /// Returns the table revision.
+    pub fn revision(&self) -> u8 {
+        self.bytes[8]
+    }
+
+    /// Returns the whole table, header included.
+    pub fn bytes(&self) -> &'static [u8] {
+        self.bytes
+    }
+
+    /// Returns the physical memory taken by the table.
+    pub fn region(&self) -> MemRegion {
+        MemRegion::new(virt_to_phys(self.bytes.as_ptr() as usize), self.bytes.len())
+    }
+}
+
+static RSDP: LazyInit<Rsdp> = LazyInit::new();
+/// Memory taken by the firmware tables, recorded by [`init`].
+static TABLE_REGIONS: LazyInit<MemRegionList<MAX_TABLE_REGIONS>> = LazyInit::new();
+
+kparam! {
+    /// Physical address of the RSDP, overriding the UEFI configuration table.
+    "acpi_rsdp" => static RSDP_PARAM: Option<usize> = None;
+}
+
+/// Where the RSDP address came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RsdpSource {
+    CommandLine,
+    Uefi,
+    Config,
+}
+
+impl fmt::Display for RsdpSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CommandLine => "command line",
+            Self::Uefi => "UEFI configuration table",
+            Self::Config => "build configuration",
+        })
+    }
+}
+
+/// Finds the physical address of the RSDP.
+///
+/// Must be called after the command line has been parsed.
+pub fn locate_rsdp() -> Option<(usize, RsdpSource)> {
+    if let Some(paddr) = RSDP_PARAM.get() {
+        return Some((paddr, RsdpSource::CommandLine));
+    }
+    if let Some(system_table) = efi::system_table_paddr()
+        && let Some(paddr) = unsafe { efi::find_rsdp(system_table) }
+    {
+        return Some((paddr, RsdpSource::Uefi));
+    }
+    ACPI_RSDP_PADDR.map(|paddr| (paddr, RsdpSource::Config))
+}
+
+/// Validates the RSDP at physical address `paddr` and makes the ACPI tables
+/// available through [`find_table`].
+///
+/// The tables listed in the root table are reported on the console, along
+/// with those failing validation. The memory taken by the RSDP, the root
+/// table, the tables it lists and the UEFI system table is recorded for
+/// [`table_regions`], as the tables are read again after the allocators are
+/// initialized.
+pub fn init(paddr: usize) -> Result<(), AcpiError> {
+    let rsdp = unsafe { Rsdp::from_paddr(paddr)? };
+    early_println!("ACPI: RSDP at {:#x}, revision {}", paddr, rsdp.revision());
+    let mut regions = MemRegionList::new();
+    let mut complete = regions.add(MemRegion::new(paddr, rsdp.length()));
+    if let Ok(root) = rsdp.root_table() {
+        complete &= regions.add(root.region());
+    }
+    for table in rsdp.table_addresses() {
+        match unsafe { Sdt::from_paddr(table) } {
+            Ok(sdt) => {
+                early_println!(
+                    "ACPI: {} at {:#x}, revision {}",
+                    core::str::from_utf8(sdt.signature()).unwrap_or("????"),
+                    table,
+                    sdt.revision()
+                );
+                complete &= regions.add(sdt.region());
+            }
+            Err(err) => {
+                early_println!("ACPI: ignoring table at {:#x}: {:?}", table, err);
+                // 保留表头，之后查找表时仍会读取它
+                complete &= regions.add(MemRegion::new(table, SDT_HEADER_SIZE));
+            }
+        }
+    }
+    if let Some(system_table) = efi::system_table_paddr()
+        && let Some(efi_regions) = unsafe { efi::table_regions(system_table) }
+    {
+        for region in efi_regions {
+            complete &= regions.add(region);
+        }
+    }
+    if !complete {
+        early_println!("ACPI: too many tables, some may be overwritten by allocations");
+    }
+    TABLE_REGIONS.init_once(regions);
+    RSDP.init_once(rsdp);
+    Ok(())
+}
+
+/// Returns the physical memory taken by the firmware tables, empty without
+/// ACPI.
+pub fn table_regions() -> impl Iterator<Item = &'static MemRegion> {
+    TABLE_REGIONS
+        .get()
+        .into_iter()
+        .flat_map(MemRegionList::iter)
+}
+
+/// Returns whether the system was booted with ACPI tables.
+pub fn is_present() -> bool {
+    RSDP.is_inited()
+}
+
+/// Finds the first valid table with the given signature.
+pub fn find_table(signature: &[u8; 4]) -> Option<Sdt> {
+    RSDP.get()?.find_table(signature)
+}
+
+/// Size of a GICv2 distributor and CPU interface, and of a GICv3 distributor.
+const GICV2_GICD_SIZE: u64 = 0x1000;
+const GICV2_GICC_SIZE: u64 = 0x2000;
+const GICV3_GICD_SIZE: u64 = 0x1_0000;
+/// Size of the redistributor of one processor: 2 frames, or 4 on a GICv4.
+const GICV3_GICR_SIZE: u64 = 0x2_0000;
+const GICV4_GICR_SIZE: u64 = 0x4_0000;
+const GIC_ITS_SIZE: u64 = 0x2_0000;
+const GIC_MSI_FRAME_SIZE: u64 = 0x1000;
+/// Size of an SBSA watchdog frame, and of the SBSA UART and 16550 register
+/// window.
+const FRAME_SIZE: u64 = 0x1000;
+
+/// Adds the GIC described by the MADT, with its ITSs and MSI frames.
+fn register_gic() {
+    let Some(madt) = madt::get() else {
+        return;
+    };
+    let Some((gicd, version)) = madt.gicd() else {
+        return;
+    };
+    let source = DeviceSource::Acpi(*b"APIC");
+    let (compatible, regs) = if version >= 3 {
+        let gicr_size = if version >= 4 {
+            GICV4_GICR_SIZE
+        } else {
+            GICV3_GICR_SIZE
+        };
+        // 优先使用 GICR 结构给出的连续区域，否则使用每个 CPU 的 GICR 地址
+        let mut regs = vec![(gicd, GICV3_GICD_SIZE)];
+        regs.extend(madt.entries().filter_map(|entry| match entry {
+            MadtEntry::Gicr { base, length } => Some((base, length as u64)),
+            _ => None,
+        }));
+        if regs.len() == 1 {
+            regs.extend(
+                madt.cpus()
+                    .filter(|cpu| cpu.gicr_base != 0)
+                    .map(|cpu| (cpu.gicr_base, gicr_size)),
+            );
+        }
+        (vec!["arm,gic-v3"], regs)
+    } else {
+        let mut regs = vec![(gicd, GICV2_GICD_SIZE)];
+        regs.extend(
+            madt.cpus()
+                .next()
+                .map(|cpu| (cpu.gicc_base, GICV2_GICC_SIZE)),
+        );
+        (vec!["arm,gic-400", "arm,cortex-a15-gic"], regs)
+    };
+    let maintenance = madt.cpus().next().map(|cpu| cpu.vgic_maintenance_gsiv);
+    registry::register(Device {
+        name: "interrupt-controller",
+        compatible,
+        regs,
+        irqs: maintenance.into_iter().filter(|&gsiv| gsiv != 0).collect(),
+        source,
+    });
+
+    for entry in madt.entries() {
+        let (name, compatible, base, size) = match entry {
+            MadtEntry::GicIts { base, .. } => {
+                ("msi-controller", "arm,gic-v3-its", base, GIC_ITS_SIZE)
+            }
+            MadtEntry::GicMsiFrame { base } => {
+                ("v2m", "arm,gic-v2m-frame", base, GIC_MSI_FRAME_SIZE)
+            }
+            _ => continue,
+        };
+        registry::register(Device {
+            name,
+            compatible: vec![compatible],
+            regs: vec![(base, size)],
+            irqs: Vec::new(),
+            source,
+        });
+    }
+}
+
+/// Adds the architected timer and the SBSA watchdogs described by the GTDT.
+fn register_timers() {
+    let Some(gtdt) = gtdt::get() else {
+        return;
+    };
+    let source = DeviceSource::Acpi(*b"GTDT");
+    // 与设备树 arm,armv8-timer 的 interrupts 顺序相同，不存在的定时器记为 0
+    let irqs = [
+        gtdt.secure_el1,
+        gtdt.non_secure_el1,
+        gtdt.virtual_el1,
+        gtdt.el2,
+    ]
+    .into_iter()
+    .map(|timer| timer.map_or(0, |timer| timer.gsiv))
+    .collect();
+    registry::register(Device {
+        name: "timer",
+        compatible: vec!["arm,armv8-timer"],
+        regs: Vec::new(),
+        irqs,
+        source,
+    });
+    for watchdog in gtdt.watchdogs() {
+        registry::register(Device {
+            name: "watchdog",
+            compatible: vec!["arm,sbsa-gwdt"],
+            regs: vec![
+                (watchdog.control_frame, FRAME_SIZE),
+                (watchdog.refresh_frame, FRAME_SIZE),
+            ],
+            irqs: vec![watchdog.gsiv],
+            source,
+        });
+    }
+}
+
+/// Adds the console UART described by the SPCR.
+fn register_console() {
+    let Some(spcr) = spcr::get() else {
+        return;
+    };
+    let compatible = match spcr.interface_type {
+        InterfaceType::Pl011 => vec!["arm,pl011", "arm,primecell"],
+        InterfaceType::Ns16550 => vec!["ns16550a"],
+        InterfaceType::Other(_) => return,
+    };
+    registry::register(Device {
+        name: "serial",
+        compatible,
+        regs: vec![(spcr.base_paddr, FRAME_SIZE)],
+        irqs: spcr.gsiv.into_iter().collect(),
+        source: DeviceSource::Acpi(*b"SPCR"),
+    });
+}
+
+/// Adds a generic ECAM PCI host bridge for each segment of the MCFG.
+fn register_pci() {
+    let Some(mcfg) = mcfg::get() else {
+        return;
+    };
+    for ecam in mcfg.ecams() {
+        registry::register(Device {
+            name: "pcie",
+            compatible: vec!["pci-host-ecam-generic"],
+            regs: vec![ecam.region()],
+            irqs: Vec::new(),
+            source: DeviceSource::Acpi(*b"MCFG"),
+        });
+    }
+}
+
+/// Adds the devices described by the static ACPI tables to the device
+/// registry, under the device tree binding of the same hardware.
+pub fn register_devices() {
+    if !is_present() {
+        return;
+    }
+    if let Some(fadt) = fadt::get() {
+        info!(
+            "ACPI: version {}.{}{}, PSCI {}",
+            fadt.version.0,
+            fadt.version.1,
+            if fadt.hw_reduced {
+                ", hardware-reduced"
+            } else {
+                ""
+            },
+            match fadt.psci {
+                Some(fadt::PsciConduit::Smc) => "via SMC",
+                Some(fadt::PsciConduit::Hvc) => "via HVC",
+                None => "not supported",
+            }
+        );
+    }
+    register_gic();
+    register_timers();
+    register_console();
+    register_pci();
+}
@@ -0,0 +1,103 @@
+//! Serial Port Console Redirection table (SPCR).
+
+use super::{Sdt, read_u32, read_u64};
+
+/// Offsets of SPCR fields.
+const INTERFACE_TYPE: usize = 36;
+const BASE_ADDRESS: usize = 40;
+const INTERRUPT_TYPE: usize = 52;
+const GSIV: usize = 54;
+const BAUD_RATE: usize = 58;
+const UART_CLOCK_FREQ: usize = 76;
+/// Minimum length of a revision 1 table.
+const SPCR_MIN_LENGTH: usize = 80;
+
+/// Generic Address Structure: `AddressSpaceId` of system memory.
+const GAS_SYSTEM_MEMORY: u8 = 0;
+
+/// Serial interface types defined by the ACPI debug port table specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterfaceType {
+    /// Full or subset 16550, or 16550-compatible with parameters from the GAS.
+    Ns16550,
+    /// ARM PL011 or SBSA generic UART.
+    Pl011,
+    /// Any other interface, identified by its raw type code.
+    Other(u8),
+}
+
+impl InterfaceType {
+    fn from_raw(raw: u8) -> Self {
+        match raw {
+            0x00 | 0x01 | 0x12 => Self::Ns16550,
+            0x03 | 0x0d | 0x0e => Self::Pl011,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// The console described by the SPCR table.
+#[derive(Debug, Clone, Copy)]
+pub struct Spcr {
+    pub interface_type: InterfaceType,
+    /// Physical base address of the UART registers.
+    pub base_paddr: u64,
+    /// Register width in bits, from the generic address structure.
+    pub register_bit_width: u8,
+    /// Register access size in bytes, if specified.
+    pub access_size: Option<u32>,
+    /// Global system interrupt, if the UART has one.
+    pub gsiv: Option<u32>,
+    /// Baud rate, or `None` if the firmware asks to keep the current setting.
+    pub baud_rate: Option<u32>,
+    /// UART input clock in Hz (revision 3 and later).
+    pub clock_hz: Option<u32>,
+}
+
+impl Spcr {
+    /// Parses an SPCR table. Returns `None` if the table is too short or the
+    /// console is not memory-mapped.
+    pub fn parse(table: &Sdt) -> Option<Self> {
+        let bytes = table.bytes();
+        if table.signature() != b"SPCR" || bytes.len() < SPCR_MIN_LENGTH {
+            return None;
+        }
+        if bytes[BASE_ADDRESS] != GAS_SYSTEM_MEMORY {
+            return None;
+        }
+        let access_size = match bytes[BASE_ADDRESS + 3] {
+            1 => Some(1),
+            2 => Some(2),
+            3 => Some(4),
+            4 => Some(8),
+            _ => None,
+        };
+        let baud_rate = match bytes[BAUD_RATE] {
+            3 => Some(9600),
+            4 => Some(19200),
+            6 => Some(57600),
+            7 => Some(115200),
+            _ => None,
+        };
+        let clock_hz = (table.revision() >= 3 && bytes.len() >= UART_CLOCK_FREQ + 4)
+            .then(|| read_u32(bytes, UART_CLOCK_FREQ))
+            .filter(|&clock| clock != 0);
+        // 第 3 位表示 ARM GIC 中断
+        let gsiv = (bytes[INTERRUPT_TYPE] & (1 << 3) != 0).then(|| read_u32(bytes, GSIV));
+
+        Some(Self {
+            interface_type: InterfaceType::from_raw(bytes[INTERFACE_TYPE]),
+            base_paddr: read_u64(bytes, BASE_ADDRESS + 4),
+            register_bit_width: bytes[BASE_ADDRESS + 1],
+            access_size,
+            gsiv,
+            baud_rate,
+            clock_hz,
+        })
+    }
+}
+
+/// Finds and parses the SPCR table.
+pub fn get() -> Option<Spcr> {
+    Spcr::parse(&super::find_table(b"SPCR")?)
+}
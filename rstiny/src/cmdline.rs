@@ -0,0 +1,275 @@
+//! Kernel command line.
+//!
+//! The command line is taken from `/chosen/bootargs` in the device tree or,
+//! without one, from the NUL-terminated string left by the UEFI loader at
+//! [`CMDLINE_PADDR`]. It is a whitespace-separated list of `name=value` (or
+//! bare `name`) parameters.
+//!
+//! Modules declare their parameters with [`kparam!`], next to the code using
+//! them, with a type and a default:
+//!
+//! ```ignore
+//! kparam! {
+//!     /// Maximum size of the heap, e.g. `heap=64M`.
+//!     "heap" => pub static HEAP_SIZE: Size = Size(HEAP_MAX_SIZE);
+//! }
+//!
+//! let size = HEAP_SIZE.get().0;
+//! ```
+//!
+//! The declarations are collected in the `.kparam` linker section, and
+//! [`init`] parses the command line against them before the console is
+//! probed. Unknown parameters and invalid values are reported on the console;
+//! an invalid value leaves the default in place. If a parameter is given
+//! several times, the last value wins.
+
+use core::fmt;
+
+use kspin::SpinNoIrq;
+use spin::Once;
+
+use crate::config::{CMDLINE_MAX_LEN, CMDLINE_PADDR};
+use crate::fdt;
+use crate::utils::mem::phys_to_virt;
+
+/// A value that can be given on the command line.
+pub trait ParamValue: Sized + Copy + Send + Sync + 'static {
+    /// Parses the text after `name=`, empty for a bare `name`.
+    fn parse(value: &'static str) -> Result<Self, &'static str>;
+}
+
+/// A typed parameter, declared with [`kparam!`].
+pub struct Param<T> {
+    default: T,
+    value: SpinNoIrq<Option<T>>,
+}
+
+impl<T: ParamValue> Param<T> {
+    pub const fn new(default: T) -> Self {
+        Self {
+            default,
+            value: SpinNoIrq::new(None),
+        }
+    }
+
+    /// Returns the value from the command line, or the default.
+    pub fn get(&self) -> T {
+        self.value.lock().unwrap_or(self.default)
+    }
+
+    /// Returns whether the parameter was given on the command line.
+    pub fn is_set(&self) -> bool {
+        self.value.lock().is_some()
+    }
+
+    #[doc(hidden)]
+    pub fn set(&self, value: &'static str) -> Result<(), &'static str> {
+        *self.value.lock() = Some(T::parse(value)?);
+        Ok(())
+    }
+}
+
+/// Entry of the `.kparam` section, generated by [`kparam!`].
+pub struct ParamDesc {
+    pub name: &'static str,
+    pub set: fn(&'static str) -> Result<(), &'static str>,
+}
+
+/// Declares a command line parameter.
+///
+/// `"name" => static IDENT: Type = default;` defines a [`Param`] read with
+/// `IDENT.get()`, and registers it under `name`. `Type` must implement
+/// [`ParamValue`].
+#[macro_export]
+macro_rules! kparam {
+    ($(#[$attr:meta])* $name:literal => $vis:vis static $ident:ident: $ty:ty = $default:expr;) => {
+        $(#[$attr])*
+        $vis static $ident: $crate::cmdline::Param<$ty> = $crate::cmdline::Param::new($default);
+
+        const _: () = {
+            fn set(value: &'static str) -> Result<(), &'static str> {
+                $ident.set(value)
+            }
+
+            #[used]
+            #[unsafe(link_section = ".kparam")]
+            static DESC: $crate::cmdline::ParamDesc = $crate::cmdline::ParamDesc { name: $name, set };
+        };
+    };
+}
+
+unsafe extern "C" {
+    static __kparam_start: ParamDesc;
+    static __kparam_end: ParamDesc;
+}
+
+/// Returns every declared parameter, in link order.
+fn registered_params() -> &'static [ParamDesc] {
+    let start = &raw const __kparam_start;
+    let end = &raw const __kparam_end;
+    let len = (end as usize - start as usize) / size_of::<ParamDesc>();
+    unsafe { core::slice::from_raw_parts(start, len) }
+}
+
+/// Where the command line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmdlineSource {
+    DeviceTree,
+    Uefi,
+}
+
+impl fmt::Display for CmdlineSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::DeviceTree => "device tree",
+            Self::Uefi => "UEFI loader",
+        })
+    }
+}
+
+static CMDLINE: Once<&'static str> = Once::new();
+
+/// Finds the command line.
+fn locate() -> Option<(&'static str, CmdlineSource)> {
+    if let Some(bootargs) = fdt::get().and_then(|fdt| fdt.bootargs()) {
+        return Some((bootargs, CmdlineSource::DeviceTree));
+    }
+    let paddr = CMDLINE_PADDR?;
+    let bytes =
+        unsafe { core::slice::from_raw_parts(phys_to_virt(paddr) as *const u8, CMDLINE_MAX_LEN) };
+    let len = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(CMDLINE_MAX_LEN);
+    match core::str::from_utf8(&bytes[..len]) {
+        Ok(cmdline) => Some((cmdline, CmdlineSource::Uefi)),
+        Err(_) => {
+            early_println!("cmdline: command line at {paddr:#x} is not UTF-8");
+            None
+        }
+    }
+}
+
+/// A parameter of the command line that could not be applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamError {
+    /// No parameter is declared under this name. Holds the whole token.
+    Unknown(&'static str),
+    /// The value was rejected by the parser of the parameter.
+    Invalid {
+        name: &'static str,
+        value: &'static str,
+        err: &'static str,
+    },
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(token) => write!(f, "unknown parameter `{token}`"),
+            Self::Invalid { name, value, err } => {
+                write!(f, "{name}: {err} (`{value}`), using the default")
+            }
+        }
+    }
+}
+
+/// Sets `params` from `cmdline`, passing every token that cannot be applied
+/// to `report`.
+pub fn apply(cmdline: &'static str, params: &[ParamDesc], mut report: impl FnMut(ParamError)) {
+    for token in cmdline.split_whitespace() {
+        let (name, value) = token.split_once('=').unwrap_or((token, ""));
+        match params.iter().find(|param| param.name == name) {
+            Some(param) => {
+                if let Err(err) = (param.set)(value) {
+                    report(ParamError::Invalid {
+                        name: param.name,
+                        value,
+                        err,
+                    });
+                }
+            }
+            None => report(ParamError::Unknown(token)),
+        }
+    }
+}
+
+/// Locates the command line and sets the declared parameters from it.
+///
+/// Must be called after the device tree has been located, and before the
+/// modules read their parameters.
+pub fn init() {
+    let Some((cmdline, source)) = locate() else {
+        return;
+    };
+    CMDLINE.call_once(|| cmdline);
+    early_println!("cmdline: `{}` (from {})", cmdline, source);
+
+    // 堆尚未初始化，逐个报告而不收集
+    apply(cmdline, registered_params(), |err| {
+        early_println!("cmdline: {}", err)
+    });
+}
+
+/// Returns the whole command line, if any.
+pub fn get() -> Option<&'static str> {
+    CMDLINE.get().copied()
+}
+
+/// A size in bytes, with an optional `K`, `M` or `G` suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size(pub usize);
+
+impl ParamValue for &'static str {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        Ok(value)
+    }
+}
+
+impl ParamValue for bool {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        match value {
+            "" | "1" | "y" | "yes" | "on" | "true" => Ok(true),
+            "0" | "n" | "no" | "off" | "false" => Ok(false),
+            _ => Err("expected a boolean"),
+        }
+    }
+}
+
+impl ParamValue for u64 {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .map_err(|_| "expected an integer")
+    }
+}
+
+impl ParamValue for usize {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        <u64 as ParamValue>::parse(value).map(|v| v as usize)
+    }
+}
+
+impl ParamValue for Size {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        let (digits, shift) = match value.as_bytes().last() {
+            Some(b'K' | b'k') => (&value[..value.len() - 1], 10),
+            Some(b'M' | b'm') => (&value[..value.len() - 1], 20),
+            Some(b'G' | b'g') => (&value[..value.len() - 1], 30),
+            _ => (value, 0),
+        };
+        let n = <usize as ParamValue>::parse(digits).map_err(|_| "expected a size, e.g. 64M")?;
+        n.checked_mul(1 << shift)
+            .map(Size)
+            .ok_or("size out of range")
+    }
+}
+
+/// Present on the command line with the given value, or absent.
+impl<T: ParamValue> ParamValue for Option<T> {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        T::parse(value).map(Some)
+    }
+}
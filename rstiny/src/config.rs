@@ -1 +1,54 @@
-pub const HEAP_ALLOCATOR_SIZE: usize = 0x1000000; // 16MB
+// 堆的静态引导区大小，其余堆内存在启动后从空闲 RAM 中获取
+pub const HEAP_BOOTSTRAP_SIZE: usize = 0x10_0000; // 1MB
+
+// 堆最多使用的空闲 RAM（从低地址开始），其余交给物理页帧分配器
+pub const HEAP_MAX_SIZE: usize = 0x200_0000; // 32MB
+
+// 没有设备树时使用的物理内存范围，与 .axconfig.toml 中的 `phys-memory-*` 保持一致
+#[cfg(not(feature = "plat-qemu-virt"))]
+pub const PHYS_MEMORY_BASE: usize = 0x8000_0000;
+#[cfg(feature = "plat-qemu-virt")]
+pub const PHYS_MEMORY_BASE: usize = 0x4000_0000;
+pub const PHYS_MEMORY_SIZE: usize = 0x800_0000; // 128MB
+
+// 启动页表线性映射覆盖的物理地址上限，超出部分不能用作堆
+pub const LINEAR_MAP_LIMIT: usize = 0x1_0000_0000; // 4GB
+
+// 线性映射偏移，与 .axconfig.toml 中的 `phys-virt-offset` 保持一致
+pub const PHYS_VIRT_OFFSET: usize = 0xffff_0000_0000_0000;
+
+// 默认控制台 UART 物理地址，与 .axconfig.toml 中的 `uart-paddr` 保持一致。
+// 只在设备树和 ACPI SPCR 都没有给出控制台时使用。
+#[cfg(not(feature = "plat-qemu-virt"))]
+pub const UART_PADDR: usize = 0x1800_2000;
+// QEMU virt 的 PL011
+#[cfg(feature = "plat-qemu-virt")]
+pub const UART_PADDR: usize = 0x0900_0000;
+
+// 8250 UART 寄存器布局（DesignWare APB UART: 32 位间隔，32 位访问）
+pub const UART_REG_SHIFT: u32 = 2;
+pub const UART_REG_IO_WIDTH: u32 = 4;
+
+// 早期控制台 UART 的虚拟地址（启动页表已映射），None 表示只记录不输出
+pub const EARLY_CONSOLE_BASE: Option<usize> = Some(UART_PADDR + PHYS_VIRT_OFFSET);
+
+// UEFI 引导程序放置的内核命令行（以 NUL 结尾）物理地址，设备树中有 bootargs 时不使用
+pub const CMDLINE_PADDR: Option<usize> = None;
+// 命令行的最大长度（字节）
+pub const CMDLINE_MAX_LEN: usize = 4096;
+
+// ACPI RSDP 物理地址，命令行 acpi_rsdp= 和 UEFI 配置表都没有给出时使用
+pub const ACPI_RSDP_PADDR: Option<usize> = None;
+// UEFI 系统表物理地址，设备树 /chosen 中有 linux,uefi-system-table 时不使用
+pub const EFI_SYSTEM_TABLE_PADDR: Option<usize> = None;
+
+// 每个 cluster 的核数，用于由 MPIDR_EL1 计算逻辑 CPU 编号
+pub const CORES_PER_CLUSTER: usize = 4;
+
+// 持久化崩溃日志区域（物理内存最高 1MB），设备树中有 ramoops 保留内存节点时以其为准。
+// PSTORE_SIZE 为 None 表示禁用。
+pub const PSTORE_PADDR: usize = PHYS_MEMORY_BASE + PHYS_MEMORY_SIZE - 0x10_0000;
+pub const PSTORE_SIZE: Option<usize> = Some(0x10_0000);
+
+// 支持的最大 CPU 数（每 CPU 数据结构的大小）
+pub const MAX_CPUS: usize = 8;
@@ -0,0 +1,117 @@
+//! Early console, usable from the first instruction of `rust_main`.
+//!
+//! Output goes straight to the UART at [`EARLY_CONSOLE_BASE`] (a fixed virtual
+//! address covered by the boot page table) with a polling driver: no
+//! allocation, no locks and no interrupts are involved, so it works before
+//! the platform is initialized. Concurrent writers from several CPUs may
+//! interleave their output.
+//!
+//! Every message is also recorded in a static buffer, so it can be replayed
+//! once the full console takes over (see [`super::init`]).
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::config::{EARLY_CONSOLE_BASE, UART_REG_IO_WIDTH, UART_REG_SHIFT};
+use crate::drivers::serial::{SerialPort, Uart, UartConfig};
+
+/// Size of the buffer holding early messages.
+const EARLY_BUF_SIZE: usize = 16 * 1024;
+
+/// Append-only buffer of early output.
+///
+/// Writers reserve space by bumping `len`, so no lock is needed. Bytes that do
+/// not fit are counted in `dropped`.
+struct EarlyBuffer {
+    data: UnsafeCell<[u8; EARLY_BUF_SIZE]>,
+    len: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+// SAFETY: 每个写者通过原子地推进 `len` 获得互不重叠的区间
+unsafe impl Sync for EarlyBuffer {}
+
+static EARLY_BUF: EarlyBuffer = EarlyBuffer {
+    data: UnsafeCell::new([0; EARLY_BUF_SIZE]),
+    len: AtomicUsize::new(0),
+    dropped: AtomicUsize::new(0),
+};
+
+impl EarlyBuffer {
+    fn push(&self, bytes: &[u8]) {
+        let start = self.len.fetch_add(bytes.len(), Ordering::Relaxed);
+        if start >= EARLY_BUF_SIZE {
+            self.dropped.fetch_add(bytes.len(), Ordering::Relaxed);
+            return;
+        }
+        let n = bytes.len().min(EARLY_BUF_SIZE - start);
+        unsafe {
+            let dst = (self.data.get() as *mut u8).add(start);
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), dst, n);
+        }
+        self.dropped.fetch_add(bytes.len() - n, Ordering::Relaxed);
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        let len = self.len.load(Ordering::Acquire).min(EARLY_BUF_SIZE);
+        unsafe { &(*self.data.get())[..len] }
+    }
+}
+
+struct EarlyWriter;
+
+impl Write for EarlyWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+fn early_uart() -> Option<Uart> {
+    EARLY_CONSOLE_BASE.map(|base| {
+        Uart::new_default(
+            UartConfig::new(base)
+                .reg_shift(UART_REG_SHIFT)
+                .reg_io_width(UART_REG_IO_WIDTH),
+        )
+    })
+}
+
+/// Writes raw bytes to the early console and records them.
+pub fn write_bytes(bytes: &[u8]) {
+    EARLY_BUF.push(bytes);
+    if let Some(mut uart) = early_uart() {
+        uart.write_bytes(bytes);
+    }
+}
+
+/// Writes raw bytes to the early UART, without newline translation or
+/// recording. Used for binary log frames.
+pub fn write_raw(bytes: &[u8]) {
+    if let Some(mut uart) = early_uart() {
+        for &b in bytes {
+            uart.putc(b);
+        }
+    }
+}
+
+/// Writes formatted output to the early console.
+pub fn write_fmt(args: fmt::Arguments) {
+    EarlyWriter.write_fmt(args).unwrap();
+}
+
+/// Returns whether early output was directly visible on a UART.
+pub fn has_uart() -> bool {
+    EARLY_CONSOLE_BASE.is_some()
+}
+
+/// Returns the recorded early output.
+pub fn messages() -> &'static [u8] {
+    EARLY_BUF.as_bytes()
+}
+
+/// Returns the number of bytes that did not fit in the early buffer.
+pub fn dropped_bytes() -> usize {
+    EARLY_BUF.dropped.load(Ordering::Relaxed)
+}
@@ -0,0 +1,191 @@
+//! Kernel console.
+//!
+//! Until [`init`] is called, output goes to the [early console](early). After
+//! platform initialization, [`init`] discovers the console UART (see
+//! [`probe`]) and hands over to it, replaying the early messages if they were
+//! not already visible on that UART. Once booting is done, the [`shell`]
+//! reads commands from it.
+
+pub mod early;
+pub mod probe;
+pub mod shell;
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kspin::SpinNoIrq;
+use lazyinit::LazyInit;
+
+use crate::config::EARLY_CONSOLE_BASE;
+use crate::drivers::serial::{SerialPort, Uart};
+
+static CONSOLE_READY: AtomicBool = AtomicBool::new(false);
+static CONSOLE_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+static CONSOLE_UART: LazyInit<Uart> = LazyInit::new();
+
+/// Writer over the console UART. The driver only holds register addresses, so
+/// each writer works on its own copy.
+struct FullConsole(Uart);
+
+impl FullConsole {
+    fn new() -> Self {
+        Self((*CONSOLE_UART).clone())
+    }
+}
+
+impl Write for FullConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Switches from the early console to the discovered console UART.
+///
+/// Must be called after the device tree and ACPI tables have been located.
+pub fn init() {
+    let desc = probe::probe();
+    let mut uart = desc.uart;
+    uart.init();
+    let line_result = match (desc.line, desc.clock_hz) {
+        (Some(line), Some(clock_hz)) => uart.set_line(clock_hz, &line),
+        _ => Ok(()),
+    };
+    CONSOLE_UART.init_once(uart);
+
+    let _guard = CONSOLE_LOCK.lock();
+    let mut console = FullConsole::new();
+    if EARLY_CONSOLE_BASE != Some(CONSOLE_UART.base()) {
+        console.0.write_bytes(early::messages());
+    }
+    let dropped = early::dropped_bytes();
+    if dropped > 0 {
+        console
+            .write_fmt(format_args!("[early console: {dropped} bytes dropped]\n"))
+            .unwrap();
+    }
+    console
+        .write_fmt(format_args!(
+            "console: UART at {:#x} (from {})\n",
+            desc.paddr, desc.source
+        ))
+        .unwrap();
+    if let Err(err) = line_result {
+        // 保留固件设置的波特率
+        console
+            .write_fmt(format_args!("console: line settings ignored: {err}\n"))
+            .unwrap();
+    }
+    CONSOLE_READY.store(true, Ordering::Release);
+}
+
+/// Returns whether the full console has taken over.
+pub fn is_ready() -> bool {
+    CONSOLE_READY.load(Ordering::Acquire)
+}
+
+/// Prints formatted output to the current console.
+pub fn _print(args: fmt::Arguments) {
+    if is_ready() {
+        let _guard = CONSOLE_LOCK.lock();
+        FullConsole::new().write_fmt(args).unwrap();
+    } else {
+        early::write_fmt(args);
+    }
+}
+
+/// Prints formatted output unless the console lock is taken, e.g. by the
+/// context this one interrupted. Returns whether the output was printed.
+pub fn try_print(args: fmt::Arguments) -> bool {
+    if !is_ready() {
+        early::write_fmt(args);
+        return true;
+    }
+    let Some(_guard) = CONSOLE_LOCK.try_lock() else {
+        return false;
+    };
+    FullConsole::new().write_fmt(args).unwrap();
+    true
+}
+
+/// Writes raw bytes to the console, without newline translation.
+///
+/// Bytes written before the full console is ready go to the early UART.
+pub fn write_raw(bytes: &[u8]) {
+    if is_ready() {
+        let _guard = CONSOLE_LOCK.lock();
+        let mut console = FullConsole::new();
+        for &b in bytes {
+            console.0.putc(b);
+        }
+    } else {
+        early::write_raw(bytes);
+    }
+}
+
+/// Reads a byte from the console UART if one is available.
+///
+/// Returns `None` until the full console has taken over, since the early
+/// console is output only.
+pub fn getc() -> Option<u8> {
+    if !is_ready() {
+        return None;
+    }
+    let _guard = CONSOLE_LOCK.lock();
+    FullConsole::new().0.getc()
+}
+
+/// Same as [`write_raw`], unless the console lock is taken. Returns whether
+/// the bytes were written.
+pub fn try_write_raw(bytes: &[u8]) -> bool {
+    if !is_ready() {
+        early::write_raw(bytes);
+        return true;
+    }
+    let Some(_guard) = CONSOLE_LOCK.try_lock() else {
+        return false;
+    };
+    let mut console = FullConsole::new();
+    for &b in bytes {
+        console.0.putc(b);
+    }
+    true
+}
+
+/// Prints formatted output without taking the console lock.
+///
+/// Used by the panic handler, where the lock may be held by the panicking
+/// context.
+pub fn emergency_print(args: fmt::Arguments) {
+    if is_ready() {
+        FullConsole::new().write_fmt(args).unwrap();
+    } else {
+        early::write_fmt(args);
+    }
+}
+
+/// Prints to the early console, without allocation or locking.
+#[macro_export]
+macro_rules! early_print {
+    ($($arg:tt)*) => ($crate::console::early::write_fmt(format_args!($($arg)*)));
+}
+
+/// Prints to the early console, with a newline.
+#[macro_export]
+macro_rules! early_println {
+    () => ($crate::early_print!("\n"));
+    ($($arg:tt)*) => ($crate::early_print!("{}\n", format_args!($($arg)*)));
+}
+
+/// Prints to the console.
+#[macro_export]
+macro_rules! console_print {
+    ($($arg:tt)*) => ($crate::console::_print(format_args!($($arg)*)));
+}
+
+/// Prints to the console, with a newline.
+#[macro_export]
+macro_rules! console_println {
+    () => ($crate::console_print!("\n"));
+    ($($arg:tt)*) => ($crate::console_print!("{}\n", format_args!($($arg)*)));
+}
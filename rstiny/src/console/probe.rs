@@ -0,0 +1,196 @@
+//! Console UART discovery.
+//!
+//! The console is taken, in order of preference, from:
+//!
+//! 1. `console=` on the command line, see [`ConsoleSpec`];
+//! 2. `/chosen/stdout-path` in the device tree, including the optional
+//!    `:115200n8` option suffix;
+//! 3. the ACPI SPCR table;
+//! 4. the [`UART_PADDR`] configured at build time.
+
+use core::fmt;
+
+use crate::acpi::{self, spcr::InterfaceType};
+use crate::cmdline::ParamValue;
+use crate::config::{UART_PADDR, UART_REG_IO_WIDTH, UART_REG_SHIFT};
+use crate::drivers::serial::{LineConfig, Ns16550, Parity, Pl011, Uart, UartConfig};
+use crate::fdt::{self, Fdt};
+use crate::kparam;
+use crate::utils::mem::phys_to_virt;
+
+/// Where the console description came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleSource {
+    CommandLine,
+    DeviceTree,
+    Spcr,
+    Config,
+}
+
+impl fmt::Display for ConsoleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CommandLine => "command line",
+            Self::DeviceTree => "device tree",
+            Self::Spcr => "ACPI SPCR",
+            Self::Config => "build config",
+        })
+    }
+}
+
+/// A discovered console UART.
+pub struct ConsoleDesc {
+    pub uart: Uart,
+    /// Physical base address of the UART registers.
+    pub paddr: usize,
+    /// Line settings to program, if given by the firmware.
+    pub line: Option<LineConfig>,
+    /// UART input clock, needed to program the line settings.
+    pub clock_hz: Option<u32>,
+    pub source: ConsoleSource,
+}
+
+/// A console UART given on the command line, in the syntax of Linux
+/// `earlycon`: `console=pl011,<paddr>`, or `console=uart8250,mmio,<paddr>`
+/// (byte-spaced registers) or `console=uart8250,mmio32,<paddr>` (32-bit
+/// registers) for an 8250/16550.
+#[derive(Debug, Clone, Copy)]
+pub enum ConsoleSpec {
+    Pl011 { paddr: usize },
+    Ns16550 { paddr: usize, mmio32: bool },
+}
+
+impl ParamValue for ConsoleSpec {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        let mut fields = value.split(',');
+        let spec = match (fields.next(), fields.next(), fields.next()) {
+            (Some("pl011"), Some(paddr), None) => Self::Pl011 {
+                paddr: usize::parse(paddr)?,
+            },
+            (Some("uart8250"), Some(io @ ("mmio" | "mmio32")), Some(paddr)) => Self::Ns16550 {
+                paddr: usize::parse(paddr)?,
+                mmio32: io == "mmio32",
+            },
+            _ => return Err("expected `pl011,<paddr>` or `uart8250,mmio|mmio32,<paddr>`"),
+        };
+        if fields.next().is_some() {
+            return Err("unexpected field after the address");
+        }
+        Ok(spec)
+    }
+}
+
+kparam! {
+    /// Console UART, overriding the firmware description.
+    "console" => pub static CONSOLE: Option<ConsoleSpec> = None;
+}
+
+/// Discovers the console UART.
+pub fn probe() -> ConsoleDesc {
+    CONSOLE
+        .get()
+        .map(from_cmdline)
+        .or_else(|| fdt::get().and_then(probe_fdt))
+        .or_else(probe_spcr)
+        .unwrap_or_else(from_config)
+}
+
+fn from_cmdline(spec: ConsoleSpec) -> ConsoleDesc {
+    let (uart, paddr) = match spec {
+        ConsoleSpec::Pl011 { paddr } => (Uart::Pl011(Pl011::new(phys_to_virt(paddr))), paddr),
+        ConsoleSpec::Ns16550 { paddr, mmio32 } => {
+            let (reg_shift, reg_io_width) = if mmio32 { (2, 4) } else { (0, 1) };
+            let config = UartConfig::new(phys_to_virt(paddr))
+                .reg_shift(reg_shift)
+                .reg_io_width(reg_io_width);
+            (Uart::Ns16550(Ns16550::new(config)), paddr)
+        }
+    };
+    ConsoleDesc {
+        uart,
+        paddr,
+        line: None,
+        clock_hz: None,
+        source: ConsoleSource::CommandLine,
+    }
+}
+
+fn probe_fdt(fdt: &Fdt) -> Option<ConsoleDesc> {
+    let chosen = fdt.chosen()?;
+    let stdout_path = chosen
+        .property_str("stdout-path")
+        .or_else(|| chosen.property_str("linux,stdout-path"))?;
+    let (path, options) = match stdout_path.split_once(':') {
+        Some((path, options)) => (path, Some(options)),
+        None => (stdout_path, None),
+    };
+
+    let node = fdt.find_node_by_path_or_alias(path)?;
+    if !node.is_available() {
+        return None;
+    }
+    let (paddr, _) = node.regions().next()?;
+    let paddr = paddr as usize;
+    let config = UartConfig::new(phys_to_virt(paddr))
+        .reg_shift(node.property_u32("reg-shift").unwrap_or(0))
+        .reg_io_width(node.property_u32("reg-io-width").unwrap_or(1));
+    let uart = node
+        .compatible()
+        .find_map(|compatible| Uart::from_compatible(compatible, config))?;
+
+    Some(ConsoleDesc {
+        uart,
+        paddr,
+        line: options.and_then(LineConfig::parse),
+        clock_hz: node.property_u32("clock-frequency"),
+        source: ConsoleSource::DeviceTree,
+    })
+}
+
+fn probe_spcr() -> Option<ConsoleDesc> {
+    let spcr = acpi::spcr::get()?;
+    let paddr = spcr.base_paddr as usize;
+    let base = phys_to_virt(paddr);
+    let uart = match spcr.interface_type {
+        InterfaceType::Pl011 => Uart::Pl011(Pl011::new(base)),
+        InterfaceType::Ns16550 => {
+            let reg_io_width = spcr.access_size.unwrap_or(1);
+            let reg_shift = match spcr.register_bit_width {
+                32 => 2,
+                16 => 1,
+                _ => 0,
+            };
+            let config = UartConfig::new(base)
+                .reg_shift(reg_shift)
+                .reg_io_width(reg_io_width);
+            Uart::Ns16550(Ns16550::new(config))
+        }
+        InterfaceType::Other(_) => return None,
+    };
+
+    Some(ConsoleDesc {
+        uart,
+        paddr,
+        line: spcr.baud_rate.map(|baud| LineConfig {
+            baud,
+            parity: Parity::None,
+            data_bits: 8,
+            flow_control: false,
+        }),
+        clock_hz: spcr.clock_hz,
+        source: ConsoleSource::Spcr,
+    })
+}
+
+fn from_config() -> ConsoleDesc {
+    let config = UartConfig::new(phys_to_virt(UART_PADDR))
+        .reg_shift(UART_REG_SHIFT)
+        .reg_io_width(UART_REG_IO_WIDTH);
+    ConsoleDesc {
+        uart: Uart::new_default(config),
+        paddr: UART_PADDR,
+        line: None,
+        clock_hz: None,
+        source: ConsoleSource::Config,
+    }
+}
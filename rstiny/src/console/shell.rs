@@ -0,0 +1,94 @@
+//! Minimal command shell on the console UART.
+//!
+//! Started once booting is done. Input is polled, so the shell also works
+//! when the UART interrupt is not routed.
+
+use crate::utils::logging::ring::{self, Cursor};
+use crate::{console_print, console_println};
+
+const LF: u8 = b'\n';
+const CR: u8 = b'\r';
+const DL: u8 = b'\x7f';
+const BS: u8 = b'\x08';
+
+const MAX_CMD_LEN: usize = 128;
+
+type CmdHandler = fn(&str);
+
+const CMD_TABLE: &[(&str, &str, CmdHandler)] = &[
+    (
+        "dmesg",
+        "print the kernel log, or its last <n> records",
+        do_dmesg,
+    ),
+    ("help", "list the commands", do_help),
+];
+
+fn do_dmesg(args: &str) {
+    let cursor = if args.is_empty() {
+        Cursor::from_oldest()
+    } else if let Ok(n) = args.parse() {
+        Cursor::from_tail(n)
+    } else {
+        console_println!("dmesg: invalid record count: {args}");
+        return;
+    };
+    ring::dump(cursor);
+}
+
+fn do_help(_args: &str) {
+    console_println!("Available commands:");
+    for (name, help, _) in CMD_TABLE {
+        console_println!("  {name:<8}{help}");
+    }
+}
+
+fn run_cmd(line: &str) {
+    let line = line.trim();
+    let (cmd, args) = line
+        .split_once(char::is_whitespace)
+        .map_or((line, ""), |(cmd, args)| (cmd, args.trim()));
+    if cmd.is_empty() {
+        return;
+    }
+    match CMD_TABLE.iter().find(|(name, ..)| *name == cmd) {
+        Some((_, _, func)) => func(args),
+        None => console_println!("{cmd}: command not found"),
+    }
+}
+
+/// Runs the shell forever.
+pub fn run() -> ! {
+    let mut buf = [0; MAX_CMD_LEN];
+    let mut len = 0;
+    console_print!("rstiny> ");
+    loop {
+        let Some(c) = super::getc() else {
+            // 空闲时打印中断处理函数推迟的日志
+            log::logger().flush();
+            core::hint::spin_loop();
+            continue;
+        };
+        match c {
+            CR | LF => {
+                console_println!();
+                // 只接受可打印 ASCII 字符，因此总是合法的 UTF-8
+                run_cmd(core::str::from_utf8(&buf[..len]).unwrap());
+                len = 0;
+                console_print!("rstiny> ");
+            }
+            BS | DL => {
+                if len > 0 {
+                    len -= 1;
+                    super::write_raw(&[BS, b' ', BS]);
+                }
+            }
+            b' '..=b'~' if len < MAX_CMD_LEN => {
+                buf[len] = c;
+                len += 1;
+                super::write_raw(&[c]);
+            }
+            _ => {}
+        }
+    }
+}
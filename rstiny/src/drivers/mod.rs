@@ -0,0 +1,4 @@
+//! Device drivers owned by rstiny itself (independent of the axplat platform crate).
+
+pub mod registry;
+pub mod serial;
@@ -0,0 +1,158 @@
+//! Registry of the devices described by the firmware.
+//!
+//! The device tree and the ACPI tables are parsed once, after the heap is
+//! ready, into a flat list of [`Device`]s indexed by `compatible` string.
+//! Drivers probe against the registry instead of hard-coding addresses, and
+//! do not need to know which firmware interface described the device: ACPI
+//! devices are registered under the device tree binding of the same hardware
+//! (e.g. `arm,pl011` for an SPCR console).
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+
+use kspin::SpinNoIrq;
+
+use crate::acpi;
+use crate::fdt::{self, Node};
+
+/// Where a device was described.
+#[derive(Clone, Copy)]
+pub enum DeviceSource {
+    /// A device tree node, which drivers can read extra properties from.
+    DeviceTree(Node<'static>),
+    /// An ACPI table, by signature.
+    Acpi([u8; 4]),
+}
+
+impl fmt::Display for DeviceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeviceTree(_) => f.write_str("device tree"),
+            Self::Acpi(signature) => {
+                write!(f, "ACPI {}", core::str::from_utf8(signature).unwrap_or("?"))
+            }
+        }
+    }
+}
+
+/// A device described by the firmware.
+#[derive(Clone)]
+pub struct Device {
+    /// Device tree node name, e.g. `serial@28001000`, or a name given by the
+    /// ACPI parser.
+    pub name: &'static str,
+    /// `compatible` strings, most specific first.
+    pub compatible: Vec<&'static str>,
+    /// MMIO ranges as CPU physical `(address, size)`.
+    pub regs: Vec<(u64, u64)>,
+    /// Interrupts as GIC interrupt IDs.
+    pub irqs: Vec<u32>,
+    pub source: DeviceSource,
+}
+
+impl Device {
+    /// Returns the physical address of the first MMIO range.
+    pub fn base_paddr(&self) -> Option<usize> {
+        self.regs.first().map(|&(address, _)| address as usize)
+    }
+
+    /// Returns the first interrupt.
+    pub fn irq(&self) -> Option<u32> {
+        self.irqs.first().copied()
+    }
+}
+
+/// Devices indexed by `compatible` string.
+pub(crate) struct Registry {
+    devices: Vec<Device>,
+    /// Indices into `devices`, in registration order.
+    by_compatible: BTreeMap<&'static str, Vec<usize>>,
+}
+
+impl Registry {
+    pub(crate) const fn new() -> Self {
+        Self {
+            devices: Vec::new(),
+            by_compatible: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a device.
+    pub(crate) fn register(&mut self, device: Device) {
+        let index = self.devices.len();
+        for &compatible in &device.compatible {
+            self.by_compatible
+                .entry(compatible)
+                .or_default()
+                .push(index);
+        }
+        self.devices.push(device);
+    }
+
+    /// Returns the devices compatible with `compatible`, in registration
+    /// order.
+    pub(crate) fn find_compatible(&self, compatible: &str) -> Vec<Device> {
+        self.by_compatible
+            .get(compatible)
+            .map_or(Vec::new(), |indices| {
+                indices
+                    .iter()
+                    .map(|&index| self.devices[index].clone())
+                    .collect()
+            })
+    }
+
+    /// Returns the first device compatible with one of `compatibles`, tried
+    /// in the driver's order of preference.
+    pub(crate) fn probe(&self, compatibles: &[&str]) -> Option<Device> {
+        compatibles.iter().find_map(|compatible| {
+            let indices = self.by_compatible.get(compatible)?;
+            Some(self.devices[*indices.first()?].clone())
+        })
+    }
+}
+
+static REGISTRY: SpinNoIrq<Registry> = SpinNoIrq::new(Registry::new());
+
+/// Adds a device to the registry.
+pub fn register(device: Device) {
+    REGISTRY.lock().register(device);
+}
+
+/// Returns the devices compatible with `compatible`, in registration order.
+pub fn find_compatible(compatible: &str) -> Vec<Device> {
+    REGISTRY.lock().find_compatible(compatible)
+}
+
+/// Returns the first device compatible with one of `compatibles`, tried in
+/// the driver's order of preference.
+pub fn probe(compatibles: &[&str]) -> Option<Device> {
+    REGISTRY.lock().probe(compatibles)
+}
+
+/// Returns every registered device.
+pub fn devices() -> Vec<Device> {
+    REGISTRY.lock().devices.clone()
+}
+
+/// Fills the registry from the device tree and the ACPI tables.
+///
+/// Must be called once, after the heap allocator is initialized.
+pub fn init() {
+    fdt::register_devices();
+    acpi::register_devices();
+
+    let devices = devices();
+    for device in &devices {
+        debug!(
+            "devices: {} [{}] from {}, regs {:x?}, irqs {:?}",
+            device.name,
+            device.compatible.join(", "),
+            device.source,
+            device.regs,
+            device.irqs
+        );
+    }
+    info!("devices: {} registered", devices.len());
+}
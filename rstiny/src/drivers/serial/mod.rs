@@ -0,0 +1,263 @@
+//! Serial port drivers.
+//!
+//! Two UART families are supported:
+//!
+//! - ARM PrimeCell PL011 ([`Pl011`]).
+//! - 8250/16550 compatible UARTs, including the DesignWare APB UART found on
+//!   some Phytium boards ([`Ns16550`]).
+//!
+//! Both implement [`SerialPort`]. [`Uart`] wraps either of them so the rest of
+//! the kernel can hold a concrete serial port without allocation or dynamic
+//! dispatch.
+
+mod ns16550;
+mod pl011;
+
+use core::fmt;
+
+pub use ns16550::Ns16550;
+pub use pl011::Pl011;
+
+/// Common interface of the UART drivers.
+pub trait SerialPort {
+    /// Initializes the UART: enables the FIFOs, the transmitter and the receiver.
+    ///
+    /// Line settings (baud rate, word length) programmed by the firmware are kept.
+    fn init(&mut self);
+
+    /// Writes a single byte, spinning until the transmitter can accept it.
+    fn putc(&mut self, c: u8);
+
+    /// Reads a single byte if one is available.
+    fn getc(&mut self) -> Option<u8>;
+
+    /// Programs the baud rate and frame format, given the UART input clock.
+    ///
+    /// The UART is left untouched if the baud rate cannot be derived from the
+    /// clock.
+    fn set_line(&mut self, clock_hz: u32, line: &LineConfig) -> Result<(), LineError>;
+
+    /// Enables or disables the receive interrupt.
+    fn set_rx_interrupt(&mut self, enable: bool);
+
+    /// Acknowledges pending interrupts. Returns `true` if received data is available.
+    fn ack_interrupts(&mut self) -> bool;
+
+    /// Writes a byte slice, translating `\n` into `\r\n`.
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for &c in bytes {
+            if c == b'\n' {
+                self.putc(b'\r');
+            }
+            self.putc(c);
+        }
+    }
+
+    /// Acknowledges the interrupt and passes every received byte to `f`.
+    ///
+    /// Intended to be called from the UART interrupt handler.
+    fn handle_irq(&mut self, mut f: impl FnMut(u8))
+    where
+        Self: Sized,
+    {
+        if self.ack_interrupts() {
+            while let Some(c) = self.getc() {
+                f(c);
+            }
+        }
+    }
+}
+
+/// Parity setting of a serial line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+/// Errors returned by [`SerialPort::set_line`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineError {
+    /// The baud rate divisor for this input clock is 0 or too large for the
+    /// divisor registers.
+    BadBaud { clock_hz: u32, baud: u32 },
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadBaud { clock_hz, baud } => {
+                write!(f, "{baud} baud unreachable from a {clock_hz} Hz clock")
+            }
+        }
+    }
+}
+
+/// Baud rate and frame format of a serial line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineConfig {
+    pub baud: u32,
+    pub parity: Parity,
+    pub data_bits: u8,
+    /// Hardware (RTS/CTS) flow control.
+    pub flow_control: bool,
+}
+
+impl LineConfig {
+    /// Parses a Linux-style option string: `<baud>[<parity>[<bits>[r]]]`,
+    /// e.g. `115200n8`.
+    pub fn parse(options: &str) -> Option<Self> {
+        let digits = options.bytes().take_while(u8::is_ascii_digit).count();
+        let baud = options[..digits].parse().ok().filter(|&baud| baud != 0)?;
+        let mut rest = options[digits..].bytes();
+        let parity = match rest.next() {
+            None | Some(b'n') => Parity::None,
+            Some(b'o') => Parity::Odd,
+            Some(b'e') => Parity::Even,
+            Some(_) => return None,
+        };
+        let data_bits = match rest.next() {
+            None => 8,
+            Some(bits @ b'5'..=b'8') => bits - b'0',
+            Some(_) => return None,
+        };
+        let flow_control = match rest.next() {
+            None => false,
+            Some(b'r') => true,
+            Some(_) => return None,
+        };
+        Some(Self {
+            baud,
+            parity,
+            data_bits,
+            flow_control,
+        })
+    }
+}
+
+/// Register layout of a memory-mapped UART.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartConfig {
+    /// Virtual base address of the register block.
+    pub base: usize,
+    /// Register index shift (`reg-shift` in the device tree).
+    pub reg_shift: u32,
+    /// Register access width in bytes (`reg-io-width` in the device tree).
+    pub reg_io_width: u32,
+}
+
+impl UartConfig {
+    /// Creates a config with byte-spaced, byte-wide registers.
+    pub const fn new(base: usize) -> Self {
+        Self {
+            base,
+            reg_shift: 0,
+            reg_io_width: 1,
+        }
+    }
+
+    /// Sets the register index shift.
+    pub const fn reg_shift(mut self, reg_shift: u32) -> Self {
+        self.reg_shift = reg_shift;
+        self
+    }
+
+    /// Sets the register access width in bytes.
+    pub const fn reg_io_width(mut self, reg_io_width: u32) -> Self {
+        self.reg_io_width = reg_io_width;
+        self
+    }
+}
+
+/// A UART driven by one of the supported drivers.
+#[derive(Clone)]
+pub enum Uart {
+    Pl011(Pl011),
+    Ns16550(Ns16550),
+}
+
+impl Uart {
+    /// Creates the driver matching a device tree `compatible` string.
+    ///
+    /// Returns `None` if no driver supports the device.
+    pub fn from_compatible(compatible: &str, config: UartConfig) -> Option<Self> {
+        if Pl011::COMPATIBLE.contains(&compatible) {
+            Some(Self::Pl011(Pl011::new(config.base)))
+        } else if Ns16550::COMPATIBLE.contains(&compatible) {
+            Some(Self::Ns16550(Ns16550::new(config)))
+        } else {
+            None
+        }
+    }
+
+    /// Creates the driver selected at build time.
+    ///
+    /// This is the PL011 driver unless the `uart-8250` feature is enabled.
+    pub const fn new_default(config: UartConfig) -> Self {
+        if cfg!(feature = "uart-8250") {
+            Self::Ns16550(Ns16550::new(config))
+        } else {
+            Self::Pl011(Pl011::new(config.base))
+        }
+    }
+
+    /// Returns the virtual base address of the register block.
+    pub const fn base(&self) -> usize {
+        match self {
+            Self::Pl011(uart) => uart.base(),
+            Self::Ns16550(uart) => uart.base(),
+        }
+    }
+}
+
+impl SerialPort for Uart {
+    fn init(&mut self) {
+        match self {
+            Self::Pl011(uart) => uart.init(),
+            Self::Ns16550(uart) => uart.init(),
+        }
+    }
+
+    fn putc(&mut self, c: u8) {
+        match self {
+            Self::Pl011(uart) => uart.putc(c),
+            Self::Ns16550(uart) => uart.putc(c),
+        }
+    }
+
+    fn getc(&mut self) -> Option<u8> {
+        match self {
+            Self::Pl011(uart) => uart.getc(),
+            Self::Ns16550(uart) => uart.getc(),
+        }
+    }
+
+    fn set_line(&mut self, clock_hz: u32, line: &LineConfig) -> Result<(), LineError> {
+        match self {
+            Self::Pl011(uart) => uart.set_line(clock_hz, line),
+            Self::Ns16550(uart) => uart.set_line(clock_hz, line),
+        }
+    }
+
+    fn set_rx_interrupt(&mut self, enable: bool) {
+        match self {
+            Self::Pl011(uart) => uart.set_rx_interrupt(enable),
+            Self::Ns16550(uart) => uart.set_rx_interrupt(enable),
+        }
+    }
+
+    fn ack_interrupts(&mut self) -> bool {
+        match self {
+            Self::Pl011(uart) => uart.ack_interrupts(),
+            Self::Ns16550(uart) => uart.ack_interrupts(),
+        }
+    }
+}
+
+impl fmt::Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
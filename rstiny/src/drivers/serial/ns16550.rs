@@ -0,0 +1,189 @@
+//! 8250/16550 compatible UART driver.
+//!
+//! Handles both byte-spaced legacy layouts and the 32-bit spaced DesignWare
+//! APB UART (`reg-shift = <2>`, `reg-io-width = <4>`).
+
+use core::ptr::{read_volatile, write_volatile};
+
+use super::{LineConfig, LineError, Parity, SerialPort, UartConfig};
+
+/// Receiver buffer (read) / transmitter holding (write) / divisor latch low.
+const RBR_THR: usize = 0;
+/// Interrupt enable / divisor latch high.
+const IER: usize = 1;
+/// Interrupt identification (read) / FIFO control (write).
+const IIR_FCR: usize = 2;
+/// Line control.
+const LCR: usize = 3;
+/// Modem control.
+const MCR: usize = 4;
+/// Line status.
+const LSR: usize = 5;
+/// DesignWare UART status.
+const DW_USR: usize = 31;
+
+const IER_ERBFI: u8 = 1 << 0;
+
+const IIR_NO_INT: u8 = 1 << 0;
+const IIR_ID_MASK: u8 = 0x0e;
+const IIR_ID_RX_DATA: u8 = 0x04;
+const IIR_ID_RX_TIMEOUT: u8 = 0x0c;
+const IIR_ID_LINE_STATUS: u8 = 0x06;
+/// DesignWare busy detect indication.
+const IIR_DW_BUSY: u8 = 0x07;
+
+const FCR_FIFO_ENABLE: u8 = 1 << 0;
+const FCR_CLEAR_RX: u8 = 1 << 1;
+const FCR_CLEAR_TX: u8 = 1 << 2;
+
+const LCR_PEN: u8 = 1 << 3;
+const LCR_EPS: u8 = 1 << 4;
+const LCR_DLAB: u8 = 1 << 7;
+
+const MCR_DTR: u8 = 1 << 0;
+const MCR_RTS: u8 = 1 << 1;
+/// Auto flow control enable.
+const MCR_AFE: u8 = 1 << 5;
+
+const LSR_DR: u8 = 1 << 0;
+const LSR_THRE: u8 = 1 << 5;
+
+/// 8250/16550 UART.
+#[derive(Clone)]
+pub struct Ns16550 {
+    config: UartConfig,
+}
+
+impl Ns16550 {
+    /// Device tree `compatible` strings handled by this driver.
+    pub const COMPATIBLE: &[&str] = &[
+        "ns16550a",
+        "ns16550",
+        "ns16450",
+        "ns8250",
+        "snps,dw-apb-uart",
+        "phytium,uart",
+    ];
+
+    /// Creates a driver for the register block described by `config`.
+    pub const fn new(config: UartConfig) -> Self {
+        Self { config }
+    }
+
+    /// Returns the virtual base address of the register block.
+    pub const fn base(&self) -> usize {
+        self.config.base
+    }
+
+    fn reg_addr(&self, reg: usize) -> usize {
+        self.config.base + (reg << self.config.reg_shift)
+    }
+
+    fn read(&self, reg: usize) -> u8 {
+        let addr = self.reg_addr(reg);
+        unsafe {
+            match self.config.reg_io_width {
+                4 => read_volatile(addr as *const u32) as u8,
+                2 => read_volatile(addr as *const u16) as u8,
+                _ => read_volatile(addr as *const u8),
+            }
+        }
+    }
+
+    fn write(&mut self, reg: usize, value: u8) {
+        let addr = self.reg_addr(reg);
+        unsafe {
+            match self.config.reg_io_width {
+                4 => write_volatile(addr as *mut u32, value as u32),
+                2 => write_volatile(addr as *mut u16, value as u16),
+                _ => write_volatile(addr as *mut u8, value),
+            }
+        }
+    }
+}
+
+impl SerialPort for Ns16550 {
+    fn init(&mut self) {
+        self.write(IER, 0);
+        self.write(IIR_FCR, FCR_FIFO_ENABLE | FCR_CLEAR_RX | FCR_CLEAR_TX);
+        self.write(MCR, MCR_DTR | MCR_RTS);
+        // 读取一次 LSR 和 RBR，丢弃上电后残留的状态
+        let _ = self.read(LSR);
+        let _ = self.read(RBR_THR);
+    }
+
+    fn putc(&mut self, c: u8) {
+        while self.read(LSR) & LSR_THRE == 0 {
+            core::hint::spin_loop();
+        }
+        self.write(RBR_THR, c);
+    }
+
+    fn getc(&mut self) -> Option<u8> {
+        if self.read(LSR) & LSR_DR != 0 {
+            Some(self.read(RBR_THR))
+        } else {
+            None
+        }
+    }
+
+    fn set_line(&mut self, clock_hz: u32, line: &LineConfig) -> Result<(), LineError> {
+        // 分频系数 = clk / (16 * baud)，四舍五入；DLL/DLM 共 16 位，0 无效
+        let error = LineError::BadBaud {
+            clock_hz,
+            baud: line.baud,
+        };
+        let baud = line.baud as u64;
+        let divisor = (clock_hz as u64 + 8 * baud)
+            .checked_div(16 * baud)
+            .filter(|&divisor| divisor != 0)
+            .and_then(|divisor| u16::try_from(divisor).ok())
+            .ok_or(error)?;
+
+        let mut lcr = line.data_bits - 5;
+        match line.parity {
+            Parity::None => {}
+            Parity::Odd => lcr |= LCR_PEN,
+            Parity::Even => lcr |= LCR_PEN | LCR_EPS,
+        }
+        self.write(LCR, lcr | LCR_DLAB);
+        self.write(RBR_THR, divisor as u8);
+        self.write(IER, (divisor >> 8) as u8);
+        self.write(LCR, lcr);
+
+        let mcr = if line.flow_control {
+            MCR_DTR | MCR_RTS | MCR_AFE
+        } else {
+            MCR_DTR | MCR_RTS
+        };
+        self.write(MCR, mcr);
+        Ok(())
+    }
+
+    fn set_rx_interrupt(&mut self, enable: bool) {
+        let ier = self.read(IER);
+        let ier = if enable { ier | IER_ERBFI } else { ier & !IER_ERBFI };
+        self.write(IER, ier);
+    }
+
+    fn ack_interrupts(&mut self) -> bool {
+        let iir = self.read(IIR_FCR);
+        if iir & 0x0f == IIR_DW_BUSY {
+            // LCR 在忙时被写入，读取 USR 清除该中断
+            let _ = self.read(DW_USR);
+            return false;
+        }
+        if iir & IIR_NO_INT != 0 {
+            return false;
+        }
+        match iir & IIR_ID_MASK {
+            IIR_ID_RX_DATA | IIR_ID_RX_TIMEOUT => true,
+            IIR_ID_LINE_STATUS => {
+                // 读取 LSR 清除线路状态中断
+                let _ = self.read(LSR);
+                false
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! ARM PrimeCell PL011 UART driver.
+
+use core::ptr::{read_volatile, write_volatile};
+
+use super::{LineConfig, LineError, Parity, SerialPort};
+
+const UARTDR: usize = 0x00;
+const UARTFR: usize = 0x18;
+const UARTIBRD: usize = 0x24;
+const UARTFBRD: usize = 0x28;
+const UARTLCR_H: usize = 0x2c;
+const UARTCR: usize = 0x30;
+const UARTIFLS: usize = 0x34;
+const UARTIMSC: usize = 0x38;
+const UARTMIS: usize = 0x40;
+const UARTICR: usize = 0x44;
+
+const FR_BUSY: u32 = 1 << 3;
+const FR_RXFE: u32 = 1 << 4;
+const FR_TXFF: u32 = 1 << 5;
+
+const LCR_H_PEN: u32 = 1 << 1;
+const LCR_H_EPS: u32 = 1 << 2;
+const LCR_H_FEN: u32 = 1 << 4;
+const LCR_H_WLEN_SHIFT: u32 = 5;
+
+const CR_UARTEN: u32 = 1 << 0;
+const CR_TXE: u32 = 1 << 8;
+const CR_RXE: u32 = 1 << 9;
+const CR_RTSEN: u32 = 1 << 14;
+const CR_CTSEN: u32 = 1 << 15;
+
+/// Receive and receive-timeout interrupts.
+const INT_RX: u32 = (1 << 4) | (1 << 6);
+
+/// PL011 UART.
+#[derive(Clone)]
+pub struct Pl011 {
+    base: usize,
+}
+
+impl Pl011 {
+    /// Device tree `compatible` strings handled by this driver.
+    pub const COMPATIBLE: &[&str] = &["arm,pl011", "arm,sbsa-uart"];
+
+    /// Creates a driver for the register block at virtual address `base`.
+    pub const fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    /// Returns the virtual base address of the register block.
+    pub const fn base(&self) -> usize {
+        self.base
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&mut self, offset: usize, value: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, value) }
+    }
+}
+
+impl SerialPort for Pl011 {
+    fn init(&mut self) {
+        // 关闭所有中断并清除挂起状态
+        self.write(UARTIMSC, 0);
+        self.write(UARTICR, 0x7ff);
+        // 开启 FIFO，RX 触发阈值 1/8
+        let lcr_h = self.read(UARTLCR_H);
+        self.write(UARTLCR_H, lcr_h | LCR_H_FEN);
+        self.write(UARTIFLS, 0);
+        self.write(UARTCR, CR_UARTEN | CR_TXE | CR_RXE);
+    }
+
+    fn putc(&mut self, c: u8) {
+        while self.read(UARTFR) & FR_TXFF != 0 {
+            core::hint::spin_loop();
+        }
+        self.write(UARTDR, c as u32);
+    }
+
+    fn getc(&mut self) -> Option<u8> {
+        if self.read(UARTFR) & FR_RXFE != 0 {
+            None
+        } else {
+            Some(self.read(UARTDR) as u8)
+        }
+    }
+
+    fn set_line(&mut self, clock_hz: u32, line: &LineConfig) -> Result<(), LineError> {
+        // 分频系数以 1/64 为单位: 64 * clk / (16 * baud)；整数部分 IBRD 为 16 位且不能为 0
+        let baud = line.baud as u64;
+        let divisor = ((clock_hz as u64) * 4 + baud / 2)
+            .checked_div(baud)
+            .filter(|divisor| (1..=0xffff).contains(&(divisor >> 6)))
+            .ok_or(LineError::BadBaud {
+                clock_hz,
+                baud: line.baud,
+            })?;
+
+        // 修改波特率前必须关闭 UART 并等待发送完成
+        while self.read(UARTFR) & FR_BUSY != 0 {
+            core::hint::spin_loop();
+        }
+        self.write(UARTCR, 0);
+
+        self.write(UARTIBRD, (divisor >> 6) as u32);
+        self.write(UARTFBRD, (divisor & 0x3f) as u32);
+
+        let mut lcr_h = LCR_H_FEN | ((line.data_bits as u32 - 5) << LCR_H_WLEN_SHIFT);
+        match line.parity {
+            Parity::None => {}
+            Parity::Odd => lcr_h |= LCR_H_PEN,
+            Parity::Even => lcr_h |= LCR_H_PEN | LCR_H_EPS,
+        }
+        self.write(UARTLCR_H, lcr_h);
+
+        let mut cr = CR_UARTEN | CR_TXE | CR_RXE;
+        if line.flow_control {
+            cr |= CR_RTSEN | CR_CTSEN;
+        }
+        self.write(UARTCR, cr);
+        Ok(())
+    }
+
+    fn set_rx_interrupt(&mut self, enable: bool) {
+        let imsc = self.read(UARTIMSC);
+        let imsc = if enable { imsc | INT_RX } else { imsc & !INT_RX };
+        self.write(UARTIMSC, imsc);
+    }
+
+    fn ack_interrupts(&mut self) -> bool {
+        let mis = self.read(UARTMIS);
+        self.write(UARTICR, mis);
+        mis & INT_RX != 0
+    }
+}
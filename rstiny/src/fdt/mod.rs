@@ -0,0 +1,699 @@
+//! Flattened device tree (FDT) parsing.
+//!
+//! The blob passed by the bootloader is validated once by [`init`] and can be
+//! queried afterwards through [`get`]. Parsing is zero-copy and needs no
+//! allocation, so it can be used before the heap is ready.
+//!
+//! Once the heap is up, [`register_devices`] adds every available node with a
+//! `compatible` property to the [device registry](crate::drivers::registry),
+//! with its `reg` translated to CPU physical addresses through the `ranges`
+//! of its ancestors.
+
+use alloc::vec::Vec;
+use core::ffi::CStr;
+
+use lazyinit::LazyInit;
+
+use crate::drivers::registry::{self, Device, DeviceSource};
+use crate::utils::mem::phys_to_virt;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_HEADER_SIZE: usize = 40;
+/// Oldest blob version accepted; version 17 added `size_dt_struct`.
+const FDT_MIN_VERSION: u32 = 17;
+const FDT_LAST_COMP_VERSION: u32 = 17;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// Default `#address-cells` when the property is absent.
+const DEFAULT_ADDRESS_CELLS: u32 = 2;
+/// Default `#size-cells` when the property is absent.
+const DEFAULT_SIZE_CELLS: u32 = 1;
+/// Deepest node nesting walked by [`Fdt::all_nodes`].
+const MAX_DEPTH: usize = 16;
+
+/// Errors found while validating a device tree blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdtError {
+    /// The header magic is not `0xd00dfeed`.
+    BadMagic,
+    /// The blob uses an incompatible version.
+    BadVersion(u32),
+    /// A block lies outside the blob, or the structure block is malformed.
+    BadLayout,
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+const fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// A validated device tree blob.
+#[derive(Clone, Copy)]
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    structs: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> Fdt<'a> {
+    /// Parses the blob at `ptr`, reading its size from the header.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to readable memory at least as large as the size
+    /// announced in the header, which must live for `'a`.
+    pub unsafe fn from_ptr(ptr: *const u8) -> Result<Self, FdtError> {
+        if ptr.is_null() || ptr as usize % 4 != 0 {
+            return Err(FdtError::BadMagic);
+        }
+        let header = unsafe { core::slice::from_raw_parts(ptr, FDT_HEADER_SIZE) };
+        if be32(header, 0) != Some(FDT_MAGIC) {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = be32(header, 4).unwrap() as usize;
+        if total_size < FDT_HEADER_SIZE {
+            return Err(FdtError::BadLayout);
+        }
+        Self::new(unsafe { core::slice::from_raw_parts(ptr, total_size) })
+    }
+
+    /// Parses and validates a blob.
+    pub fn new(data: &'a [u8]) -> Result<Self, FdtError> {
+        let field = |offset| be32(data, offset).ok_or(FdtError::BadLayout);
+        if field(0)? != FDT_MAGIC {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = field(4)? as usize;
+        let version = field(20)?;
+        let last_comp_version = field(24)?;
+        if version < FDT_MIN_VERSION || last_comp_version > FDT_LAST_COMP_VERSION {
+            return Err(FdtError::BadVersion(version));
+        }
+        if total_size > data.len() {
+            return Err(FdtError::BadLayout);
+        }
+        let data = &data[..total_size];
+
+        let block = |offset: u32, size: u32| {
+            let start = offset as usize;
+            let end = start.checked_add(size as usize).ok_or(FdtError::BadLayout)?;
+            data.get(start..end).ok_or(FdtError::BadLayout)
+        };
+        let structs = block(field(8)?, field(36)?)?;
+        let strings = block(field(12)?, field(32)?)?;
+        if structs.len() % 4 != 0 {
+            return Err(FdtError::BadLayout);
+        }
+
+        let fdt = Self {
+            data,
+            structs,
+            strings,
+        };
+        fdt.validate_structure()?;
+        Ok(fdt)
+    }
+
+    /// Checks that the structure block holds a single root node, with
+    /// balanced node tokens and well-formed properties, followed by `FDT_END`.
+    fn validate_structure(&self) -> Result<(), FdtError> {
+        let mut tokens = self.tokens(0);
+        // 根节点必须是结构块中的第一个节点
+        if !matches!(
+            tokens.by_ref().find(|token| !matches!(token, Token::Nop)),
+            Some(Token::BeginNode { .. })
+        ) {
+            return Err(FdtError::BadLayout);
+        }
+        let mut depth = 1usize;
+        while depth > 0 {
+            match tokens.next().ok_or(FdtError::BadLayout)? {
+                Token::BeginNode { .. } => depth += 1,
+                Token::EndNode => depth -= 1,
+                Token::Prop { .. } | Token::Nop => {}
+            }
+        }
+        // 根节点之后只允许 NOP，然后是 FDT_END
+        if tokens.any(|token| !matches!(token, Token::Nop))
+            || be32(self.structs, tokens.offset) != Some(FDT_END)
+        {
+            return Err(FdtError::BadLayout);
+        }
+        Ok(())
+    }
+
+    /// Returns the address of the blob.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.data.as_ptr()
+    }
+
+    /// Returns the total size of the blob in bytes.
+    pub fn total_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the root node.
+    pub fn root(&self) -> Node<'a> {
+        match self.tokens(0).find(|token| !matches!(token, Token::Nop)) {
+            Some(Token::BeginNode { name, body }) => Node {
+                fdt: *self,
+                name,
+                body,
+                parent_cells: (DEFAULT_ADDRESS_CELLS, DEFAULT_SIZE_CELLS),
+            },
+            _ => unreachable!("validated in Fdt::new"),
+        }
+    }
+
+    /// Finds a node by its full path, e.g. `/soc/serial@28001000`.
+    ///
+    /// A path component without a unit address matches any unit address.
+    pub fn find_node(&self, path: &str) -> Option<Node<'a>> {
+        let path = path.strip_prefix('/')?;
+        let mut node = self.root();
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            node = node.children().find(|child| child.matches_name(component))?;
+        }
+        Some(node)
+    }
+
+    /// Resolves an alias from the `/aliases` node to a full path.
+    pub fn resolve_alias(&self, alias: &str) -> Option<&'a str> {
+        self.find_node("/aliases")?.property_str(alias)
+    }
+
+    /// Finds a node by full path or by alias (`serial0`, `serial0/child`).
+    pub fn find_node_by_path_or_alias(&self, path: &str) -> Option<Node<'a>> {
+        if path.starts_with('/') {
+            return self.find_node(path);
+        }
+        let (alias, rest) = path.split_once('/').unwrap_or((path, ""));
+        let node = self.find_node(self.resolve_alias(alias)?)?;
+        if rest.is_empty() {
+            Some(node)
+        } else {
+            rest.split('/')
+                .filter(|c| !c.is_empty())
+                .try_fold(node, |node, component| {
+                    node.children().find(|child| child.matches_name(component))
+                })
+        }
+    }
+
+    /// Iterates over every node, depth first, starting with the root.
+    ///
+    /// Nodes nested deeper than [`MAX_DEPTH`] are not visited.
+    pub fn all_nodes(&self) -> impl Iterator<Item = Node<'a>> + use<'a> {
+        let fdt = *self;
+        // cells[d] 是第 d 层节点的 #address-cells 和 #size-cells，描述第 d + 1 层节点的 reg
+        let mut cells = [(DEFAULT_ADDRESS_CELLS, DEFAULT_SIZE_CELLS); MAX_DEPTH];
+        let mut depth = 0usize;
+        self.tokens(0).filter_map(move |token| match token {
+            Token::BeginNode { name, body } => {
+                depth += 1;
+                if depth > MAX_DEPTH {
+                    return None;
+                }
+                let node = Node {
+                    fdt,
+                    name,
+                    body,
+                    parent_cells: cells[depth - 1],
+                };
+                if depth < MAX_DEPTH {
+                    cells[depth] = node.cells();
+                }
+                Some(node)
+            }
+            Token::EndNode => {
+                depth = depth.saturating_sub(1);
+                None
+            }
+            Token::Prop { .. } | Token::Nop => None,
+        })
+    }
+
+    /// Finds the node with the given `phandle`.
+    pub fn find_phandle(&self, phandle: u32) -> Option<Node<'a>> {
+        self.all_nodes().find(|node| node.phandle() == Some(phandle))
+    }
+
+    /// Returns the `/chosen` node.
+    pub fn chosen(&self) -> Option<Node<'a>> {
+        self.find_node("/chosen")
+    }
+
+    /// Iterates over the RAM ranges of the `/memory` nodes, as `(address, size)`.
+    pub fn memory_regions(&self) -> impl Iterator<Item = (u64, u64)> + use<'a> {
+        self.root()
+            .children()
+            .filter(|node| {
+                node.property_str("device_type") == Some("memory")
+                    || node.name().split('@').next() == Some("memory")
+            })
+            .filter(|node| node.is_available())
+            .flat_map(|node| node.reg())
+    }
+
+    /// Iterates over reserved RAM ranges, as `(address, size)`: the memory
+    /// reservation block followed by the `/reserved-memory` children with a
+    /// `reg` property.
+    pub fn reserved_regions(&self) -> impl Iterator<Item = (u64, u64)> + use<'a> {
+        let rsvmap = be32(self.data, 16)
+            .and_then(|offset| self.data.get(offset as usize..))
+            .unwrap_or(&[]);
+        let rsvmap = CellPairs::new(rsvmap, 2, 2)
+            .take_while(|&(address, size)| address != 0 || size != 0);
+        let nodes = self
+            .find_node("/reserved-memory")
+            .into_iter()
+            .flat_map(|reserved| reserved.children())
+            .flat_map(|node| node.reg());
+        rsvmap.chain(nodes)
+    }
+
+    /// Returns the kernel command line from `/chosen/bootargs`.
+    pub fn bootargs(&self) -> Option<&'a str> {
+        self.chosen()?.property_str("bootargs")
+    }
+
+    fn tokens(&self, offset: usize) -> Tokens<'a> {
+        Tokens {
+            fdt: *self,
+            offset,
+        }
+    }
+
+    fn string_at(&self, offset: usize) -> Option<&'a str> {
+        let bytes = self.strings.get(offset..)?;
+        CStr::from_bytes_until_nul(bytes).ok()?.to_str().ok()
+    }
+}
+
+/// A token of the structure block.
+enum Token<'a> {
+    /// Start of a node. `body` is the offset of the token after the name.
+    BeginNode { name: &'a str, body: usize },
+    EndNode,
+    Prop { name: &'a str, value: &'a [u8] },
+    Nop,
+}
+
+/// Iterator over structure block tokens. Stops at `FDT_END` or on malformed data.
+struct Tokens<'a> {
+    fdt: Fdt<'a>,
+    offset: usize,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let structs = self.fdt.structs;
+        let token = be32(structs, self.offset)?;
+        let start = self.offset + 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = CStr::from_bytes_until_nul(structs.get(start..)?).ok()?;
+                let body = align4(start + name.to_bytes_with_nul().len());
+                self.offset = body;
+                Some(Token::BeginNode {
+                    name: name.to_str().ok()?,
+                    body,
+                })
+            }
+            FDT_END_NODE => {
+                self.offset = start;
+                Some(Token::EndNode)
+            }
+            FDT_PROP => {
+                let len = be32(structs, start)? as usize;
+                let name_offset = be32(structs, start + 4)? as usize;
+                let value_start = start + 8;
+                let value = structs.get(value_start..value_start + len)?;
+                self.offset = align4(value_start + len);
+                Some(Token::Prop {
+                    name: self.fdt.string_at(name_offset)?,
+                    value,
+                })
+            }
+            FDT_NOP => {
+                self.offset = start;
+                Some(Token::Nop)
+            }
+            FDT_END => None,
+            _ => None,
+        }
+    }
+}
+
+/// A device tree node.
+#[derive(Clone, Copy)]
+pub struct Node<'a> {
+    fdt: Fdt<'a>,
+    name: &'a str,
+    /// Offset of the first token after the node name.
+    body: usize,
+    /// `#address-cells` and `#size-cells` of the parent, which describe `reg`.
+    parent_cells: (u32, u32),
+}
+
+impl<'a> Node<'a> {
+    /// Returns the node name including the unit address, e.g. `serial@28001000`.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns whether the node name matches a path component.
+    fn matches_name(&self, component: &str) -> bool {
+        if component.contains('@') {
+            self.name == component
+        } else {
+            self.name.split('@').next() == Some(component)
+        }
+    }
+
+    /// Iterates over the properties of this node.
+    pub fn properties(&self) -> impl Iterator<Item = (&'a str, &'a [u8])> + use<'a> {
+        self.fdt
+            .tokens(self.body)
+            .filter(|token| !matches!(token, Token::Nop))
+            .map_while(|token| match token {
+                Token::Prop { name, value } => Some((name, value)),
+                _ => None,
+            })
+    }
+
+    /// Returns the raw value of a property.
+    pub fn property(&self, name: &str) -> Option<&'a [u8]> {
+        self.properties()
+            .find(|&(prop, _)| prop == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns a property holding a single `u32` cell.
+    pub fn property_u32(&self, name: &str) -> Option<u32> {
+        be32(self.property(name)?, 0)
+    }
+
+    /// Returns a property holding a single string.
+    pub fn property_str(&self, name: &str) -> Option<&'a str> {
+        CStr::from_bytes_until_nul(self.property(name)?)
+            .ok()?
+            .to_str()
+            .ok()
+    }
+
+    /// Iterates over the strings of the `compatible` property.
+    pub fn compatible(&self) -> impl Iterator<Item = &'a str> + use<'a> {
+        self.property("compatible")
+            .unwrap_or(&[])
+            .split(|&b| b == 0)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| core::str::from_utf8(s).ok())
+    }
+
+    /// Returns whether the node is compatible with `compatible`.
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.compatible().any(|c| c == compatible)
+    }
+
+    /// Returns the `phandle` of this node, which other nodes refer to it by.
+    pub fn phandle(&self) -> Option<u32> {
+        self.property_u32("phandle")
+            .or_else(|| self.property_u32("linux,phandle"))
+    }
+
+    /// Returns the node referred to by a phandle property, e.g. `clocks`.
+    pub fn property_phandle(&self, name: &str) -> Option<Node<'a>> {
+        self.fdt.find_phandle(self.property_u32(name)?)
+    }
+
+    /// Returns the parent node, or `None` for the root.
+    pub fn parent(&self) -> Option<Node<'a>> {
+        // 节点在结构块中按深度优先排列，逐层找到包含本节点的最后一个子节点
+        let mut parent = None;
+        let mut node = self.fdt.root();
+        while node.body != self.body {
+            let next = node
+                .children()
+                .take_while(|child| child.body <= self.body)
+                .last()?;
+            parent = Some(node);
+            node = next;
+        }
+        parent
+    }
+
+    /// Returns the interrupt controller of this node: its `interrupt-parent`,
+    /// or that of the nearest ancestor with one.
+    pub fn interrupt_parent(&self) -> Option<Node<'a>> {
+        let mut node = *self;
+        loop {
+            if let Some(controller) = node.property_phandle("interrupt-parent") {
+                return Some(controller);
+            }
+            node = node.parent()?;
+        }
+    }
+
+    /// Iterates over the specifiers of the `interrupts` property, each made of
+    /// `#interrupt-cells` cells of the [interrupt parent](Self::interrupt_parent).
+    pub fn interrupts(&self) -> impl Iterator<Item = &'a [u8]> + use<'a> {
+        let mut data = self.property("interrupts").unwrap_or(&[]);
+        let cells = if data.is_empty() {
+            0
+        } else {
+            self.interrupt_parent()
+                .and_then(|controller| controller.property_u32("#interrupt-cells"))
+                .unwrap_or(0) as usize
+        };
+        if cells == 0 {
+            data = &[];
+        }
+        data.chunks_exact(cells.max(1) * 4)
+    }
+
+    /// Returns whether the `status` property allows the device to be used.
+    pub fn is_available(&self) -> bool {
+        matches!(self.property_str("status"), None | Some("okay") | Some("ok"))
+    }
+
+    /// Returns `#address-cells` and `#size-cells` describing the children of this node.
+    pub fn cells(&self) -> (u32, u32) {
+        (
+            self.property_u32("#address-cells")
+                .unwrap_or(DEFAULT_ADDRESS_CELLS),
+            self.property_u32("#size-cells").unwrap_or(DEFAULT_SIZE_CELLS),
+        )
+    }
+
+    /// Iterates over the `(address, size)` pairs of the `reg` property, as
+    /// addresses of the parent bus. See [`regions`](Self::regions) for CPU
+    /// physical addresses.
+    pub fn reg(&self) -> impl Iterator<Item = (u64, u64)> + use<'a> {
+        let (address_cells, size_cells) = self.parent_cells;
+        CellPairs::new(
+            self.property("reg").unwrap_or(&[]),
+            address_cells,
+            size_cells,
+        )
+    }
+
+    /// Iterates over the `reg` ranges translated to CPU physical addresses.
+    ///
+    /// Ranges that cannot be translated, because an ancestor bus has no
+    /// `ranges` property or none of its entries covers them, are skipped.
+    pub fn regions(&self) -> impl Iterator<Item = (u64, u64)> + use<'a> {
+        let bus = self.parent();
+        self.reg().filter_map(move |(address, size)| match bus {
+            Some(bus) => Some((bus.translate(address)?, size)),
+            None => Some((address, size)),
+        })
+    }
+
+    /// Translates an address on the bus of this node (the address space of
+    /// its children) to a CPU physical address, following the `ranges` of
+    /// this node and its ancestors up to the root.
+    pub fn translate(&self, mut address: u64) -> Option<u64> {
+        let mut bus = *self;
+        while let Some(parent) = bus.parent() {
+            // 空的 ranges 表示一一映射，没有 ranges 表示不可转换
+            let ranges = bus.property("ranges")?;
+            if !ranges.is_empty() {
+                address = translate_range(ranges, bus.cells(), parent.cells().0, address)?;
+            }
+            bus = parent;
+        }
+        Some(address)
+    }
+
+    /// Iterates over the direct children of this node.
+    pub fn children(&self) -> impl Iterator<Item = Node<'a>> + use<'a> {
+        let fdt = self.fdt;
+        let cells = self.cells();
+        let mut depth = 0usize;
+        self.fdt.tokens(self.body).map_while(move |token| match token {
+            Token::BeginNode { name, body } => {
+                depth += 1;
+                Some((depth == 1).then_some(Node {
+                    fdt,
+                    name,
+                    body,
+                    parent_cells: cells,
+                }))
+            }
+            Token::EndNode if depth == 0 => None,
+            Token::EndNode => {
+                depth -= 1;
+                Some(None)
+            }
+            Token::Prop { .. } | Token::Nop => Some(None),
+        })
+        .flatten()
+    }
+}
+
+/// Reads a big-endian number made of `cells` 32-bit cells.
+fn read_cells(data: &[u8], cells: u32) -> Option<u64> {
+    (0..cells as usize).try_fold(0u64, |acc, i| {
+        Some((acc << 32) | be32(data, i * 4)? as u64)
+    })
+}
+
+/// Translates `address` through one `ranges` property, made of
+/// `(child address, parent address, size)` entries.
+fn translate_range(
+    ranges: &[u8],
+    (child_cells, size_cells): (u32, u32),
+    parent_cells: u32,
+    address: u64,
+) -> Option<u64> {
+    let entry_size = (child_cells + parent_cells + size_cells) as usize * 4;
+    if entry_size == 0 {
+        return None;
+    }
+    ranges.chunks_exact(entry_size).find_map(|entry| {
+        let child = read_cells(entry, child_cells)?;
+        let parent = read_cells(&entry[child_cells as usize * 4..], parent_cells)?;
+        let size = read_cells(
+            &entry[(child_cells + parent_cells) as usize * 4..],
+            size_cells,
+        )?;
+        let offset = address.checked_sub(child)?;
+        (offset < size).then(|| parent.wrapping_add(offset))
+    })
+}
+
+/// Iterator over `(address, size)` pairs encoded with the given cell counts.
+pub struct CellPairs<'a> {
+    data: &'a [u8],
+    address_cells: u32,
+    size_cells: u32,
+}
+
+impl<'a> CellPairs<'a> {
+    pub fn new(data: &'a [u8], address_cells: u32, size_cells: u32) -> Self {
+        Self {
+            data,
+            address_cells,
+            size_cells,
+        }
+    }
+}
+
+impl Iterator for CellPairs<'_> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<(u64, u64)> {
+        let entry_size = (self.address_cells + self.size_cells) as usize * 4;
+        if entry_size == 0 || self.data.len() < entry_size {
+            return None;
+        }
+        let address = read_cells(self.data, self.address_cells)?;
+        let size = read_cells(&self.data[self.address_cells as usize * 4..], self.size_cells)?;
+        self.data = &self.data[entry_size..];
+        Some((address, size))
+    }
+}
+
+static FDT: LazyInit<Fdt<'static>> = LazyInit::new();
+
+/// Validates the device tree blob at physical address `paddr` and makes it
+/// available through [`get`].
+pub fn init(paddr: usize) -> Result<(), FdtError> {
+    if paddr == 0 {
+        return Err(FdtError::BadMagic);
+    }
+    let fdt = unsafe { Fdt::from_ptr(phys_to_virt(paddr) as *const u8)? };
+    FDT.init_once(fdt);
+    Ok(())
+}
+
+/// Returns the device tree passed at boot, if a valid one was found.
+pub fn get() -> Option<&'static Fdt<'static>> {
+    FDT.get()
+}
+
+/// Returns whether `controller` is an Arm GIC, whose interrupt specifiers
+/// are `<type number flags>`.
+fn is_gic(controller: &Node) -> bool {
+    controller
+        .property_u32("#interrupt-cells")
+        .is_some_and(|cells| cells >= 3)
+        && controller
+            .compatible()
+            .any(|c| c.starts_with("arm,gic") || c.ends_with("-gic"))
+}
+
+/// Converts a GIC interrupt specifier to an interrupt ID: SPIs start at 32
+/// and PPIs at 16.
+fn gic_intid(specifier: &[u8]) -> Option<u32> {
+    let number = be32(specifier, 4)?;
+    match be32(specifier, 0)? {
+        0 => number.checked_add(32),
+        1 => number.checked_add(16),
+        _ => None,
+    }
+}
+
+/// Adds the devices described by the device tree to the device registry.
+///
+/// Every available node with a `compatible` property is registered, except
+/// the root. Interrupts are only decoded for devices wired to a GIC.
+pub fn register_devices() {
+    let Some(fdt) = get() else {
+        return;
+    };
+    for node in fdt.all_nodes().skip(1) {
+        if !node.is_available() {
+            continue;
+        }
+        let compatible: Vec<_> = node.compatible().collect();
+        if compatible.is_empty() {
+            continue;
+        }
+        let irqs = match node.interrupt_parent() {
+            Some(controller) if is_gic(&controller) => {
+                node.interrupts().filter_map(gic_intid).collect()
+            }
+            _ => Vec::new(),
+        };
+        registry::register(Device {
+            name: node.name(),
+            compatible,
+            regs: node.regions().collect(),
+            irqs,
+            source: DeviceSource::DeviceTree(node),
+        });
+    }
+}
@@ -6,12 +6,28 @@
 extern crate log;
 
 extern crate alloc;
+#[cfg(feature = "plat-d3000m")]
 extern crate axplat_aarch64_d3000m_n80_laptop;
+#[cfg(feature = "plat-qemu-virt")]
+extern crate axplat_aarch64_qemu_virt;
+#[cfg(all(feature = "plat-d3000m", feature = "plat-qemu-virt"))]
+compile_error!("features `plat-d3000m` and `plat-qemu-virt` are mutually exclusive");
 
+#[macro_use]
+mod console;
+#[macro_use]
+mod cmdline;
+mod acpi;
 mod config;
+mod drivers;
+mod fdt;
+#[cfg(feature = "smp")]
+mod smp;
+#[cfg(feature = "irq")]
+mod trap;
 mod utils;
 mod vga;
-// mod vga;
+#[cfg(feature = "ktest")]
 mod test;
 
 fn init_kernel(cpu_id: usize, arg: usize) {
@@ -22,60 +38,70 @@ fn init_kernel(cpu_id: usize, arg: usize) {
     axplat::init::init_later(cpu_id, arg);
 }
 
-// UART0 基地址 (QEMU virt 机器)
-const UART0_BASE: usize = 0xffff_0000_1800_2000;
-// const UART0_BASE: usize = 0x09000000;
-
-/// 向 UART 写入一个字符
-fn uart_putc(c: u8) {
-    unsafe {
-        let uart = UART0_BASE as *mut u8;
-        core::ptr::write_volatile(uart, c);
-    }
-}
-
-/// 打印字符串到 UART
-fn uart_puts(s: &str) {
-    for c in s.bytes() {
-        uart_putc(c);
-    }
-}
-
 #[axplat::main]
-pub fn rust_main(_cpu_id: usize, _arg: usize) -> ! {
+pub fn rust_main(cpu_id: usize, arg: usize) -> ! {
     // utils::mem::clear_bss();
-    // init_kernel(cpu_id, arg);
-
-    // uart_puts("Hello, RSTiny World wseirfhw!\n");
+    early_println!("RSTiny booting on CPU {cpu_id}, arg = {arg:#x}");
 
-    // axplat::console_println!("Hello, RSTiny!");
+    if let Err(err) = fdt::init(arg) {
+        early_println!("No valid device tree at {arg:#x}: {err:?}");
+    }
+    cmdline::init();
+    if let Some((rsdp, source)) = acpi::locate_rsdp()
+        && let Err(err) = acpi::init(rsdp)
+    {
+        early_println!("Invalid ACPI RSDP at {rsdp:#x} (from {source}): {err:?}");
+    }
 
-    // uart_puts("Hello, RSTiny World 12342342!\n");
+    init_kernel(cpu_id, arg);
+    console::init();
+
+    utils::logging::log_init();
+
+    let mut ram = utils::mem::free_ram_regions();
+    let heap_ram = ram.take_front(utils::heap_allocator::HEAP_SIZE.get().0);
+    utils::heap_allocator::init(&heap_ram);
+    utils::frame_allocator::init(&ram);
+    utils::pstore::init();
+    drivers::registry::init();
+    #[cfg(feature = "smp")]
+    smp::start_secondary_cpus();
+
+    info!("Hello, RSTiny!");
+    // 打印启动期间中断处理函数推迟的日志
+    log::logger().flush();
+
+    #[cfg(feature = "ktest")]
+    {
+        let passed = test::run_tests();
+        if let Some(method) = test::TEST_EXIT.get() {
+            test::exit(method, passed);
+        }
+    }
 
-    // 初始化 VGA framebuffer
-    // vga::init();
-    
     // 启动图形显示
-    vga::show_img()
-
-
-    // vga::print_hello_world();
-    // init_kernel(cpu_id, arg);
+    vga::show_img();
 
-    // axplat::console_println!("Hello, RSTiny!");
-
-    // utils::logging::log_init();
-
-    // info!("Logging initialized. This is an info message.");
-
-    // test::run_allocator_tests();
+    console::shell::run()
+}
 
-    // axplat::power::system_off()
+#[cfg(feature = "smp")]
+#[axplat::secondary_main]
+pub fn rust_main_secondary(cpu_id: usize) -> ! {
+    smp::secondary_entry(cpu_id)
 }
 
+/// Number of recent log records printed by the panic handler.
+const PANIC_DUMP_RECORDS: usize = 32;
+
 #[cfg(all(target_os = "none", not(test)))]
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    axplat::console_println!("{info}");
+    #[cfg(feature = "ktest")]
+    test::recover(info);
+    utils::pstore::record_panic(info);
+    console::emergency_print(format_args!("{info}\n"));
+    utils::logging::ring::dump_emergency(PANIC_DUMP_RECORDS);
+    console::emergency_print(format_args!("{}", utils::heap_allocator::try_stats()));
     axplat::power::system_off()
 }
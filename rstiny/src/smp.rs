@@ -0,0 +1,103 @@
+//! Secondary CPU bring-up (`smp` feature).
+//!
+//! The secondary CPUs have no scheduler to run: once started they wait for
+//! jobs published with [`run_on_all`], which is enough for SMP tests and
+//! benchmarks.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::config::MAX_CPUS;
+use crate::fdt;
+use crate::utils::arch;
+use crate::utils::frame_allocator::{self, PAGE_SIZE};
+
+/// Stack size of each secondary CPU.
+const SECONDARY_STACK_SIZE: usize = 0x4_0000; // 256 KiB
+/// Spins to wait for a secondary CPU before giving up on it.
+const BOOT_TIMEOUT_SPINS: usize = 100_000_000;
+
+/// Number of CPUs running, the boot CPU included.
+static ONLINE: AtomicUsize = AtomicUsize::new(1);
+static JOB: AtomicUsize = AtomicUsize::new(0);
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+static DONE: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of CPUs described by the device tree, capped to
+/// [`MAX_CPUS`].
+pub fn cpu_count() -> usize {
+    let count = fdt::get()
+        .and_then(|fdt| fdt.find_node("/cpus"))
+        .map(|cpus| {
+            cpus.children()
+                .filter(|cpu| cpu.property_str("device_type") == Some("cpu") && cpu.is_available())
+                .count()
+        })
+        .unwrap_or(1);
+    count.clamp(1, MAX_CPUS)
+}
+
+/// Returns the number of CPUs running.
+pub fn online_cpus() -> usize {
+    ONLINE.load(Ordering::Acquire)
+}
+
+/// Starts the secondary CPUs and waits for them to come up. Returns the
+/// number of CPUs online.
+pub fn start_secondary_cpus() -> usize {
+    for cpu_id in 1..cpu_count() {
+        let Some(stack) =
+            frame_allocator::alloc_frames(SECONDARY_STACK_SIZE / PAGE_SIZE, PAGE_SIZE)
+        else {
+            warn!("smp: no memory for the stack of CPU {cpu_id}");
+            break;
+        };
+        let online = online_cpus();
+        axplat::power::cpu_boot(cpu_id, stack.paddr + stack.size());
+        let mut spins = 0;
+        while online_cpus() == online && spins < BOOT_TIMEOUT_SPINS {
+            core::hint::spin_loop();
+            spins += 1;
+        }
+        if online_cpus() == online {
+            warn!("smp: CPU {cpu_id} did not come up");
+            frame_allocator::dealloc_frames(stack);
+            break;
+        }
+    }
+    let online = online_cpus();
+    info!("smp: {online} CPUs online");
+    online
+}
+
+/// Entry of the secondary CPUs, called by the platform once the MMU is on.
+pub fn secondary_entry(cpu_id: usize) -> ! {
+    axplat::init::init_early_secondary(cpu_id);
+    axplat::init::init_later_secondary(cpu_id);
+    debug!("smp: CPU {cpu_id} online (MPIDR id {})", arch::cpu_id());
+    let mut seen = GENERATION.load(Ordering::Acquire);
+    ONLINE.fetch_add(1, Ordering::AcqRel);
+    loop {
+        let generation = GENERATION.load(Ordering::Acquire);
+        if generation == seen {
+            core::hint::spin_loop();
+            continue;
+        }
+        seen = generation;
+        let job: fn(usize) = unsafe { core::mem::transmute(JOB.load(Ordering::Acquire)) };
+        job(cpu_id);
+        DONE.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// Runs `job(cpu_id)` on every online CPU, the calling one included, and
+/// waits for all of them to finish.
+pub fn run_on_all(job: fn(usize)) {
+    let secondaries = online_cpus() - 1;
+    DONE.store(0, Ordering::Release);
+    JOB.store(job as usize, Ordering::Release);
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+    job(0);
+    while DONE.load(Ordering::Acquire) < secondaries {
+        core::hint::spin_loop();
+    }
+}
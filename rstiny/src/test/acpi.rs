@@ -0,0 +1,247 @@
+//! ACPI table parser tests
+
+use alloc::vec::Vec;
+
+use rstiny_macros::ktest;
+
+use crate::acpi::fadt::{Fadt, PsciConduit};
+use crate::acpi::gtdt::{Gtdt, TimerIrq};
+use crate::acpi::madt::Madt;
+use crate::acpi::mcfg::{Ecam, Mcfg};
+use crate::acpi::{AcpiError, SDT_HEADER_SIZE, Sdt, efi};
+use crate::utils::mem::{MemRegion, virt_to_phys};
+
+/// Builds a table with a valid checksum, leaked since tables are `'static`
+fn table(signature: &[u8; 4], revision: u8, body: &[u8]) -> &'static mut [u8] {
+    let mut bytes = Vec::with_capacity(SDT_HEADER_SIZE + body.len());
+    bytes.extend_from_slice(signature);
+    bytes.extend_from_slice(&((SDT_HEADER_SIZE + body.len()) as u32).to_le_bytes());
+    bytes.push(revision);
+    bytes.resize(SDT_HEADER_SIZE, 0);
+    bytes.extend_from_slice(body);
+    let sum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    bytes[9] = sum.wrapping_neg();
+    bytes.leak()
+}
+
+fn paddr(bytes: &[u8]) -> usize {
+    virt_to_phys(bytes.as_ptr() as usize)
+}
+
+fn parse(bytes: &[u8]) -> Sdt {
+    unsafe { Sdt::from_paddr(paddr(bytes)) }.expect("Valid table rejected")
+}
+
+/// Appends little-endian fields to a table body
+#[derive(Default)]
+struct Body(Vec<u8>);
+
+impl Body {
+    fn u8(mut self, value: u8) -> Self {
+        self.0.push(value);
+        self
+    }
+
+    fn u16(mut self, value: u16) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u32(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u64(mut self, value: u64) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Pads with zeros up to `len` bytes after the table header
+    fn pad(mut self, len: usize) -> Self {
+        self.0.resize(len - SDT_HEADER_SIZE, 0);
+        self
+    }
+}
+
+/// GICC structure of ACPI 6.0 (80 bytes)
+fn gicc(uid: u32, flags: u32, mpidr: u64, gicr_base: u64) -> Body {
+    let start = Body::default()
+        .u8(0x0b)
+        .u8(80)
+        .u16(0)
+        .u32(uid)
+        .u32(uid)
+        .u32(flags);
+    let mut gicc = start.u32(0).u32(23).u64(0).u64(0).u64(0).u64(0).u32(25);
+    gicc = gicc.u64(gicr_base).u64(mpidr).u32(0);
+    assert_eq!(gicc.0.len(), 80);
+    gicc
+}
+
+/// GIC layout and CPUs are read from the MADT
+#[ktest]
+fn madt_gic_layout() {
+    let mut body = Body::default().u32(0).u32(0);
+    body.0.extend(gicc(0, 1, 0x0, 0).0);
+    body.0.extend(gicc(1, 0, 0x1, 0).0);
+    body.0.extend(gicc(2, 1 << 3, 0x100, 0).0);
+    // GICD（版本 0，需根据其他结构推断）、GICR 区域、ITS
+    body = body
+        .u8(0x0c)
+        .u8(24)
+        .u16(0)
+        .u32(0)
+        .u64(0x3000_0000)
+        .u32(0)
+        .u8(0)
+        .u8(0)
+        .u16(0);
+    body = body.u8(0x0e).u8(16).u16(0).u64(0x3010_0000).u32(0x20_0000);
+    body = body.u8(0x0f).u8(20).u16(0).u32(7).u64(0x3004_0000).u32(0);
+    let madt = Madt::parse(&parse(table(b"APIC", 5, &body.0))).expect("MADT rejected");
+
+    assert_eq!(
+        madt.gicd(),
+        Some((0x3000_0000, 3)),
+        "GIC version not inferred"
+    );
+    let cpus: Vec<_> = madt.cpus().map(|cpu| (cpu.uid, cpu.mpidr)).collect();
+    assert_eq!(cpus, [(0, 0x0), (2, 0x100)], "Disabled CPU listed");
+    let cpu = madt.cpus().next().unwrap();
+    assert_eq!((cpu.pmu_gsiv, cpu.vgic_maintenance_gsiv), (23, 25));
+    assert_eq!(madt.entries().count(), 6);
+}
+
+/// Timer interrupts, counter frames and watchdogs are read from the GTDT
+#[ktest]
+fn gtdt_timers() {
+    let mut body = Body::default().u64(u64::MAX).u32(0);
+    body = body
+        .u32(29)
+        .u32(0)
+        .u32(30)
+        .u32(0)
+        .u32(27)
+        .u32(0b10)
+        .u32(26)
+        .u32(0);
+    body = body.u64(0x2a81_0000).u32(1).u32(104).u32(0).u32(0);
+    body = body
+        .u8(1)
+        .u16(28)
+        .u8(0)
+        .u64(0x2a44_0000)
+        .u64(0x2a45_0000)
+        .u32(48)
+        .u32(0);
+    let gtdt = Gtdt::parse(&parse(table(b"GTDT", 3, &body.0))).expect("GTDT rejected");
+
+    assert_eq!(gtdt.cnt_control_base, None);
+    assert_eq!(gtdt.cnt_read_base, Some(0x2a81_0000));
+    assert_eq!(gtdt.non_secure_el1.map(|t| t.gsiv), Some(30));
+    assert_eq!(
+        gtdt.virtual_el1,
+        Some(TimerIrq {
+            gsiv: 27,
+            edge: false,
+            active_low: true
+        })
+    );
+    assert_eq!(gtdt.virtual_el2, None);
+    let watchdogs: Vec<_> = gtdt
+        .watchdogs()
+        .map(|w| (w.refresh_frame, w.control_frame, w.gsiv))
+        .collect();
+    assert_eq!(watchdogs, [(0x2a44_0000, 0x2a45_0000, 48)]);
+}
+
+/// ECAM regions come from the MCFG, and a bad checksum is refused
+#[ktest]
+fn mcfg_ecam() {
+    let body = Body::default()
+        .u64(0)
+        .u64(0x4000_0000)
+        .u16(1)
+        .u8(2)
+        .u8(3)
+        .u32(0);
+    let bytes = table(b"MCFG", 1, &body.0);
+    let mcfg = Mcfg::parse(&parse(bytes)).expect("MCFG rejected");
+    assert_eq!(
+        parse(bytes).region(),
+        MemRegion::new(paddr(bytes), SDT_HEADER_SIZE + 24)
+    );
+    let ecam = mcfg.ecams().next().expect("No ECAM region");
+    assert_eq!(
+        ecam,
+        Ecam {
+            base: 0x4000_0000,
+            segment: 1,
+            start_bus: 2,
+            end_bus: 3
+        }
+    );
+    assert_eq!(ecam.region(), (0x4020_0000, 0x20_0000));
+
+    bytes[SDT_HEADER_SIZE] ^= 1;
+    assert_eq!(
+        unsafe { Sdt::from_paddr(paddr(bytes)) }.err(),
+        Some(AcpiError::BadChecksum(*b"MCFG"))
+    );
+}
+
+/// PSCI conduit and DSDT address come from the FADT
+#[ktest]
+fn fadt_fields() {
+    let body = Body::default()
+        .u32(0)
+        .u32(0x1000)
+        .pad(112)
+        .u32(1 << 20)
+        .pad(129)
+        .u16(0b11)
+        .u8(1)
+        .pad(140)
+        .u64(0x9000_0000)
+        .pad(268);
+    let fadt = Fadt::parse(&parse(table(b"FACP", 6, &body.0))).expect("FADT rejected");
+    assert_eq!(fadt.version, (6, 1));
+    assert!(fadt.hw_reduced, "Hardware-reduced flag lost");
+    assert_eq!(fadt.psci, Some(PsciConduit::Hvc));
+    assert_eq!(fadt.dsdt_paddr, 0x9000_0000, "X_DSDT not preferred");
+}
+
+/// The RSDP is found through the UEFI configuration table, which is kept out
+/// of free RAM
+#[ktest]
+fn efi_configuration_table() {
+    let acpi20 = [
+        0x71, 0xe8, 0x68, 0x88, 0xf1, 0xe4, 0xd3, 0x11, 0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88,
+        0x81,
+    ];
+    let mut entries = Vec::new();
+    for (guid, pointer) in [([0xaa; 16], 0x1111), (acpi20, 0x8000_1000u64)] {
+        entries.extend_from_slice(&guid);
+        entries.extend_from_slice(&pointer.to_le_bytes());
+    }
+    let mut system_table = Body::default()
+        .u64(0x5453_5953_2049_4249)
+        .u32(0x0002_0046)
+        .u32(120);
+    system_table.0.resize(104, 0);
+    system_table = system_table.u64(2).u64(paddr(&entries) as u64);
+
+    let found = unsafe { efi::find_rsdp(paddr(&system_table.0)) };
+    assert_eq!(found, Some(0x8000_1000));
+    // 系统表和配置表都不能当作空闲内存
+    assert_eq!(
+        unsafe { efi::table_regions(paddr(&system_table.0)) },
+        Some([
+            MemRegion::new(paddr(&system_table.0), 120),
+            MemRegion::new(paddr(&entries), 48),
+        ])
+    );
+    system_table.0[0] = 0;
+    assert_eq!(unsafe { efi::find_rsdp(paddr(&system_table.0)) }, None);
+}
@@ -0,0 +1,302 @@
+//! Memory allocator stress test module
+//!
+//! Tests beyond the basic collections: over-aligned layouts, `realloc`,
+//! a random workload driven by a seeded PRNG, fragmentation and heap
+//! exhaustion. The random workload uses the seed of the test run, printed by
+//! the runner; a failing run can be reproduced with `test_seed=` on the kernel
+//! command line.
+
+use alloc::alloc::{alloc, dealloc, realloc};
+use alloc::vec::Vec;
+use core::alloc::Layout;
+
+use rstiny_macros::ktest;
+
+use super::runner;
+use crate::utils::heap_allocator;
+
+/// Operations of the random workload
+const RANDOM_OPS: usize = 20_000;
+/// Live blocks kept by the random workload
+const RANDOM_SLOTS: usize = 256;
+/// Block size used to exhaust the heap
+const EXHAUSTION_BLOCK: usize = 64 * 1024;
+/// Maximum number of blocks allocated while exhausting the heap
+const EXHAUSTION_MAX_BLOCKS: usize = 4096;
+
+/// xorshift64* generator
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Byte written at `offset` of a block tagged `tag`
+fn pattern(tag: usize, offset: usize) -> u8 {
+    (tag.wrapping_mul(31) ^ offset.wrapping_mul(7)) as u8
+}
+
+unsafe fn fill(ptr: *mut u8, len: usize, tag: usize) {
+    for offset in 0..len {
+        unsafe { *ptr.add(offset) = pattern(tag, offset) };
+    }
+}
+
+unsafe fn check(ptr: *const u8, len: usize, tag: usize) -> bool {
+    (0..len).all(|offset| unsafe { *ptr.add(offset) } == pattern(tag, offset))
+}
+
+/// Layouts aligned up to a page
+#[ktest]
+fn over_aligned() {
+    for shift in 4..=12 {
+        let align = 1usize << shift;
+        for size in [1, align / 2, align, align * 3 + 1] {
+            let layout = Layout::from_size_align(size, align).unwrap();
+            let ptr = unsafe { alloc(layout) };
+            assert!(
+                !ptr.is_null(),
+                "Over-aligned allocation of {:?} failed",
+                layout
+            );
+            assert!(
+                ptr as usize % align == 0,
+                "Misaligned block: {:p} is not aligned to {:#x}",
+                ptr,
+                align
+            );
+            unsafe {
+                fill(ptr, size, shift);
+                assert!(check(ptr, size, shift), "Over-aligned block corrupted");
+                dealloc(ptr, layout);
+            }
+        }
+    }
+
+    #[repr(align(4096))]
+    struct Page([u8; 4096]);
+    let page = alloc::boxed::Box::new(Page([0x5a; 4096]));
+    assert!(
+        &*page as *const Page as usize % 4096 == 0 && page.0[4095] == 0x5a,
+        "Page-aligned Box failed"
+    );
+}
+
+/// `realloc` keeps the contents when growing and shrinking
+#[ktest]
+fn realloc_grow_shrink() {
+    for align in [8, 64, 512] {
+        let mut layout = Layout::from_size_align(8, align).unwrap();
+        let mut ptr = unsafe { alloc(layout) };
+        assert!(!ptr.is_null(), "Initial allocation failed");
+        unsafe { fill(ptr, layout.size(), align) };
+        // 先逐步增长到 64 KiB，再逐步缩小回 8 字节
+        let sizes = (4..=16)
+            .map(|shift| 1 << shift)
+            .chain((3..16).rev().map(|shift| 1 << shift));
+        for new_size in sizes {
+            let kept = layout.size().min(new_size);
+            ptr = unsafe { realloc(ptr, layout, new_size) };
+            assert!(!ptr.is_null(), "Realloc to {} bytes failed", new_size);
+            layout = Layout::from_size_align(new_size, align).unwrap();
+            assert!(ptr as usize % align == 0, "Realloc lost the alignment");
+            assert!(
+                unsafe { check(ptr, kept, align) },
+                "Realloc lost the contents"
+            );
+            unsafe { fill(ptr, new_size, align) };
+        }
+        unsafe { dealloc(ptr, layout) };
+    }
+}
+
+/// Random interleaving of allocations and frees with content checks
+#[ktest]
+fn random_workload() {
+    let seed = runner::seed();
+    let mut rng = Rng::new(seed);
+    let mut slots: Vec<Option<(*mut u8, Layout, usize)>> =
+        (0..RANDOM_SLOTS).map(|_| None).collect();
+
+    for op in 0..RANDOM_OPS {
+        let slot = rng.below(RANDOM_SLOTS);
+        if let Some((ptr, layout, tag)) = slots[slot].take() {
+            assert!(
+                unsafe { check(ptr, layout.size(), tag) },
+                "Block {:p} ({:?}) corrupted at operation {} (seed {:#x})",
+                ptr,
+                layout,
+                op,
+                seed
+            );
+            unsafe { dealloc(ptr, layout) };
+            continue;
+        }
+        // 大多数为小块，偶尔分配大块
+        let size = match rng.below(16) {
+            0 => 1 + rng.below(64 * 1024),
+            1..=3 => 1 + rng.below(4096),
+            _ => 1 + rng.below(256),
+        };
+        let align = 1 << rng.below(9);
+        let layout = Layout::from_size_align(size, align).unwrap();
+        let ptr = unsafe { alloc(layout) };
+        assert!(
+            !ptr.is_null() && ptr as usize % align == 0,
+            "Allocation of {:?} returned {:p} at operation {} (seed {:#x})",
+            layout,
+            ptr,
+            op,
+            seed
+        );
+        unsafe { fill(ptr, size, op) };
+        slots[slot] = Some((ptr, layout, op));
+    }
+    for (ptr, layout, _) in slots.into_iter().flatten() {
+        unsafe { dealloc(ptr, layout) };
+    }
+}
+
+/// Interleaved frees leave holes; freeing everything closes them again
+#[ktest]
+fn fragmentation() {
+    #[cfg(not(feature = "debug-alloc"))]
+    heap_allocator::flush_cpu_caches();
+    let before = heap_allocator::stats().largest_free_block.unwrap_or(0);
+
+    let small = Layout::from_size_align(256, 16).unwrap();
+    let mut blocks: Vec<*mut u8> = Vec::with_capacity(1024);
+    for _ in 0..1024 {
+        let ptr = unsafe { alloc(small) };
+        assert!(!ptr.is_null(), "Small allocation failed");
+        blocks.push(ptr);
+    }
+    // 释放一半，留下交错的空洞
+    let mut kept = Vec::with_capacity(blocks.len() / 2 + 1);
+    for (i, ptr) in blocks.into_iter().enumerate() {
+        if i % 2 == 0 {
+            unsafe { dealloc(ptr, small) };
+        } else {
+            kept.push(ptr);
+        }
+    }
+    // 空洞放不下的大块仍然可以分配
+    let large = Layout::from_size_align(16 * 1024, 16).unwrap();
+    let ptr = unsafe { alloc(large) };
+    assert!(
+        !ptr.is_null(),
+        "Large allocation failed in a fragmented heap"
+    );
+    unsafe { dealloc(ptr, large) };
+    for ptr in kept {
+        unsafe { dealloc(ptr, small) };
+    }
+
+    #[cfg(not(feature = "debug-alloc"))]
+    heap_allocator::flush_cpu_caches();
+    let after = heap_allocator::stats().largest_free_block.unwrap_or(0);
+    info!(
+        "largest free block: {} bytes before, {} bytes after ({} backend)",
+        before,
+        after,
+        heap_allocator::BACKEND_NAME
+    );
+    // debug-alloc 的隔离区会暂时扣留已释放的块
+    if heap_allocator::BACKEND_COALESCES && !cfg!(feature = "debug-alloc") {
+        assert!(after >= before, "Free blocks were not merged back");
+    }
+}
+
+/// Allocating until the heap is exhausted fails cleanly and the memory
+/// can be reused afterwards
+#[ktest]
+fn exhaustion() {
+    let layout = Layout::from_size_align(EXHAUSTION_BLOCK, 16).unwrap();
+    let mut blocks: Vec<*mut u8> = Vec::with_capacity(EXHAUSTION_MAX_BLOCKS);
+    let mut exhausted = false;
+    while blocks.len() < EXHAUSTION_MAX_BLOCKS {
+        let ptr = unsafe { alloc(layout) };
+        if ptr.is_null() {
+            exhausted = true;
+            break;
+        }
+        // 每页写一个字节，确认内存真实可用
+        for offset in (0..EXHAUSTION_BLOCK).step_by(4096) {
+            unsafe { *ptr.add(offset) = blocks.len() as u8 };
+        }
+        blocks.push(ptr);
+    }
+    info!(
+        "heap exhausted after {} blocks of {} KiB",
+        blocks.len(),
+        EXHAUSTION_BLOCK / 1024
+    );
+
+    assert!(exhausted, "Heap not exhausted");
+    assert!(!blocks.is_empty(), "No block could be allocated");
+    for (i, &ptr) in blocks.iter().enumerate() {
+        assert!(
+            (0..EXHAUSTION_BLOCK)
+                .step_by(4096)
+                .all(|offset| unsafe { *ptr.add(offset) } == i as u8),
+            "Block overwritten while exhausting the heap"
+        );
+    }
+    let count = blocks.len();
+    for ptr in blocks {
+        unsafe { dealloc(ptr, layout) };
+    }
+
+    // 释放后应能重新分配同样多的块
+    let mut again = Vec::with_capacity(count);
+    for _ in 0..count {
+        let ptr = unsafe { alloc(layout) };
+        if ptr.is_null() {
+            break;
+        }
+        again.push(ptr);
+    }
+    let reused = again.len();
+    for ptr in again {
+        unsafe { dealloc(ptr, layout) };
+    }
+    if heap_allocator::BACKEND_COALESCES && !cfg!(feature = "debug-alloc") {
+        assert!(reused == count, "Memory not reusable after exhaustion");
+    }
+}
+
+/// A valid layout that overflows once padded with the debug header and red
+/// zones fails like any other allocation instead of panicking
+#[cfg(feature = "debug-alloc")]
+#[ktest]
+fn debug_padding_overflow() {
+    let huge = isize::MAX as usize - 15;
+    let layout = Layout::from_size_align(huge, 16).unwrap();
+    assert!(
+        unsafe { alloc(layout) }.is_null(),
+        "Oversized block allocated"
+    );
+
+    let small = Layout::from_size_align(64, 16).unwrap();
+    let ptr = unsafe { alloc(small) };
+    assert!(!ptr.is_null());
+    assert!(
+        unsafe { realloc(ptr, small, huge) }.is_null(),
+        "Oversized block reallocated"
+    );
+    // realloc 失败时原来的块仍然有效
+    unsafe { dealloc(ptr, small) };
+}
@@ -0,0 +1,183 @@
+//! Command line parameter tests
+
+use alloc::vec::Vec;
+
+use rstiny_macros::ktest;
+
+use crate::cmdline::{self, Param, ParamDesc, ParamError, ParamValue, Size};
+use crate::console::probe::ConsoleSpec;
+use crate::vga::{FbInfo, FbParam};
+
+/// Sizes take an optional binary suffix and must fit in a `usize`
+#[ktest]
+fn sizes() {
+    assert_eq!(Size::parse("4096"), Ok(Size(4096)));
+    assert_eq!(Size::parse("4k"), Ok(Size(4 << 10)));
+    assert_eq!(Size::parse("64M"), Ok(Size(64 << 20)));
+    assert_eq!(Size::parse("2G"), Ok(Size(2 << 30)));
+    assert_eq!(Size::parse("0x10K"), Ok(Size(16 << 10)));
+    assert_eq!(Size::parse("99999999999G"), Err("size out of range"));
+    for bad in ["", "M", "12X", "-1K", "1.5G"] {
+        assert!(Size::parse(bad).is_err(), "`{bad}` accepted");
+    }
+}
+
+/// Integers are decimal or `0x` hexadecimal
+#[ktest]
+fn integers() {
+    assert_eq!(usize::parse("42"), Ok(42));
+    assert_eq!(usize::parse("0x1f"), Ok(0x1f));
+    assert_eq!(u64::parse("0xffffffffffffffff"), Ok(u64::MAX));
+    for bad in ["", "0x", "0xg", "-1", "1_000", "18446744073709551616"] {
+        assert_eq!(
+            u64::parse(bad),
+            Err("expected an integer"),
+            "`{bad}` accepted"
+        );
+    }
+}
+
+/// A bare name enables a boolean; the usual spellings are accepted
+#[ktest]
+fn booleans() {
+    for on in ["", "1", "y", "yes", "on", "true"] {
+        assert_eq!(bool::parse(on), Ok(true), "`{on}`");
+    }
+    for off in ["0", "n", "no", "off", "false"] {
+        assert_eq!(bool::parse(off), Ok(false), "`{off}`");
+    }
+    assert_eq!(bool::parse("maybe"), Err("expected a boolean"));
+}
+
+/// An optional parameter is `None` until given, and keeps the errors of the
+/// inner type
+#[ktest]
+fn optional_values() {
+    assert_eq!(Option::<usize>::parse("0x10"), Ok(Some(16)));
+    assert_eq!(Option::<usize>::parse("x"), Err("expected an integer"));
+
+    static LIMIT: Param<Option<usize>> = Param::new(None);
+    assert_eq!((LIMIT.get(), LIMIT.is_set()), (None, false));
+    LIMIT.set("7").unwrap();
+    assert_eq!((LIMIT.get(), LIMIT.is_set()), (Some(7), true));
+}
+
+fn geometry(info: FbInfo) -> (usize, usize, usize, usize) {
+    (info.paddr, info.width, info.height, info.stride)
+}
+
+/// `fb=` geometries: default stride, explicit stride, and invalid or
+/// overflowing sizes
+#[ktest]
+fn framebuffer_geometry() {
+    assert!(FbParam::parse("off").unwrap().0.is_none());
+    let fb = FbParam::parse("0x80000000,640x480").unwrap().0.unwrap();
+    assert_eq!(geometry(fb), (0x8000_0000, 640, 480, 2560));
+    let fb = FbParam::parse("0x80000000,640x480,4096")
+        .unwrap()
+        .0
+        .unwrap();
+    assert_eq!(fb.stride, 4096);
+
+    // 每行字节数小于 宽 * 4
+    assert_eq!(
+        FbParam::parse("0x80000000,640x480,2556").err(),
+        Some("invalid framebuffer geometry")
+    );
+    assert_eq!(
+        FbParam::parse("0x80000000,0x480").err(),
+        Some("invalid framebuffer geometry")
+    );
+    assert_eq!(
+        FbParam::parse("0,4611686018427387904x1").err(),
+        Some("framebuffer width too large")
+    );
+    assert_eq!(
+        FbParam::parse("0,16x0x4000000000000000").err(),
+        Some("framebuffer too large")
+    );
+    assert_eq!(
+        FbParam::parse("0xffffffffffff0000,1024x768").err(),
+        Some("framebuffer too large")
+    );
+    for bad in ["", "0x1000", "0x1000,640", "0x1000,640x480,4096,1", "on"] {
+        assert!(FbParam::parse(bad).is_err(), "`{bad}` accepted");
+    }
+}
+
+/// `console=` takes the `earlycon` syntax of a PL011 or an 8250
+#[ktest]
+fn console_specs() {
+    assert!(matches!(
+        ConsoleSpec::parse("pl011,0x9000000"),
+        Ok(ConsoleSpec::Pl011 { paddr: 0x900_0000 })
+    ));
+    assert!(matches!(
+        ConsoleSpec::parse("uart8250,mmio,0x28001000"),
+        Ok(ConsoleSpec::Ns16550 {
+            paddr: 0x2800_1000,
+            mmio32: false
+        })
+    ));
+    assert!(matches!(
+        ConsoleSpec::parse("uart8250,mmio32,0x28001000"),
+        Ok(ConsoleSpec::Ns16550 {
+            paddr: 0x2800_1000,
+            mmio32: true
+        })
+    ));
+    for bad in [
+        "",
+        "pl011",
+        "pl011,0x",
+        "pl011,0x9000000,115200",
+        "uart8250,io,0x3f8",
+        "uart8250,mmio32,0x28001000,115200n8",
+        "sbi",
+    ] {
+        assert!(ConsoleSpec::parse(bad).is_err(), "`{bad}` accepted");
+    }
+}
+
+/// Unknown parameters and invalid values are reported; an invalid value
+/// keeps the previous one, and the last valid value wins
+#[ktest]
+fn unknown_and_invalid_parameters() {
+    static VERBOSE: Param<bool> = Param::new(false);
+    static BUFFER: Param<Size> = Param::new(Size(0));
+    let params = [
+        ParamDesc {
+            name: "test_verbose",
+            set: |value| VERBOSE.set(value),
+        },
+        ParamDesc {
+            name: "test_buffer",
+            set: |value| BUFFER.set(value),
+        },
+    ];
+
+    let mut errors = Vec::new();
+    cmdline::apply(
+        "test_verbose test_buffer=1M bogus=1 test_buffer=4K test_buffer=lots test_verbose=maybe",
+        &params,
+        |err| errors.push(err),
+    );
+    assert!(VERBOSE.get(), "Bare boolean not set");
+    assert_eq!(BUFFER.get(), Size(4 << 10));
+    assert_eq!(
+        errors,
+        [
+            ParamError::Unknown("bogus=1"),
+            ParamError::Invalid {
+                name: "test_buffer",
+                value: "lots",
+                err: "expected a size, e.g. 64M"
+            },
+            ParamError::Invalid {
+                name: "test_verbose",
+                value: "maybe",
+                err: "expected a boolean"
+            },
+        ]
+    );
+}
@@ -0,0 +1,231 @@
+//! Device tree parser and device registry tests
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use rstiny_macros::ktest;
+
+use crate::drivers::registry::{Device, DeviceSource, Registry};
+use crate::fdt::{Fdt, FdtError};
+
+const FDT_HEADER_SIZE: usize = 40;
+/// Memory reservation block holding only its terminating entry
+const RSVMAP_SIZE: usize = 16;
+
+/// Minimal device tree blob writer
+struct Builder {
+    structs: Vec<u8>,
+    strings: Vec<u8>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            structs: Vec::new(),
+            strings: Vec::new(),
+        }
+    }
+
+    fn token(&mut self, token: u32) -> &mut Self {
+        self.structs.extend_from_slice(&token.to_be_bytes());
+        self
+    }
+
+    fn pad(&mut self) {
+        self.structs.resize(self.structs.len().next_multiple_of(4), 0);
+    }
+
+    fn begin(&mut self, name: &str) -> &mut Self {
+        self.token(0x1);
+        self.structs.extend_from_slice(name.as_bytes());
+        self.structs.push(0);
+        self.pad();
+        self
+    }
+
+    fn end(&mut self) -> &mut Self {
+        self.token(0x2)
+    }
+
+    fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+        let name_offset = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.token(0x3)
+            .token(value.len() as u32)
+            .token(name_offset);
+        self.structs.extend_from_slice(value);
+        self.pad();
+        self
+    }
+
+    fn cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+        let value: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+        self.prop(name, &value)
+    }
+
+    fn string(&mut self, name: &str, value: &str) -> &mut Self {
+        let mut bytes = Vec::from(value.as_bytes());
+        bytes.push(0);
+        self.prop(name, &bytes)
+    }
+
+    /// Terminates the structure block and returns the blob
+    fn finish(&mut self) -> Vec<u8> {
+        self.token(0x9);
+        let structs_offset = FDT_HEADER_SIZE + RSVMAP_SIZE;
+        let strings_offset = structs_offset + self.structs.len();
+        let total_size = strings_offset + self.strings.len();
+        let header = [
+            0xd00d_feed,
+            total_size as u32,
+            structs_offset as u32,
+            strings_offset as u32,
+            FDT_HEADER_SIZE as u32,
+            17,
+            16,
+            0,
+            self.strings.len() as u32,
+            self.structs.len() as u32,
+        ];
+        let mut blob: Vec<u8> = header.iter().flat_map(|v| v.to_be_bytes()).collect();
+        blob.resize(structs_offset, 0);
+        blob.extend_from_slice(&self.structs);
+        blob.extend_from_slice(&self.strings);
+        blob
+    }
+}
+
+/// A GIC, a bus with `ranges`, and a bus without
+fn sample_tree() -> Vec<u8> {
+    let mut b = Builder::new();
+    b.begin("")
+        .cells("#address-cells", &[2])
+        .cells("#size-cells", &[2])
+        .cells("interrupt-parent", &[1]);
+    b.begin("interrupt-controller@8000000")
+        .string("compatible", "arm,gic-v3")
+        .cells("#interrupt-cells", &[3])
+        .prop("interrupt-controller", &[])
+        .cells("reg", &[0, 0x800_0000, 0, 0x1_0000])
+        .cells("phandle", &[1])
+        .end();
+    b.begin("soc")
+        .string("compatible", "simple-bus")
+        .cells("#address-cells", &[1])
+        .cells("#size-cells", &[1])
+        .cells("ranges", &[0, 0, 0x2800_0000, 0x100_0000]);
+    b.begin("serial@1000")
+        .prop("compatible", b"arm,pl011\0arm,primecell\0")
+        .cells("reg", &[0x1000, 0x1000])
+        .cells("interrupts", &[0, 84, 4])
+        .end();
+    b.begin("serial@2000")
+        .string("compatible", "arm,pl011")
+        .string("status", "disabled")
+        .cells("reg", &[0x2000, 0x1000])
+        .end();
+    b.end();
+    b.begin("isolated")
+        .cells("#address-cells", &[1])
+        .cells("#size-cells", &[1]);
+    b.begin("device@0").cells("reg", &[0, 0x10]).end();
+    b.end();
+    b.end();
+    b.finish()
+}
+
+/// Phandles, parents and interrupt specifiers resolve
+#[ktest]
+fn phandles() {
+    let blob = sample_tree();
+    let fdt = Fdt::new(&blob).expect("Sample tree rejected");
+    let gic = fdt.find_phandle(1).expect("Phandle 1 not found");
+    assert_eq!(gic.name(), "interrupt-controller@8000000");
+
+    let serial = fdt.find_node("/soc/serial@1000").expect("Serial not found");
+    assert_eq!(serial.parent().map(|n| n.name()), Some("soc"));
+    assert!(fdt.root().parent().is_none(), "Root has a parent");
+    assert_eq!(
+        serial.interrupt_parent().map(|n| n.name()),
+        Some(gic.name()),
+        "Inherited interrupt-parent not found"
+    );
+    let specifiers: Vec<&[u8]> = serial.interrupts().collect();
+    assert_eq!(specifiers, [&[0, 0, 0, 0, 0, 0, 0, 84, 0, 0, 0, 4][..]]);
+    assert_eq!(fdt.all_nodes().count(), 7);
+}
+
+/// `reg` is translated through the `ranges` of the parent buses
+#[ktest]
+fn ranges_translation() {
+    let blob = sample_tree();
+    let fdt = Fdt::new(&blob).unwrap();
+    let serial = fdt.find_node("/soc/serial@1000").unwrap();
+    assert_eq!(serial.reg().collect::<Vec<_>>(), [(0x1000, 0x1000)]);
+    assert_eq!(
+        serial.regions().collect::<Vec<_>>(),
+        [(0x2800_1000, 0x1000)]
+    );
+    let gic = fdt.find_node("/interrupt-controller").unwrap();
+    assert_eq!(
+        gic.regions().collect::<Vec<_>>(),
+        [(0x800_0000, 0x1_0000)]
+    );
+    // 没有 ranges 的总线上的地址无法转换
+    let device = fdt.find_node("/isolated/device@0").unwrap();
+    assert_eq!(device.reg().count(), 1);
+    assert_eq!(device.regions().count(), 0);
+}
+
+/// Truncated or unbalanced structure blocks are rejected
+#[ktest]
+fn malformed_blobs() {
+    let mut b = Builder::new();
+    b.begin("").begin("child").end();
+    assert_eq!(Fdt::new(&b.finish()).err(), Some(FdtError::BadLayout));
+
+    let mut b = Builder::new();
+    b.begin("").end().end();
+    assert_eq!(Fdt::new(&b.finish()).err(), Some(FdtError::BadLayout));
+
+    let mut blob = sample_tree();
+    blob[0] = 0;
+    assert_eq!(Fdt::new(&blob).err(), Some(FdtError::BadMagic));
+    let blob = sample_tree();
+    assert_eq!(
+        Fdt::new(&blob[..blob.len() - 1]).err(),
+        Some(FdtError::BadLayout)
+    );
+}
+
+/// Devices are found by any of their compatible strings, in order
+#[ktest]
+fn registry_lookup() {
+    let device = |name, compatible| Device {
+        name,
+        compatible: vec![compatible, "rstiny,test-device"],
+        regs: vec![(0x1000, 0x100)],
+        irqs: vec![42],
+        source: DeviceSource::Acpi(*b"TEST"),
+    };
+    let mut registry = Registry::new();
+    registry.register(device("first", "rstiny,test-a"));
+    registry.register(device("second", "rstiny,test-b"));
+
+    let names = |devices: Vec<Device>| devices.iter().map(|d| d.name).collect::<Vec<_>>();
+    assert_eq!(
+        names(registry.find_compatible("rstiny,test-device")),
+        ["first", "second"]
+    );
+    assert_eq!(names(registry.find_compatible("rstiny,test-b")), ["second"]);
+    assert!(registry.find_compatible("rstiny,absent").is_empty());
+
+    let probed = registry
+        .probe(&["rstiny,absent", "rstiny,test-b", "rstiny,test-a"])
+        .expect("Probe failed");
+    assert_eq!(
+        (probed.name, probed.base_paddr(), probed.irq()),
+        ("second", Some(0x1000), Some(42))
+    );
+}
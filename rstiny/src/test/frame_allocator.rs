@@ -0,0 +1,89 @@
+//! Physical page frame allocator tests
+
+use alloc::vec::Vec;
+
+use rstiny_macros::ktest;
+
+use crate::utils::frame_allocator::{self, FrameRange, PAGE_SIZE};
+
+/// Single frame allocation and release
+#[ktest]
+fn single_frame() {
+    let before = frame_allocator::free_frames();
+    let frame = frame_allocator::alloc_frame().expect("Frame allocation failed");
+    assert!(
+        frame.paddr % PAGE_SIZE == 0 && frame_allocator::free_frames() == before - 1,
+        "Bad frame or accounting"
+    );
+    frame_allocator::dealloc_frames(frame);
+    assert_eq!(frame_allocator::free_frames(), before, "Frame not returned");
+}
+
+/// Contiguous allocations honour the requested alignment
+#[ktest]
+fn aligned_frames() {
+    let before = frame_allocator::free_frames();
+    let mut ranges = Vec::new();
+    for (count, align) in [(3, PAGE_SIZE), (5, 0x4000), (1, 0x10_0000), (17, 0x2_0000)] {
+        let range =
+            frame_allocator::alloc_frames(count, align).expect("Contiguous allocation failed");
+        assert!(
+            range.paddr % align == 0 && range.count == count,
+            "Misaligned frame range"
+        );
+        ranges.push(range);
+    }
+    for range in ranges {
+        frame_allocator::dealloc_frames(range);
+    }
+    assert_eq!(
+        frame_allocator::free_frames(),
+        before,
+        "Free frame count not restored"
+    );
+}
+
+/// Zeroed frames are zero even after the memory was dirtied
+#[ktest]
+fn zeroed_frames() {
+    let dirty = frame_allocator::alloc_frames(2, PAGE_SIZE).expect("Frame allocation failed");
+    unsafe { core::ptr::write_bytes(dirty.as_mut_ptr(), 0x5a, dirty.size()) };
+    frame_allocator::dealloc_frames(dirty);
+
+    let range =
+        frame_allocator::alloc_zeroed_frames(2, PAGE_SIZE).expect("Frame allocation failed");
+    let bytes = unsafe { core::slice::from_raw_parts(range.as_mut_ptr(), range.size()) };
+    let zeroed = bytes.iter().all(|&byte| byte == 0);
+    frame_allocator::dealloc_frames(range);
+    assert!(zeroed, "Frames not zeroed");
+}
+
+/// The tail of a range can be freed separately
+#[ktest]
+fn partial_free() {
+    let before = frame_allocator::free_frames();
+    let range = frame_allocator::alloc_frames(8, PAGE_SIZE).expect("Frame allocation failed");
+    let tail = FrameRange {
+        paddr: range.paddr + 5 * PAGE_SIZE,
+        count: 3,
+    };
+    frame_allocator::dealloc_frames(tail);
+    frame_allocator::dealloc_frames(FrameRange { count: 5, ..range });
+    assert_eq!(
+        frame_allocator::free_frames(),
+        before,
+        "Free frame count not restored"
+    );
+}
+
+/// Freeing a frame inside a merged free block is caught
+#[ktest(should_panic = "is already free")]
+fn double_free_in_merged_block() {
+    let range = frame_allocator::alloc_frames(2, 2 * PAGE_SIZE).expect("Frame allocation failed");
+    frame_allocator::dealloc_frames(range);
+    // 两页已合并为一个块，第二页不是块首，本身没有空闲标记
+    frame_allocator::dealloc_frames(FrameRange {
+        paddr: range.paddr + PAGE_SIZE,
+        count: 1,
+    });
+}
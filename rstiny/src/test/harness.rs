@@ -0,0 +1,31 @@
+//! Tests of the test runner itself
+//!
+//! Check that panics inside `should_panic` tests are reported back to the
+//! runner, and that IRQs are unmasked while a test runs, which the deadlines
+//! depend on.
+
+use alloc::vec;
+use core::hint::black_box;
+
+use rstiny_macros::ktest;
+
+use crate::utils::arch;
+
+/// Panic expected without a message check
+#[ktest(should_panic)]
+fn should_panic_plain() {
+    panic!("expected panic");
+}
+
+/// Panic expected with a matching message
+#[ktest(should_panic = "index out of bounds")]
+fn should_panic_with_message() {
+    let v = vec![1u8];
+    let _ = v[black_box(v.len())];
+}
+
+/// Tests run with IRQs unmasked, so the watchdog can stop them
+#[ktest(timeout_ms = 1000)]
+fn irqs_enabled() {
+    assert!(!arch::irqs_disabled(), "IRQs masked while a test runs");
+}
@@ -0,0 +1,98 @@
+//! Heap contention benchmark
+//!
+//! Every online CPU runs the same mix of small allocations and frees, first
+//! with the per-CPU caches disabled (every operation takes the backend lock),
+//! then with them enabled. With the `smp` feature the secondary CPUs are
+//! started at boot, so the benchmark measures contention; otherwise it only
+//! runs on the boot CPU.
+
+use core::alloc::Layout;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use rstiny_macros::ktest;
+
+use crate::config::MAX_CPUS;
+use crate::utils::{arch, heap_allocator};
+
+/// Allocation/free pairs per CPU
+const ITERATIONS: usize = 20_000;
+/// Blocks kept live by each CPU
+const LIVE_BLOCKS: usize = 16;
+const SIZES: [usize; 8] = [16, 24, 40, 64, 100, 256, 512, 1500];
+
+static TICKS: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+fn workload(cpu_id: usize) {
+    let mut live = [(core::ptr::null_mut::<u8>(), Layout::new::<u8>()); LIVE_BLOCKS];
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64 ^ cpu_id as u64;
+    let start = arch::counter_ticks();
+    for i in 0..ITERATIONS {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        let slot = i % LIVE_BLOCKS;
+        let (ptr, layout) = live[slot];
+        if !ptr.is_null() {
+            unsafe { alloc::alloc::dealloc(ptr, layout) };
+        }
+        let layout = Layout::from_size_align(SIZES[seed as usize % SIZES.len()], 8).unwrap();
+        let ptr = unsafe { alloc::alloc::alloc(layout) };
+        if !ptr.is_null() {
+            unsafe { ptr.write(i as u8) };
+        }
+        live[slot] = (ptr, layout);
+    }
+    for (ptr, layout) in live {
+        if !ptr.is_null() {
+            unsafe { alloc::alloc::dealloc(ptr, layout) };
+        }
+    }
+    TICKS[cpu_id].store(arch::counter_ticks() - start, Ordering::Relaxed);
+}
+
+fn run_everywhere(job: fn(usize)) -> usize {
+    #[cfg(feature = "smp")]
+    {
+        crate::smp::run_on_all(job);
+        crate::smp::online_cpus()
+    }
+    #[cfg(not(feature = "smp"))]
+    {
+        job(0);
+        1
+    }
+}
+
+/// Heap throughput with the per-CPU caches off, then on, on every online CPU
+#[ktest(timeout_ms = 30000)]
+fn heap_contention() {
+    for enabled in [false, true] {
+        heap_allocator::set_cpu_caches_enabled(enabled);
+        for ticks in &TICKS {
+            ticks.store(0, Ordering::Relaxed);
+        }
+        let start = arch::counter_ticks();
+        let cpus = run_everywhere(workload);
+        let wall = arch::ticks_to_nanos(arch::counter_ticks() - start);
+        let slowest = TICKS[..cpus]
+            .iter()
+            .map(|ticks| arch::ticks_to_nanos(ticks.load(Ordering::Relaxed)))
+            .max()
+            .unwrap_or(0);
+        assert!(
+            TICKS[..cpus]
+                .iter()
+                .all(|ticks| ticks.load(Ordering::Relaxed) != 0),
+            "A CPU did not run the workload"
+        );
+        let ops = (cpus * ITERATIONS * 2) as u64;
+        info!(
+            "heap bench: per-CPU caches {}: {cpus} CPUs, {ops} ops in {} us (slowest CPU {} us), {} ns/op",
+            if enabled { "on" } else { "off" },
+            wall / 1000,
+            slowest / 1000,
+            wall * cpus as u64 / ops.max(1)
+        );
+    }
+    heap_allocator::print_stats();
+}
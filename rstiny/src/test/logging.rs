@@ -0,0 +1,32 @@
+//! Deferred logging tests
+
+use log::LevelFilter;
+use rstiny_macros::ktest;
+
+use crate::utils::arch;
+use crate::utils::logging::{self, deferred};
+
+/// Records are queued only from handlers, not whenever IRQs are masked
+#[ktest]
+fn defer_only_in_handlers() {
+    let target = module_path!();
+    let previous = logging::filter().level_for(target);
+    logging::set_target_level(target, LevelFilter::Info);
+    let cpu = arch::cpu_index();
+    log::logger().flush();
+
+    arch::disable_irqs();
+    info!("logged with IRQs masked");
+    let masked = deferred::pending(cpu);
+    deferred::enter_handler();
+    info!("logged from a handler");
+    let in_handler = deferred::pending(cpu);
+    deferred::exit_handler();
+    arch::enable_irqs();
+    log::logger().flush();
+    logging::set_target_level(target, previous);
+
+    assert_eq!(masked, 0, "Record deferred outside a handler");
+    assert_eq!(in_handler, 1, "Record printed from a handler");
+    assert_eq!(deferred::pending(cpu), 0, "Queue not drained");
+}
@@ -1,3 +1,18 @@
+//! On-target tests, registered with `#[ktest]` and run at boot by [`run_tests`].
+
+mod acpi;
 mod allocator;
+mod allocator_stress;
+mod cmdline;
+mod fdt;
+mod frame_allocator;
+mod harness;
+#[cfg(not(feature = "debug-alloc"))]
+mod heap_bench;
+mod logging;
+mod report;
+mod runner;
+mod watchdog;
 
-pub use allocator::run_allocator_tests;
+pub use runner::{KTest, ShouldPanic, TEST_EXIT, exit, recover, run_tests};
+pub use watchdog::after_irq;
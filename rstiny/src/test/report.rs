@@ -0,0 +1,216 @@
+//! Machine-readable test reports
+//!
+//! The report is printed on the console between two marker lines:
+//!
+//! ```text
+//! #### ktest begin format=tap
+//! ...
+//! #### ktest end result=ok passed=12 failed=0 timed_out=0
+//! ```
+//!
+//! Timed-out tests count as neither passed nor failed. In TAP they are `not ok`
+//! lines with a `timeout` diagnostic; in JUnit they are `<error>` elements.
+//!
+//! TAP lines are printed as each test finishes, so a partial report survives
+//! a hang; JUnit XML is printed once all tests have run. Log records of the
+//! tests may appear between the markers in TAP mode, as non-TAP lines.
+
+use alloc::string::String;
+use core::fmt;
+
+use crate::cmdline::ParamValue;
+
+/// Prefix of the marker lines around a report.
+pub const MARKER: &str = "#### ktest";
+
+/// Report format, chosen with `test_format=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// Test Anything Protocol, version 13.
+    #[default]
+    Tap,
+    /// JUnit XML.
+    Junit,
+}
+
+impl ParamValue for ReportFormat {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        match value {
+            "tap" => Ok(Self::Tap),
+            "junit" => Ok(Self::Junit),
+            _ => Err("expected `tap` or `junit`"),
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tap => "tap",
+            Self::Junit => "junit",
+        })
+    }
+}
+
+/// How a test ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Passed,
+    /// Failed with the given panic message or explanation.
+    Failed(String),
+    /// Killed at its deadline, in milliseconds.
+    TimedOut(u64),
+}
+
+/// Outcome of one test.
+pub struct TestOutcome {
+    pub name: &'static str,
+    pub nanos: u64,
+    pub verdict: Verdict,
+}
+
+/// Prints the begin marker and, for TAP, the plan.
+pub fn begin(format: ReportFormat, count: usize) {
+    console_println!("{} begin format={}", MARKER, format);
+    if format == ReportFormat::Tap {
+        console_println!("TAP version 13");
+        console_println!("1..{}", count);
+    }
+}
+
+/// Reports the `index`-th (0-based) test as soon as it has run.
+pub fn test_done(format: ReportFormat, index: usize, outcome: &TestOutcome) {
+    if format != ReportFormat::Tap {
+        return;
+    }
+    let status = match outcome.verdict {
+        Verdict::Passed => "ok",
+        _ => "not ok",
+    };
+    console_println!(
+        "{} {} - {} # time={}.{:03}ms",
+        status,
+        index + 1,
+        outcome.name,
+        outcome.nanos / 1_000_000,
+        outcome.nanos / 1_000 % 1_000
+    );
+    match &outcome.verdict {
+        Verdict::Passed => {}
+        Verdict::Failed(message) => {
+            console_println!("  ---");
+            console_println!("  message: |");
+            for line in message.lines() {
+                console_println!("    {}", line);
+            }
+            console_println!("  ...");
+        }
+        Verdict::TimedOut(ms) => {
+            console_println!("  ---");
+            console_println!("  message: timed out after {} ms", ms);
+            console_println!("  timeout: {}", ms);
+            console_println!("  ...");
+        }
+    }
+}
+
+/// Prints the JUnit document if needed, then the end marker.
+pub fn end(format: ReportFormat, outcomes: &[TestOutcome], total_nanos: u64) {
+    let count = |f: fn(&Verdict) -> bool| outcomes.iter().filter(|o| f(&o.verdict)).count();
+    let passed = count(|v| *v == Verdict::Passed);
+    let failed = count(|v| matches!(v, Verdict::Failed(_)));
+    let timed_out = count(|v| matches!(v, Verdict::TimedOut(_)));
+    if format == ReportFormat::Junit {
+        print_junit(outcomes, failed, timed_out, total_nanos);
+    }
+    console_println!(
+        "{} end result={} passed={} failed={} timed_out={}",
+        MARKER,
+        if passed == outcomes.len() {
+            "ok"
+        } else {
+            "failed"
+        },
+        passed,
+        failed,
+        timed_out
+    );
+}
+
+fn print_junit(outcomes: &[TestOutcome], failed: usize, timed_out: usize, total_nanos: u64) {
+    console_println!(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    console_println!("<testsuites>");
+    console_println!(
+        r#"<testsuite name="{}" tests="{}" failures="{}" errors="{}" time="{}">"#,
+        env!("CARGO_PKG_NAME"),
+        outcomes.len(),
+        failed,
+        timed_out,
+        Seconds(total_nanos)
+    );
+    for outcome in outcomes {
+        // `allocator::basic_allocation` -> classname `allocator`, name `basic_allocation`
+        let (class, name) = outcome.name.rsplit_once("::").unwrap_or(("", outcome.name));
+        console_print!(
+            r#"  <testcase classname="{}" name="{}" time="{}""#,
+            Xml(class),
+            Xml(name),
+            Seconds(outcome.nanos)
+        );
+        match &outcome.verdict {
+            Verdict::Passed => console_println!("/>"),
+            Verdict::Failed(message) => {
+                let summary = message.lines().last().unwrap_or("");
+                console_println!(">");
+                console_println!(
+                    r#"    <failure message="{}">{}</failure>"#,
+                    Xml(summary),
+                    Xml(message)
+                );
+                console_println!("  </testcase>");
+            }
+            Verdict::TimedOut(ms) => {
+                console_println!(">");
+                console_println!(
+                    r#"    <error type="timeout" message="timed out after {} ms"/>"#,
+                    ms
+                );
+                console_println!("  </testcase>");
+            }
+        }
+    }
+    console_println!("</testsuite>");
+    console_println!("</testsuites>");
+}
+
+/// Formats nanoseconds as seconds with microsecond precision.
+struct Seconds(u64);
+
+impl fmt::Display for Seconds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:06}",
+            self.0 / 1_000_000_000,
+            self.0 / 1_000 % 1_000_000
+        )
+    }
+}
+
+/// Escapes text for XML attributes and content.
+struct Xml<'a>(&'a str);
+
+impl fmt::Display for Xml<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '&' => f.write_str("&amp;")?,
+                '"' => f.write_str("&quot;")?,
+                c => fmt::Write::write_char(f, c)?,
+            }
+        }
+        Ok(())
+    }
+}
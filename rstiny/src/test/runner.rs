@@ -0,0 +1,374 @@
+//! Kernel test runner
+//!
+//! Functions annotated with `#[ktest]` (see the `rstiny-macros` crate) are
+//! registered as [`KTest`] descriptors in the `.ktest` linker section.
+//! [`run_tests`] runs them in name order, optionally only those whose name
+//! contains one of the comma-separated patterns given with `test=` on the
+//! kernel command line, and reports the outcome and duration of each in the
+//! format chosen with `test_format=` (see [`report`]).
+//!
+//! A test fails by panicking. While a test runs, the panic handler calls
+//! [`recover`], which records the panic message and resumes the runner right
+//! after its call into the test, much like `longjmp`. The frames of the test
+//! are abandoned without running destructors, so its heap allocations leak and
+//! any lock it held stays locked.
+//!
+//! Each test also has a deadline, `timeout_ms` in its attribute or
+//! [`DEFAULT_TIMEOUT_MS`]. Past it, the timer interrupt resumes the runner the
+//! same way (see [`watchdog`]) and the test is reported as timed out, apart
+//! from the failures. A test marked `should_panic` passes only if it panics.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+
+use super::report::{self, ReportFormat, TestOutcome, Verdict};
+use super::watchdog;
+use crate::cmdline::ParamValue;
+use crate::utils::logging::deferred;
+use crate::utils::text::TruncatingWriter;
+use crate::utils::{arch, frame_allocator, heap_allocator, semihosting};
+
+/// Descriptor of a `#[ktest]` function, placed in the `.ktest` section.
+pub struct KTest {
+    /// Full path of the function, starting with the crate name.
+    pub name: &'static str,
+    pub func: fn(),
+    pub should_panic: ShouldPanic,
+    /// Deadline in milliseconds, or 0 for [`DEFAULT_TIMEOUT_MS`].
+    pub timeout_ms: u64,
+}
+
+/// Whether a test is expected to panic, from `#[ktest(should_panic)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShouldPanic {
+    No,
+    Yes,
+    /// The panic message must contain the given text.
+    WithMessage(&'static str),
+}
+
+impl KTest {
+    /// Returns the path of the test without the crate name.
+    pub fn short_name(&self) -> &'static str {
+        self.name
+            .strip_prefix(concat!(env!("CARGO_PKG_NAME"), "::"))
+            .unwrap_or(self.name)
+    }
+
+    fn timeout_ms(&self) -> u64 {
+        match self.timeout_ms {
+            0 => DEFAULT_TIMEOUT_MS,
+            ms => ms,
+        }
+    }
+}
+
+unsafe extern "C" {
+    static __ktest_start: KTest;
+    static __ktest_end: KTest;
+}
+
+/// Returns every registered test, in link order.
+fn registered_tests() -> &'static [KTest] {
+    let start = &raw const __ktest_start;
+    let end = &raw const __ktest_end;
+    let len = (end as usize - start as usize) / size_of::<KTest>();
+    unsafe { core::slice::from_raw_parts(start, len) }
+}
+
+/// Registers of the runner saved before entering a test: x19-x30, sp and DAIF.
+#[repr(C)]
+struct Context {
+    regs: [usize; 14],
+}
+
+/// Deadline of tests without `timeout_ms`.
+pub const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+/// Value of [`RUNNING_ON`] when no test is running.
+const NOT_RUNNING: usize = usize::MAX;
+/// Longest panic message kept for the report, in bytes.
+const MESSAGE_LEN: usize = 512;
+
+/// CPU running a test, or [`NOT_RUNNING`].
+static RUNNING_ON: AtomicUsize = AtomicUsize::new(NOT_RUNNING);
+static mut CONTEXT: Context = Context { regs: [0; 14] };
+/// Whether the runner was resumed by the watchdog rather than a panic.
+static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+static PANIC_MESSAGE: SpinNoIrq<Message> = SpinNoIrq::new(Message {
+    buf: [0; MESSAGE_LEN],
+    len: 0,
+});
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+/// Panic message of the failing test, truncated to [`MESSAGE_LEN`] bytes.
+struct Message {
+    buf: [u8; MESSAGE_LEN],
+    len: usize,
+}
+
+/// Saves the registers of the caller in `ctx` and calls `entry(arg)`.
+///
+/// Returns `true` when `entry` returns, or `false` when [`resume`] is called
+/// with `ctx` instead.
+#[unsafe(naked)]
+unsafe extern "C" fn call_guarded(
+    ctx: *mut Context,
+    entry: extern "C" fn(usize),
+    arg: usize,
+) -> bool {
+    core::arch::naked_asm!(
+        "stp x19, x20, [x0, #0]",
+        "stp x21, x22, [x0, #16]",
+        "stp x23, x24, [x0, #32]",
+        "stp x25, x26, [x0, #48]",
+        "stp x27, x28, [x0, #64]",
+        "stp x29, x30, [x0, #80]",
+        "mov x9, sp",
+        "mrs x10, daif",
+        "stp x9, x10, [x0, #96]",
+        "stp x29, x30, [sp, #-16]!",
+        "mov x29, sp",
+        "mov x9, x1",
+        "mov x0, x2",
+        "blr x9",
+        "ldp x29, x30, [sp], #16",
+        "mov x0, #1",
+        "ret",
+    )
+}
+
+/// Restores the registers saved by [`call_guarded`], which then returns
+/// `false` to its caller.
+#[unsafe(naked)]
+unsafe extern "C" fn resume(ctx: *const Context) -> ! {
+    core::arch::naked_asm!(
+        "ldp x19, x20, [x0, #0]",
+        "ldp x21, x22, [x0, #16]",
+        "ldp x23, x24, [x0, #32]",
+        "ldp x25, x26, [x0, #48]",
+        "ldp x27, x28, [x0, #64]",
+        "ldp x29, x30, [x0, #80]",
+        "ldp x9, x10, [x0, #96]",
+        "mov sp, x9",
+        "msr daif, x10",
+        "mov x0, #0",
+        "ret",
+    )
+}
+
+extern "C" fn enter_test(test: usize) {
+    let test = unsafe { &*(test as *const KTest) };
+    // 上下文已保存，此后 panic 或超时都可以回到运行器
+    RUNNING_ON.store(arch::cpu_id(), Ordering::Release);
+    arch::enable_irqs();
+    (test.func)();
+}
+
+/// Called by the panic handler. If a test is running on this CPU, records
+/// the panic and resumes the runner; otherwise returns.
+pub fn recover(info: &PanicInfo) {
+    if RUNNING_ON.load(Ordering::Acquire) != arch::cpu_id() {
+        return;
+    }
+    RUNNING_ON.store(NOT_RUNNING, Ordering::Release);
+    if let Some(mut message) = PANIC_MESSAGE.try_lock() {
+        let mut writer = TruncatingWriter::new(&mut message.buf);
+        let _ = write!(writer, "{info}");
+        message.len = writer.written();
+    }
+    unsafe { resume(&raw const CONTEXT) }
+}
+
+/// Called by the timer IRQ handler once the deadline of the running test has
+/// passed, after the interrupt is acknowledged. Resumes the runner if the test
+/// runs on this CPU; otherwise disarms the timer and returns.
+pub(super) fn time_out() {
+    if RUNNING_ON.load(Ordering::Acquire) != arch::cpu_id() {
+        watchdog::disarm();
+        return;
+    }
+    RUNNING_ON.store(NOT_RUNNING, Ordering::Release);
+    TIMED_OUT.store(true, Ordering::Release);
+    unsafe { resume(&raw const CONTEXT) }
+}
+
+/// Seed for randomized tests, chosen at the start of the run.
+pub fn seed() -> u64 {
+    SEED.load(Ordering::Relaxed)
+}
+
+/// Runs `test` under its deadline and judges the outcome.
+fn run_one(test: &'static KTest) -> Verdict {
+    PANIC_MESSAGE.lock().len = 0;
+    TIMED_OUT.store(false, Ordering::Release);
+    let irqs_disabled = arch::irqs_disabled();
+    watchdog::arm(test.timeout_ms());
+    let completed =
+        unsafe { call_guarded(&raw mut CONTEXT, enter_test, test as *const KTest as usize) };
+    RUNNING_ON.store(NOT_RUNNING, Ordering::Release);
+    // 测试可能在中断处理函数中 panic 后直接回到这里
+    deferred::reset_handlers();
+    if irqs_disabled {
+        arch::disable_irqs();
+    }
+    watchdog::disarm();
+
+    if completed {
+        return match test.should_panic {
+            ShouldPanic::No => Verdict::Passed,
+            _ => Verdict::Failed("test did not panic as expected".into()),
+        };
+    }
+    if TIMED_OUT.load(Ordering::Acquire) {
+        return Verdict::TimedOut(test.timeout_ms());
+    }
+    let message = PANIC_MESSAGE.lock();
+    let message = String::from_utf8_lossy(&message.buf[..message.len]).into_owned();
+    match test.should_panic {
+        ShouldPanic::No => Verdict::Failed(message),
+        ShouldPanic::Yes => Verdict::Passed,
+        ShouldPanic::WithMessage(expected) if message.contains(expected) => Verdict::Passed,
+        ShouldPanic::WithMessage(expected) => Verdict::Failed(format!(
+            "panic did not contain expected string\n  panic message: {message}\n  expected substring: {expected:?}"
+        )),
+    }
+}
+
+/// Returns whether `name` is selected by the comma-separated `filter`.
+fn selected(name: &str, filter: Option<&str>) -> bool {
+    match filter {
+        None => true,
+        Some(filter) => filter
+            .split(',')
+            .any(|pattern| !pattern.is_empty() && name.contains(pattern)),
+    }
+}
+
+/// How to leave the emulator after the test run, chosen with `test_exit=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitMethod {
+    /// Semihosting `SYS_EXIT`: QEMU (with `-semihosting`) exits with status 0
+    /// if all tests passed, 1 otherwise.
+    Semihosting,
+    /// PSCI `SYSTEM_OFF`: the exit status carries no verdict, which has to be
+    /// read from the end marker of the report.
+    Psci,
+}
+
+impl ParamValue for ExitMethod {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        match value {
+            "semihosting" => Ok(Self::Semihosting),
+            "psci" => Ok(Self::Psci),
+            _ => Err("expected `semihosting` or `psci`"),
+        }
+    }
+}
+
+kparam! {
+    /// Comma-separated patterns selecting the tests to run by name.
+    "test" => pub static TEST_FILTER: Option<&'static str> = None;
+}
+
+kparam! {
+    /// Seed of the randomized tests, decimal or `0x` hexadecimal; random if
+    /// not given.
+    "test_seed" => pub static TEST_SEED: Option<u64> = None;
+}
+
+kparam! {
+    /// Format of the test report.
+    "test_format" => pub static TEST_FORMAT: ReportFormat = ReportFormat::Tap;
+}
+
+kparam! {
+    /// How to stop the machine after the tests; it keeps booting if not given.
+    "test_exit" => pub static TEST_EXIT: Option<ExitMethod> = None;
+}
+
+/// Stops the machine after a test run with the given verdict.
+pub fn exit(method: ExitMethod, passed: bool) -> ! {
+    match method {
+        ExitMethod::Semihosting => semihosting::exit(if passed { 0 } else { 1 }),
+        ExitMethod::Psci => axplat::power::system_off(),
+    }
+}
+
+/// Runs the registered tests selected by `test=`, with the seed given by
+/// `test_seed=` for the randomized ones, and prints a report in the format
+/// given by `test_format=`.
+///
+/// Returns whether all tests passed.
+pub fn run_tests() -> bool {
+    let filter = TEST_FILTER.get();
+    let format = TEST_FORMAT.get();
+    let seed = TEST_SEED.get().unwrap_or_else(arch::counter_ticks);
+    SEED.store(seed, Ordering::Relaxed);
+    watchdog::init();
+
+    let mut tests: Vec<&'static KTest> = registered_tests()
+        .iter()
+        .filter(|test| selected(test.short_name(), filter))
+        .collect();
+    tests.sort_unstable_by_key(|test| test.name);
+    let filtered_out = registered_tests().len() - tests.len();
+    info!(
+        "running {} tests ({} filtered out), seed {:#x}",
+        tests.len(),
+        filtered_out,
+        seed
+    );
+
+    report::begin(format, tests.len());
+    let mut outcomes = Vec::with_capacity(tests.len());
+    let run_start = arch::counter_ticks();
+    for (index, &test) in tests.iter().enumerate() {
+        let name = test.short_name();
+        #[cfg(feature = "alloc-trace")]
+        let snapshot = heap_allocator::trace::snapshot();
+        let start = arch::counter_ticks();
+        let verdict = run_one(test);
+        let outcome = TestOutcome {
+            name,
+            nanos: arch::ticks_to_nanos(arch::counter_ticks() - start),
+            verdict,
+        };
+        #[cfg(feature = "alloc-trace")]
+        if outcome.verdict == Verdict::Passed && !snapshot.diff().is_clean() {
+            warn!("test {} left heap allocations behind", name);
+            snapshot.print_diff();
+        }
+        report::test_done(format, index, &outcome);
+        outcomes.push(outcome);
+    }
+    report::end(
+        format,
+        &outcomes,
+        arch::ticks_to_nanos(arch::counter_ticks() - run_start),
+    );
+
+    let passed = outcomes
+        .iter()
+        .all(|outcome| outcome.verdict == Verdict::Passed);
+    if !passed {
+        for outcome in &outcomes {
+            match outcome.verdict {
+                Verdict::Passed => {}
+                Verdict::Failed(_) => error!("test {} failed", outcome.name),
+                Verdict::TimedOut(ms) => error!("test {} timed out after {} ms", outcome.name, ms),
+            }
+        }
+        warn!("reproduce with test_seed={:#x}", seed);
+    }
+    heap_allocator::print_stats();
+    frame_allocator::print_stats();
+    #[cfg(feature = "coverage")]
+    crate::utils::coverage::dump();
+    passed
+}
@@ -0,0 +1,70 @@
+//! Per-test deadlines
+//!
+//! While a test runs, the generic timer is armed for its deadline and IRQs
+//! are unmasked. When the timer interrupt fires past the deadline, the IRQ
+//! entry (see `crate::trap`) acknowledges it and then calls [`after_irq`],
+//! which resumes the runner like a panic would (see [`super::runner`]). A test spinning with IRQs masked, e.g. on a
+//! `SpinNoIrq` lock, cannot be interrupted and hangs the run.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axplat::time::{monotonic_time_nanos, set_oneshot_timer};
+
+use super::runner;
+
+/// Value of [`DEADLINE`] when no test is running.
+const DISARMED: u64 = u64::MAX;
+/// How far the timer is pushed when disarmed, in nanoseconds.
+const IDLE_NANOS: u64 = 3_600 * 1_000_000_000;
+
+/// Deadline of the running test in monotonic nanoseconds, or [`DISARMED`].
+static DEADLINE: AtomicU64 = AtomicU64::new(DISARMED);
+
+/// Registers the timer interrupt handler.
+pub fn init() {
+    let irq = axplat::time::irq_num();
+    if !axplat::irq::register(irq, on_timer) {
+        warn!(
+            "timer IRQ {} already registered, test deadlines may not work",
+            irq
+        );
+    }
+    axplat::irq::set_enable(irq, true);
+    disarm();
+}
+
+/// Arms the timer to fire `timeout_ms` from now.
+pub fn arm(timeout_ms: u64) {
+    let deadline = monotonic_time_nanos() + timeout_ms * 1_000_000;
+    DEADLINE.store(deadline, Ordering::Release);
+    set_oneshot_timer(deadline);
+}
+
+/// Pushes the timer far into the future, so it stops firing.
+pub fn disarm() {
+    DEADLINE.store(DISARMED, Ordering::Release);
+    set_oneshot_timer(monotonic_time_nanos() + IDLE_NANOS);
+}
+
+fn expired() -> bool {
+    let deadline = DEADLINE.load(Ordering::Acquire);
+    deadline != DISARMED && monotonic_time_nanos() >= deadline
+}
+
+fn on_timer() {
+    // 未到期时重新设定定时器；否则推迟它，避免电平触发的中断一直到来
+    let deadline = DEADLINE.load(Ordering::Acquire);
+    if deadline != DISARMED && monotonic_time_nanos() < deadline {
+        set_oneshot_timer(deadline);
+    } else {
+        set_oneshot_timer(monotonic_time_nanos() + IDLE_NANOS);
+    }
+}
+
+/// Called by the IRQ entry once the interrupt is handled.
+pub fn after_irq() {
+    // 中断已应答（EOI），此时可以放弃异常栈帧，直接回到测试运行器
+    if expired() {
+        runner::time_out();
+    }
+}
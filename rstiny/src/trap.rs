@@ -0,0 +1,23 @@
+//! Interrupt entry (`irq` feature).
+//!
+//! axcpu saves the exception frame and calls [`handle_irq`], which dispatches
+//! the IRQ to the handler registered with `axplat::irq::register`. The
+//! dispatch is marked as a handler context for the logger, so records logged
+//! by IRQ handlers are deferred instead of spinning on the console lock.
+//!
+//! Synchronous exceptions need no marking: axcpu panics on every one of them,
+//! and the panic handler prints without the console lock.
+
+use axcpu::trap::{IRQ, register_trap_handler};
+
+use crate::utils::logging::deferred;
+
+#[register_trap_handler(IRQ)]
+fn handle_irq(vector: usize) -> bool {
+    deferred::enter_handler();
+    axplat::irq::handle(vector);
+    deferred::exit_handler();
+    #[cfg(feature = "ktest")]
+    crate::test::after_irq();
+    true
+}
@@ -0,0 +1,107 @@
+//! AArch64 system register helpers.
+
+use core::arch::asm;
+
+use crate::config::{CORES_PER_CLUSTER, MAX_CPUS};
+
+/// Returns the current value of the physical counter (`CNTPCT_EL0`).
+#[inline]
+pub fn counter_ticks() -> u64 {
+    let ticks: u64;
+    unsafe { asm!("isb; mrs {}, cntpct_el0", out(reg) ticks, options(nomem, nostack)) };
+    ticks
+}
+
+/// Returns the frequency of the generic timer in Hz (`CNTFRQ_EL0`).
+#[inline]
+pub fn counter_freq() -> u64 {
+    let freq: u64;
+    unsafe { asm!("mrs {}, cntfrq_el0", out(reg) freq, options(nomem, nostack)) };
+    freq
+}
+
+/// Converts generic timer ticks to nanoseconds.
+pub fn ticks_to_nanos(ticks: u64) -> u64 {
+    match counter_freq() {
+        0 => 0,
+        freq => (ticks as u128 * 1_000_000_000 / freq as u128) as u64,
+    }
+}
+
+/// Returns the time since the counter was reset, in nanoseconds.
+pub fn monotonic_nanos() -> u64 {
+    ticks_to_nanos(counter_ticks())
+}
+
+/// Returns the logical id of the current CPU, derived from `MPIDR_EL1`.
+#[inline]
+pub fn cpu_id() -> usize {
+    let mpidr: u64;
+    unsafe { asm!("mrs {}, mpidr_el1", out(reg) mpidr, options(nomem, nostack)) };
+    let aff0 = (mpidr & 0xff) as usize;
+    let aff1 = ((mpidr >> 8) & 0xff) as usize;
+    aff1 * CORES_PER_CLUSTER + aff0
+}
+
+/// Returns the index of the current CPU in the per-CPU arrays, which have
+/// [`MAX_CPUS`] entries.
+///
+/// [`cpu_id`] is derived from the affinity fields and may exceed the arrays on
+/// a machine with more CPUs than configured; such CPUs share the last entry,
+/// so per-CPU state must stay safe to use from several CPUs.
+#[inline]
+pub fn cpu_index() -> usize {
+    cpu_id().min(MAX_CPUS - 1)
+}
+
+/// Returns the smallest data cache line size in bytes (`CTR_EL0.DminLine`).
+fn dcache_line_size() -> usize {
+    let ctr: u64;
+    unsafe { asm!("mrs {}, ctr_el0", out(reg) ctr, options(nomem, nostack)) };
+    4 << ((ctr >> 16) & 0xf)
+}
+
+/// Cleans the data cache for `[start, start + size)` to the point of
+/// coherency, so the data reaches DRAM even if the CPU is reset afterwards.
+pub fn clean_dcache_range(start: usize, size: usize) {
+    let line = dcache_line_size();
+    let mut addr = start & !(line - 1);
+    while addr < start + size {
+        unsafe { asm!("dc cvac, {}", in(reg) addr, options(nostack)) };
+        addr += line;
+    }
+    unsafe { asm!("dsb sy", options(nostack)) };
+}
+
+/// Returns whether IRQs are masked on the current CPU (`DAIF.I`).
+///
+/// Exception and interrupt handlers run with IRQs masked.
+#[inline]
+pub fn irqs_disabled() -> bool {
+    let daif: u64;
+    unsafe { asm!("mrs {}, daif", out(reg) daif, options(nomem, nostack)) };
+    daif & (1 << 7) != 0
+}
+
+/// Unmasks IRQs on the current CPU.
+#[inline]
+pub fn enable_irqs() {
+    // 不能标记 nomem：开关中断也是编译器屏障，临界区内的访存不能被移出
+    unsafe { asm!("msr daifclr, #2", options(nostack)) };
+}
+
+/// Masks IRQs on the current CPU.
+#[inline]
+pub fn disable_irqs() {
+    // 不能标记 nomem：开关中断也是编译器屏障，临界区内的访存不能被移出
+    unsafe { asm!("msr daifset, #2", options(nostack)) };
+}
+
+/// Returns the link register, i.e. the return address of the current function
+/// when called before any other call in its body.
+#[inline(always)]
+pub fn return_address() -> usize {
+    let lr: usize;
+    unsafe { asm!("mov {}, x30", out(reg) lr, options(nomem, nostack, preserves_flags)) };
+    lr
+}
@@ -0,0 +1,119 @@
+//! Source-based code coverage over the console.
+//!
+//! With the `coverage` feature and `make COVERAGE=y`, rstiny (not its
+//! dependencies) is built with `-Cinstrument-coverage`. There is no LLVM
+//! profiler runtime in `no_std`: the instrumented code only increments the
+//! counters in the `__llvm_prf_cnts` section (and sets MC/DC bits in
+//! `__llvm_prf_bits`), and [`dump`] sends these two sections over the console
+//! at the end of the test run. The other profile sections are constant, so
+//! `tools/kcov` reads them from the ELF and rebuilds a `.profraw` file.
+//!
+//! # Dump format
+//!
+//! ```text
+//! #### kcov begin counters=<bytes> bitmap=<bytes>
+//! c <offset> <hex bytes>
+//! b <offset> <hex bytes>
+//! #### kcov end lines=<n> crc32=<crc>
+//! ```
+//!
+//! `c` and `b` lines hold up to [`LINE_BYTES`] bytes of the counter and
+//! bitmap sections at a hexadecimal offset; chunks that are all zero are left
+//! out. The CRC-32 covers both sections in full, zeros included, as sent.
+
+use core::ptr;
+
+/// Prefix of the marker lines around a dump.
+pub const MARKER: &str = "#### kcov";
+/// Bytes of section data per line.
+const LINE_BYTES: usize = 32;
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+unsafe extern "C" {
+    static __start___llvm_prf_cnts: u8;
+    static __stop___llvm_prf_cnts: u8;
+    static __start___llvm_prf_bits: u8;
+    static __stop___llvm_prf_bits: u8;
+}
+
+/// Referenced by instrumented code on targets without an LLVM profiler
+/// runtime; defining it is enough.
+#[allow(non_upper_case_globals)]
+#[unsafe(no_mangle)]
+static __llvm_profile_runtime: i32 = 0;
+
+fn section(start: *const u8, stop: *const u8) -> *const [u8] {
+    ptr::slice_from_raw_parts(start, stop as usize - start as usize)
+}
+
+/// Sends the counters and MC/DC bitmaps over the console.
+///
+/// Code running meanwhile, including this function, keeps updating the
+/// counters; each chunk is copied before it is sent and checksummed, so the
+/// dump stays consistent.
+pub fn dump() {
+    let counters = section(
+        &raw const __start___llvm_prf_cnts,
+        &raw const __stop___llvm_prf_cnts,
+    );
+    let bitmap = section(
+        &raw const __start___llvm_prf_bits,
+        &raw const __stop___llvm_prf_bits,
+    );
+
+    console_println!(
+        "{} begin counters={} bitmap={}",
+        MARKER,
+        counters.len(),
+        bitmap.len()
+    );
+    let mut crc = Crc32::new();
+    let mut lines = 0;
+    for (tag, data) in [('c', counters), ('b', bitmap)] {
+        let start = data as *const u8;
+        for offset in (0..data.len()).step_by(LINE_BYTES) {
+            let len = LINE_BYTES.min(data.len() - offset);
+            let mut chunk = [0u8; LINE_BYTES];
+            // 计数器可能被并发更新，按字节做 volatile 拷贝
+            for (i, byte) in chunk[..len].iter_mut().enumerate() {
+                *byte = unsafe { start.add(offset + i).read_volatile() };
+            }
+            let chunk = &chunk[..len];
+            crc.update(chunk);
+            if chunk.iter().all(|&b| b == 0) {
+                continue;
+            }
+            let mut hex = [0u8; LINE_BYTES * 2];
+            for (i, byte) in chunk.iter().enumerate() {
+                hex[2 * i] = HEX_DIGITS[(byte >> 4) as usize];
+                hex[2 * i + 1] = HEX_DIGITS[(byte & 0xf) as usize];
+            }
+            let hex = core::str::from_utf8(&hex[..len * 2]).unwrap();
+            console_println!("{} {:x} {}", tag, offset, hex);
+            lines += 1;
+        }
+    }
+    console_println!("{} end lines={} crc32={:08x}", MARKER, lines, crc.finish());
+}
+
+/// CRC-32 (IEEE 802.3), bitwise.
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Self {
+        Self(!0)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.0 ^= byte as u32;
+            for _ in 0..8 {
+                self.0 = (self.0 >> 1) ^ (0xedb8_8320 & (self.0 & 1).wrapping_neg());
+            }
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.0
+    }
+}
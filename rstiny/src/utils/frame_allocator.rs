@@ -0,0 +1,415 @@
+//! Physical page frame allocator.
+//!
+//! A buddy allocator over the free RAM left to it by the heap, for memory
+//! that must be page-aligned or physically contiguous: page tables, DMA
+//! buffers. Each free RAM region is a zone with its own free lists and
+//! accounting. The free lists are linked through the free pages themselves
+//! (via the linear mapping); the only metadata is one byte per page, stored
+//! at the start of the zone.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use kspin::SpinNoIrq;
+
+use crate::utils::mem::{MemRegionList, phys_to_virt, virt_to_phys};
+
+pub const PAGE_SIZE: usize = 0x1000;
+/// Largest block order: blocks of up to `2^MAX_ORDER` pages (8 MiB).
+pub const MAX_ORDER: usize = 11;
+const ORDERS: usize = MAX_ORDER + 1;
+/// Maximum number of zones.
+const MAX_ZONES: usize = 16;
+
+/// Metadata flag of the first page of a free block; the low bits hold its order.
+const FREE: u8 = 0x80;
+/// End of a free list.
+const NIL: usize = usize::MAX;
+
+/// Link stored in the first page of each free block.
+#[derive(Clone, Copy)]
+struct FreeBlock {
+    next: usize,
+    prev: usize,
+}
+
+/// A run of physically contiguous page frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRange {
+    pub paddr: usize,
+    pub count: usize,
+}
+
+impl FrameRange {
+    /// Returns the frames containing `[vaddr, vaddr + size)` in the linear mapping.
+    pub fn from_virt(vaddr: usize, size: usize) -> Self {
+        Self {
+            paddr: virt_to_phys(vaddr),
+            count: size.div_ceil(PAGE_SIZE),
+        }
+    }
+
+    pub const fn size(&self) -> usize {
+        self.count * PAGE_SIZE
+    }
+
+    /// Returns the address of the frames in the linear mapping.
+    pub const fn vaddr(&self) -> usize {
+        phys_to_virt(self.paddr)
+    }
+
+    pub const fn as_mut_ptr(&self) -> *mut u8 {
+        self.vaddr() as *mut u8
+    }
+}
+
+/// Accounting of one zone.
+#[derive(Debug, Clone)]
+pub struct ZoneStats {
+    /// Physical range managed by the zone, metadata excluded.
+    pub start: usize,
+    pub end: usize,
+    pub total_pages: usize,
+    pub free_pages: usize,
+    /// Lowest value reached by `free_pages`.
+    pub min_free_pages: usize,
+    pub allocs: u64,
+    pub frees: u64,
+    /// Allocations the zone could not satisfy.
+    pub failures: u64,
+    /// Number of free blocks of each order.
+    pub free_blocks: [usize; ORDERS],
+}
+
+impl fmt::Display for ZoneStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:#x}, {:#x}): {}/{} pages free (min {}), {} allocs, {} frees, {} failures, blocks",
+            self.start,
+            self.end,
+            self.free_pages,
+            self.total_pages,
+            self.min_free_pages,
+            self.allocs,
+            self.frees,
+            self.failures
+        )?;
+        for count in self.free_blocks {
+            write!(f, " {count}")?;
+        }
+        Ok(())
+    }
+}
+
+struct Zone {
+    start_pfn: usize,
+    end_pfn: usize,
+    /// Virtual address of the metadata bytes, one per page.
+    meta: usize,
+    free_lists: [usize; ORDERS],
+    free_pages: usize,
+    min_free_pages: usize,
+    allocs: u64,
+    frees: u64,
+    failures: u64,
+}
+
+impl Zone {
+    const EMPTY: Self = Self {
+        start_pfn: 0,
+        end_pfn: 0,
+        meta: 0,
+        free_lists: [NIL; ORDERS],
+        free_pages: 0,
+        min_free_pages: 0,
+        allocs: 0,
+        frees: 0,
+        failures: 0,
+    };
+
+    /// Creates a zone over `[start, end)`, using its first pages for the
+    /// metadata. Returns `None` if nothing is left.
+    fn new(start: usize, end: usize) -> Option<Self> {
+        let start_pfn = start.div_ceil(PAGE_SIZE);
+        let end_pfn = end / PAGE_SIZE;
+        let pages = end_pfn.checked_sub(start_pfn)?;
+        let meta_pages = pages.div_ceil(PAGE_SIZE);
+        if pages <= meta_pages {
+            return None;
+        }
+        let meta = phys_to_virt(start_pfn * PAGE_SIZE);
+        let mut zone = Self {
+            start_pfn: start_pfn + meta_pages,
+            end_pfn,
+            meta,
+            ..Self::EMPTY
+        };
+        unsafe { core::ptr::write_bytes(meta as *mut u8, 0, zone.pages()) };
+        zone.free_range(zone.start_pfn, zone.end_pfn);
+        zone.free_pages = zone.pages();
+        zone.min_free_pages = zone.free_pages;
+        Some(zone)
+    }
+
+    fn pages(&self) -> usize {
+        self.end_pfn - self.start_pfn
+    }
+
+    fn contains(&self, pfn: usize, count: usize) -> bool {
+        pfn >= self.start_pfn && pfn + count <= self.end_pfn
+    }
+
+    fn meta(&mut self, pfn: usize) -> &mut u8 {
+        unsafe { &mut *(self.meta as *mut u8).add(pfn - self.start_pfn) }
+    }
+
+    fn node(pfn: usize) -> &'static mut FreeBlock {
+        unsafe { &mut *(phys_to_virt(pfn * PAGE_SIZE) as *mut FreeBlock) }
+    }
+
+    fn push(&mut self, pfn: usize, order: usize) {
+        let head = self.free_lists[order];
+        *Self::node(pfn) = FreeBlock {
+            next: head,
+            prev: NIL,
+        };
+        if head != NIL {
+            Self::node(head).prev = pfn;
+        }
+        self.free_lists[order] = pfn;
+        *self.meta(pfn) = FREE | order as u8;
+    }
+
+    fn unlink(&mut self, pfn: usize, order: usize) {
+        let FreeBlock { next, prev } = *Self::node(pfn);
+        if prev == NIL {
+            self.free_lists[order] = next;
+        } else {
+            Self::node(prev).next = next;
+        }
+        if next != NIL {
+            Self::node(next).prev = prev;
+        }
+        *self.meta(pfn) = 0;
+    }
+
+    /// Frees a block, merging it with its buddies.
+    fn free_block(&mut self, mut pfn: usize, mut order: usize) {
+        while order < MAX_ORDER {
+            let buddy = pfn ^ (1 << order);
+            if !self.contains(buddy, 1 << order) || *self.meta(buddy) != FREE | order as u8 {
+                break;
+            }
+            self.unlink(buddy, order);
+            pfn = pfn.min(buddy);
+            order += 1;
+        }
+        self.push(pfn, order);
+    }
+
+    /// Frees `[pfn, end)` as the largest naturally aligned blocks.
+    fn free_range(&mut self, mut pfn: usize, end: usize) {
+        while pfn < end {
+            let mut order = (pfn.trailing_zeros() as usize).min(MAX_ORDER);
+            while pfn + (1 << order) > end {
+                order -= 1;
+            }
+            self.free_block(pfn, order);
+            pfn += 1 << order;
+        }
+    }
+
+    fn alloc(&mut self, count: usize, align_order: usize) -> Option<usize> {
+        let order = (count.next_power_of_two().trailing_zeros() as usize).max(align_order);
+        let Some(mut found) = (order..ORDERS).find(|&k| self.free_lists[k] != NIL) else {
+            self.failures += 1;
+            return None;
+        };
+        let pfn = self.free_lists[found];
+        self.unlink(pfn, found);
+        while found > order {
+            found -= 1;
+            self.push(pfn + (1 << found), found);
+        }
+        // 归还超出 count 的尾部页
+        self.free_range(pfn + count, pfn + (1 << order));
+
+        self.free_pages -= count;
+        self.min_free_pages = self.min_free_pages.min(self.free_pages);
+        self.allocs += 1;
+        Some(pfn)
+    }
+
+    /// Returns whether `pfn` lies in a free block: only the first page of a
+    /// block is marked, so the aligned candidates of every order are checked.
+    fn is_free(&mut self, pfn: usize) -> bool {
+        (0..ORDERS).any(|order| {
+            let head = pfn & !((1 << order) - 1);
+            self.contains(head, 1 << order) && *self.meta(head) == FREE | order as u8
+        })
+    }
+
+    /// Frees `count` frames at `pfn`. If one of them is already free, leaves
+    /// the zone untouched and returns that frame.
+    fn dealloc(&mut self, pfn: usize, count: usize) -> Result<(), usize> {
+        if let Some(page) = (pfn..pfn + count).find(|&page| self.is_free(page)) {
+            return Err(page);
+        }
+        self.free_range(pfn, pfn + count);
+        self.free_pages += count;
+        self.frees += 1;
+        Ok(())
+    }
+
+    fn stats(&self) -> ZoneStats {
+        let mut free_blocks = [0; ORDERS];
+        for (order, count) in free_blocks.iter_mut().enumerate() {
+            let mut pfn = self.free_lists[order];
+            while pfn != NIL {
+                *count += 1;
+                pfn = Self::node(pfn).next;
+            }
+        }
+        ZoneStats {
+            start: self.start_pfn * PAGE_SIZE,
+            end: self.end_pfn * PAGE_SIZE,
+            total_pages: self.pages(),
+            free_pages: self.free_pages,
+            min_free_pages: self.min_free_pages,
+            allocs: self.allocs,
+            frees: self.frees,
+            failures: self.failures,
+            free_blocks,
+        }
+    }
+}
+
+struct FrameAllocator {
+    zones: [Zone; MAX_ZONES],
+    len: usize,
+}
+
+impl FrameAllocator {
+    fn zones(&mut self) -> &mut [Zone] {
+        &mut self.zones[..self.len]
+    }
+}
+
+static FRAMES: SpinNoIrq<FrameAllocator> = SpinNoIrq::new(FrameAllocator {
+    zones: [Zone::EMPTY; MAX_ZONES],
+    len: 0,
+});
+
+/// Hands the free RAM `regions` to the frame allocator, one zone per region.
+pub fn init<const N: usize>(regions: &MemRegionList<N>) {
+    let mut frames = FRAMES.lock();
+    for region in regions.iter() {
+        if frames.len == MAX_ZONES {
+            warn!(
+                "frames: too many RAM regions, ignoring [{:#x}, {:#x})",
+                region.start, region.end
+            );
+            continue;
+        }
+        if let Some(zone) = Zone::new(region.start, region.end) {
+            let index = frames.len;
+            frames.zones[index] = zone;
+            frames.len += 1;
+        }
+    }
+    let zones = frames.len;
+    let pages: usize = frames.zones().iter().map(Zone::pages).sum();
+    drop(frames);
+
+    info!(
+        "frames: {} MiB in {zones} zones",
+        pages * PAGE_SIZE / (1024 * 1024)
+    );
+}
+
+/// Allocates `count` contiguous frames whose physical address is aligned to
+/// `align` bytes (a power of two; at least a page).
+pub fn alloc_frames(count: usize, align: usize) -> Option<FrameRange> {
+    assert!(
+        align.is_power_of_two(),
+        "frame alignment {align:#x} is not a power of two"
+    );
+    if count == 0 {
+        return None;
+    }
+    let align_order = (align / PAGE_SIZE).max(1).trailing_zeros() as usize;
+    if align_order > MAX_ORDER || count > 1 << MAX_ORDER {
+        return None;
+    }
+    let mut frames = FRAMES.lock();
+    let pfn = frames
+        .zones()
+        .iter_mut()
+        .find_map(|zone| zone.alloc(count, align_order))?;
+    Some(FrameRange {
+        paddr: pfn * PAGE_SIZE,
+        count,
+    })
+}
+
+/// Like [`alloc_frames`], with the frames filled with zeros.
+pub fn alloc_zeroed_frames(count: usize, align: usize) -> Option<FrameRange> {
+    let range = alloc_frames(count, align)?;
+    unsafe { core::ptr::write_bytes(range.as_mut_ptr(), 0, range.size()) };
+    Some(range)
+}
+
+/// Allocates one page-aligned frame.
+pub fn alloc_frame() -> Option<FrameRange> {
+    alloc_frames(1, PAGE_SIZE)
+}
+
+/// Returns frames to the allocator. `range` need not be a whole allocation,
+/// so the tail of a buffer can be freed early.
+pub fn dealloc_frames(range: FrameRange) {
+    let pfn = range.paddr / PAGE_SIZE;
+    let mut frames = FRAMES.lock();
+    let result = frames
+        .zones()
+        .iter_mut()
+        .find(|zone| zone.contains(pfn, range.count))
+        .map(|zone| zone.dealloc(pfn, range.count));
+    // 先释放锁再 panic，否则之后的分配都会死锁
+    drop(frames);
+    match result {
+        Some(Ok(())) => {}
+        Some(Err(page)) => panic!(
+            "frame_allocator: freeing {} frames at {:#x}, but frame {:#x} is already free",
+            range.count,
+            range.paddr,
+            page * PAGE_SIZE
+        ),
+        None => panic!(
+            "frame_allocator: {} frames at {:#x} are not managed by any zone",
+            range.count, range.paddr
+        ),
+    }
+}
+
+/// Returns the accounting of every zone.
+pub fn zone_stats() -> Vec<ZoneStats> {
+    FRAMES.lock().zones().iter().map(Zone::stats).collect()
+}
+
+/// Returns the number of free frames over all zones.
+pub fn free_frames() -> usize {
+    FRAMES
+        .lock()
+        .zones()
+        .iter()
+        .map(|zone| zone.free_pages)
+        .sum()
+}
+
+/// Prints the accounting of every zone to the console.
+pub fn print_stats() {
+    for (index, stats) in zone_stats().iter().enumerate() {
+        console_println!("frames: zone {index} {stats}");
+    }
+}
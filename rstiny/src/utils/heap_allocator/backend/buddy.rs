@@ -0,0 +1,123 @@
+//! Buddy heap backend.
+//!
+//! Blocks are naturally aligned powers of two of at least [`MIN_BLOCK`]
+//! bytes, kept in one singly linked free list per order. Freeing a block
+//! merges it with its buddy while the buddy is free.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use super::HeapBackend;
+
+const MIN_BLOCK: usize = 16;
+const ORDERS: usize = usize::BITS as usize;
+
+struct Buddy {
+    /// Address of the first free block of each order, 0 if none.
+    free: [usize; ORDERS],
+}
+
+fn block_order(layout: Layout) -> usize {
+    let size = layout.size().max(layout.align()).max(MIN_BLOCK);
+    size.next_power_of_two().trailing_zeros() as usize
+}
+
+impl Buddy {
+    fn push(&mut self, block: usize, order: usize) {
+        unsafe { *(block as *mut usize) = self.free[order] };
+        self.free[order] = block;
+    }
+
+    fn pop(&mut self, order: usize) -> Option<usize> {
+        let block = self.free[order];
+        if block == 0 {
+            return None;
+        }
+        self.free[order] = unsafe { *(block as *const usize) };
+        Some(block)
+    }
+
+    /// Removes `block` from the free list of `order`, if it is there.
+    fn remove(&mut self, block: usize, order: usize) -> bool {
+        let mut link = &mut self.free[order] as *mut usize;
+        unsafe {
+            while *link != 0 {
+                if *link == block {
+                    *link = *(block as *const usize);
+                    return true;
+                }
+                link = *link as *mut usize;
+            }
+        }
+        false
+    }
+
+    fn free_block(&mut self, mut block: usize, mut order: usize) {
+        while order < ORDERS - 1 && self.remove(block ^ (1 << order), order) {
+            block &= !(1 << order);
+            order += 1;
+        }
+        self.push(block, order);
+    }
+}
+
+pub struct BuddyBackend(spin::Mutex<Buddy>);
+
+impl BuddyBackend {
+    pub const fn new() -> Self {
+        Self(spin::Mutex::new(Buddy { free: [0; ORDERS] }))
+    }
+}
+
+impl HeapBackend for BuddyBackend {
+    const NAME: &'static str = "buddy";
+
+    unsafe fn add_memory(&self, start: *mut u8, size: usize) {
+        let mut buddy = self.0.lock();
+        let mut block = (start as usize).next_multiple_of(MIN_BLOCK);
+        let end = (start as usize + size) & !(MIN_BLOCK - 1);
+        while block < end {
+            let fits = usize::BITS - 1 - (end - block).leading_zeros();
+            let order = block.trailing_zeros().min(fits) as usize;
+            buddy.free_block(block, order);
+            block += 1 << order;
+        }
+    }
+
+    unsafe fn alloc(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let order = block_order(layout);
+        let mut buddy = self.0.lock();
+        let mut found = (order..ORDERS).find(|&k| buddy.free[k] != 0)?;
+        let block = buddy.pop(found)?;
+        while found > order {
+            found -= 1;
+            buddy.push(block + (1 << found), found);
+        }
+        NonNull::new(block as *mut u8)
+    }
+
+    unsafe fn dealloc(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0
+            .lock()
+            .free_block(ptr.as_ptr() as usize, block_order(layout));
+    }
+
+    unsafe fn resize_in_place(&self, _ptr: NonNull<u8>, layout: Layout, new_size: usize) -> bool {
+        let new_layout = Layout::from_size_align(new_size, layout.align()).unwrap();
+        block_order(new_layout) == block_order(layout)
+    }
+
+    fn largest_free_block(&self, _limit: usize, wait: bool) -> Option<usize> {
+        let buddy = if wait {
+            self.0.lock()
+        } else {
+            self.0.try_lock()?
+        };
+        Some(
+            (0..ORDERS)
+                .rev()
+                .find(|&order| buddy.free[order] != 0)
+                .map_or(0, |order| 1 << order),
+        )
+    }
+}
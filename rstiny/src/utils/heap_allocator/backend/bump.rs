@@ -0,0 +1,85 @@
+//! Bump heap backend, for tests.
+//!
+//! Allocations are carved from the current arena in order. Freeing the last
+//! block moves the pointer back; the arena is only reset once every block is
+//! freed. Adding memory switches to the new arena, abandoning what is left
+//! of the previous one.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use super::HeapBackend;
+
+struct Bump {
+    start: usize,
+    next: usize,
+    end: usize,
+    live: usize,
+}
+
+pub struct BumpBackend(spin::Mutex<Bump>);
+
+impl BumpBackend {
+    pub const fn new() -> Self {
+        Self(spin::Mutex::new(Bump {
+            start: 0,
+            next: 0,
+            end: 0,
+            live: 0,
+        }))
+    }
+}
+
+impl HeapBackend for BumpBackend {
+    const NAME: &'static str = "bump";
+    const COALESCES: bool = false;
+
+    unsafe fn add_memory(&self, start: *mut u8, size: usize) {
+        let mut bump = self.0.lock();
+        bump.start = start as usize;
+        bump.next = start as usize;
+        bump.end = start as usize + size;
+    }
+
+    unsafe fn alloc(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let mut bump = self.0.lock();
+        let start = bump.next.checked_next_multiple_of(layout.align())?;
+        let end = start.checked_add(layout.size())?;
+        if end > bump.end {
+            return None;
+        }
+        bump.next = end;
+        bump.live += 1;
+        NonNull::new(start as *mut u8)
+    }
+
+    unsafe fn dealloc(&self, ptr: NonNull<u8>, layout: Layout) {
+        let mut bump = self.0.lock();
+        bump.live -= 1;
+        if bump.live == 0 {
+            bump.next = bump.start;
+        } else if ptr.as_ptr() as usize + layout.size() == bump.next {
+            bump.next = ptr.as_ptr() as usize;
+        }
+    }
+
+    unsafe fn resize_in_place(&self, ptr: NonNull<u8>, layout: Layout, new_size: usize) -> bool {
+        let mut bump = self.0.lock();
+        let start = ptr.as_ptr() as usize;
+        if start + layout.size() == bump.next && start + new_size <= bump.end {
+            bump.next = start + new_size;
+            true
+        } else {
+            new_size <= layout.size()
+        }
+    }
+
+    fn largest_free_block(&self, _limit: usize, wait: bool) -> Option<usize> {
+        let bump = if wait {
+            self.0.lock()
+        } else {
+            self.0.try_lock()?
+        };
+        Some(bump.end - bump.next)
+    }
+}
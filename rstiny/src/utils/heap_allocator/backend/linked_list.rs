@@ -0,0 +1,160 @@
+//! Linked-list heap backend.
+//!
+//! Free memory is an address-ordered list of holes, each starting with a
+//! [`Hole`] header. Allocation takes the first hole that fits; freeing
+//! merges the block with the adjacent holes. Every size and address is a
+//! multiple of [`UNIT`], so a hole is always large enough for its header.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use super::HeapBackend;
+
+const UNIT: usize = 16;
+
+#[derive(Clone, Copy)]
+struct Hole {
+    size: usize,
+    /// Address of the next hole, 0 at the end of the list.
+    next: usize,
+}
+
+fn hole<'a>(addr: usize) -> &'a mut Hole {
+    unsafe { &mut *(addr as *mut Hole) }
+}
+
+fn block_size(layout: Layout) -> usize {
+    layout.size().next_multiple_of(UNIT)
+}
+
+struct FreeList {
+    head: usize,
+}
+
+impl FreeList {
+    fn set_next(&mut self, prev: Option<usize>, next: usize) {
+        match prev {
+            Some(prev) => hole(prev).next = next,
+            None => self.head = next,
+        }
+    }
+
+    fn alloc(&mut self, size: usize, align: usize) -> Option<usize> {
+        let mut prev = None;
+        let mut addr = self.head;
+        while addr != 0 {
+            let Hole {
+                size: hole_size,
+                next,
+            } = *hole(addr);
+            let start = addr.next_multiple_of(align);
+            let end = start + size;
+            if end <= addr + hole_size {
+                let mut after = next;
+                if end < addr + hole_size {
+                    *hole(end) = Hole {
+                        size: addr + hole_size - end,
+                        next,
+                    };
+                    after = end;
+                }
+                if start > addr {
+                    *hole(addr) = Hole {
+                        size: start - addr,
+                        next: after,
+                    };
+                } else {
+                    self.set_next(prev, after);
+                }
+                return Some(start);
+            }
+            prev = Some(addr);
+            addr = next;
+        }
+        None
+    }
+
+    fn free(&mut self, addr: usize, mut size: usize) {
+        let mut prev = None;
+        let mut next = self.head;
+        while next != 0 && next < addr {
+            prev = Some(next);
+            next = hole(next).next;
+        }
+        if next != 0 && addr + size == next {
+            size += hole(next).size;
+            next = hole(next).next;
+        }
+        match prev {
+            Some(prev) if prev + hole(prev).size == addr => {
+                *hole(prev) = Hole {
+                    size: hole(prev).size + size,
+                    next,
+                };
+            }
+            _ => {
+                *hole(addr) = Hole { size, next };
+                self.set_next(prev, addr);
+            }
+        }
+    }
+}
+
+pub struct LinkedListBackend(spin::Mutex<FreeList>);
+
+impl LinkedListBackend {
+    pub const fn new() -> Self {
+        Self(spin::Mutex::new(FreeList { head: 0 }))
+    }
+}
+
+impl HeapBackend for LinkedListBackend {
+    const NAME: &'static str = "linked-list";
+
+    unsafe fn add_memory(&self, start: *mut u8, size: usize) {
+        let begin = (start as usize).next_multiple_of(UNIT);
+        let end = (start as usize + size) & !(UNIT - 1);
+        if end > begin {
+            self.0.lock().free(begin, end - begin);
+        }
+    }
+
+    unsafe fn alloc(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let addr = self
+            .0
+            .lock()
+            .alloc(block_size(layout), layout.align().max(UNIT))?;
+        NonNull::new(addr as *mut u8)
+    }
+
+    unsafe fn dealloc(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0
+            .lock()
+            .free(ptr.as_ptr() as usize, block_size(layout));
+    }
+
+    unsafe fn resize_in_place(&self, ptr: NonNull<u8>, layout: Layout, new_size: usize) -> bool {
+        let old = block_size(layout);
+        let new = new_size.next_multiple_of(UNIT);
+        if new < old {
+            // 归还尾部
+            self.0.lock().free(ptr.as_ptr() as usize + new, old - new);
+        }
+        new <= old
+    }
+
+    fn largest_free_block(&self, _limit: usize, wait: bool) -> Option<usize> {
+        let list = if wait {
+            self.0.lock()
+        } else {
+            self.0.try_lock()?
+        };
+        let mut largest = 0;
+        let mut addr = list.head;
+        while addr != 0 {
+            largest = largest.max(hole(addr).size);
+            addr = hole(addr).next;
+        }
+        Some(largest)
+    }
+}
@@ -0,0 +1,129 @@
+//! Heap backends, selected by cargo feature.
+//!
+//! - `talc` (default): the talc allocator.
+//! - `heap-buddy`: power-of-two buddy allocator.
+//! - `heap-linked-list`: address-ordered first-fit free list.
+//! - `heap-bump`: bump allocator, only reclaiming memory when everything is
+//!   freed; for tests.
+//!
+//! A backend only manages the memory it is given with
+//! [`HeapBackend::add_memory`]; growing the heap is done above it.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+#[cfg(feature = "heap-buddy")]
+mod buddy;
+#[cfg(feature = "heap-bump")]
+mod bump;
+#[cfg(feature = "heap-linked-list")]
+mod linked_list;
+#[cfg(not(any(
+    feature = "heap-buddy",
+    feature = "heap-linked-list",
+    feature = "heap-bump"
+)))]
+mod talc;
+
+#[cfg(any(
+    all(feature = "heap-buddy", feature = "heap-linked-list"),
+    all(feature = "heap-buddy", feature = "heap-bump"),
+    all(feature = "heap-linked-list", feature = "heap-bump"),
+    all(
+        feature = "heap-talc",
+        any(
+            feature = "heap-buddy",
+            feature = "heap-linked-list",
+            feature = "heap-bump"
+        )
+    ),
+))]
+compile_error!("only one `heap-*` backend feature can be enabled");
+
+#[cfg(feature = "heap-buddy")]
+pub type Backend = buddy::BuddyBackend;
+#[cfg(feature = "heap-bump")]
+pub type Backend = bump::BumpBackend;
+#[cfg(feature = "heap-linked-list")]
+pub type Backend = linked_list::LinkedListBackend;
+#[cfg(not(any(
+    feature = "heap-buddy",
+    feature = "heap-linked-list",
+    feature = "heap-bump"
+)))]
+pub type Backend = talc::TalcBackend;
+
+/// Interface between the heap and the allocator managing its memory.
+///
+/// Every method locks the backend itself; none of them grows the heap.
+pub trait HeapBackend: Sync {
+    /// Name shown in logs and test reports.
+    const NAME: &'static str;
+    /// Whether freed blocks are merged with their free neighbours, so that
+    /// freeing everything restores the largest free block.
+    const COALESCES: bool = true;
+
+    /// Hands `[start, start + size)` to the backend.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be valid, unused by anything else, and never freed.
+    unsafe fn add_memory(&self, start: *mut u8, size: usize);
+
+    /// Allocates a block, or returns `None` if no free block fits.
+    ///
+    /// # Safety
+    ///
+    /// `layout` must have a non-zero size.
+    unsafe fn alloc(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// Frees a block.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been allocated by this backend with `layout`.
+    unsafe fn dealloc(&self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Resizes a block without moving it. Returns `false` if that is not
+    /// possible, in which case the block is unchanged.
+    ///
+    /// # Safety
+    ///
+    /// As for [`dealloc`](Self::dealloc); `new_size` must not be zero.
+    unsafe fn resize_in_place(&self, ptr: NonNull<u8>, layout: Layout, new_size: usize) -> bool {
+        let _ = (ptr, layout);
+        new_size == layout.size()
+    }
+
+    /// Allocates up to `blocks.len()` blocks of `layout`, storing their
+    /// addresses in `blocks`. Returns how many were allocated.
+    ///
+    /// # Safety
+    ///
+    /// As for [`alloc`](Self::alloc).
+    unsafe fn alloc_batch(&self, layout: Layout, blocks: &mut [usize]) -> usize {
+        for (count, block) in blocks.iter_mut().enumerate() {
+            match unsafe { self.alloc(layout) } {
+                Some(ptr) => *block = ptr.as_ptr() as usize,
+                None => return count,
+            }
+        }
+        blocks.len()
+    }
+
+    /// Frees blocks of `layout`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`dealloc`](Self::dealloc), for every block.
+    unsafe fn dealloc_batch(&self, layout: Layout, blocks: &[usize]) {
+        for &block in blocks {
+            unsafe { self.dealloc(NonNull::new_unchecked(block as *mut u8), layout) };
+        }
+    }
+
+    /// Returns the largest block that can be allocated now, searching no
+    /// further than `limit` bytes. With `wait` unset, returns `None` instead
+    /// of waiting for the backend lock.
+    fn largest_free_block(&self, limit: usize, wait: bool) -> Option<usize>;
+}
@@ -0,0 +1,85 @@
+//! talc backend.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use talc::{ErrOnOom, Span, Talc, Talck};
+
+use super::HeapBackend;
+
+pub struct TalcBackend(Talck<spin::Mutex<()>, ErrOnOom>);
+
+impl TalcBackend {
+    pub const fn new() -> Self {
+        Self(Talc::new(ErrOnOom).lock())
+    }
+}
+
+impl HeapBackend for TalcBackend {
+    const NAME: &'static str = "talc";
+
+    unsafe fn add_memory(&self, start: *mut u8, size: usize) {
+        if unsafe { self.0.lock().claim(Span::from_base_size(start, size)) }.is_err() {
+            warn!("heap: talc cannot use {size} bytes at {start:p}");
+        }
+    }
+
+    unsafe fn alloc(&self, layout: Layout) -> Option<NonNull<u8>> {
+        unsafe { self.0.lock().malloc(layout) }.ok()
+    }
+
+    unsafe fn dealloc(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { self.0.lock().free(ptr, layout) }
+    }
+
+    unsafe fn resize_in_place(&self, ptr: NonNull<u8>, layout: Layout, new_size: usize) -> bool {
+        let mut talc = self.0.lock();
+        if new_size <= layout.size() {
+            unsafe { talc.shrink(ptr, layout, new_size) };
+            true
+        } else {
+            unsafe { talc.grow_in_place(ptr, layout, new_size) }.is_ok()
+        }
+    }
+
+    unsafe fn alloc_batch(&self, layout: Layout, blocks: &mut [usize]) -> usize {
+        let mut talc = self.0.lock();
+        for (count, block) in blocks.iter_mut().enumerate() {
+            match unsafe { talc.malloc(layout) } {
+                Ok(ptr) => *block = ptr.as_ptr() as usize,
+                Err(()) => return count,
+            }
+        }
+        blocks.len()
+    }
+
+    unsafe fn dealloc_batch(&self, layout: Layout, blocks: &[usize]) {
+        let mut talc = self.0.lock();
+        for &block in blocks {
+            unsafe { talc.free(NonNull::new_unchecked(block as *mut u8), layout) };
+        }
+    }
+
+    /// talc does not expose its free lists: binary search over allocation
+    /// sizes instead.
+    fn largest_free_block(&self, limit: usize, wait: bool) -> Option<usize> {
+        let mut talc = if wait {
+            self.0.lock()
+        } else {
+            self.0.try_lock()?
+        };
+        let (mut lo, mut hi) = (0, limit);
+        while lo < hi {
+            let size = lo + (hi - lo).div_ceil(2);
+            let layout = Layout::from_size_align(size, 1).unwrap();
+            match unsafe { talc.malloc(layout) } {
+                Ok(ptr) => {
+                    unsafe { talc.free(ptr, layout) };
+                    lo = size;
+                }
+                Err(()) => hi = size - 1,
+            }
+        }
+        Some(lo)
+    }
+}
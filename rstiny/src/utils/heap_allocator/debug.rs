@@ -0,0 +1,190 @@
+//! Debug allocator, enabled by the `debug-alloc` feature.
+//!
+//! Each block is laid out as
+//!
+//! ```text
+//! | padding | Header | red zone | user data | red zone |
+//!                               ^ returned pointer
+//! ```
+//!
+//! Fresh memory is filled with [`ALLOC_POISON`], freed memory with
+//! [`FREE_POISON`] and the red zones with [`GUARD_BYTE`]. On free, the header
+//! is checked against the layout given by the caller and both red zones are
+//! validated. Freed blocks go through a small quarantine before returning to
+//! the backend, so double frees are caught by their header and writes after
+//! free by the poison pattern when they leave the quarantine.
+//!
+//! Any violation panics with the layout and the caller addresses involved.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
+use core::ptr;
+
+use super::RawHeap;
+
+/// Pattern of freshly allocated memory.
+pub const ALLOC_POISON: u8 = 0xa5;
+/// Pattern of freed memory.
+pub const FREE_POISON: u8 = 0xdd;
+/// Pattern of the red zones around each block.
+pub const GUARD_BYTE: u8 = 0xfd;
+
+/// Size of each red zone, in bytes.
+const RED_ZONE: usize = 16;
+/// Minimum alignment of the user data, so that the header is aligned too.
+const MIN_ALIGN: usize = 16;
+/// Number of freed blocks held back before returning them to the backend.
+const QUARANTINE_LEN: usize = 64;
+
+const MAGIC_LIVE: usize = 0x4c49_5645_4845_4150; // "LIVEHEAP"
+const MAGIC_FREED: usize = 0x4652_4545_4845_4150; // "FREEHEAP"
+
+#[repr(C, align(16))]
+struct Header {
+    magic: usize,
+    size: usize,
+    align: usize,
+    /// Distance from the start of the backend block to the user data.
+    front: usize,
+    alloc_caller: usize,
+    free_caller: usize,
+}
+
+const HEADER_SIZE: usize = size_of::<Header>();
+
+struct Quarantine {
+    blocks: [usize; QUARANTINE_LEN],
+    next: usize,
+}
+
+static QUARANTINE: spin::Mutex<Quarantine> = spin::Mutex::new(Quarantine {
+    blocks: [0; QUARANTINE_LEN],
+    next: 0,
+});
+
+/// Returns the layout of the backend block backing a `layout` allocation, and
+/// the offset of the user data in it, or `None` if the padded layout is too
+/// large.
+fn outer_layout(size: usize, align: usize) -> Option<(Layout, usize)> {
+    let align = align.max(MIN_ALIGN);
+    let front = (HEADER_SIZE + RED_ZONE).next_multiple_of(align);
+    let outer_size = front.checked_add(size)?.checked_add(RED_ZONE)?;
+    let layout = Layout::from_size_align(outer_size, align).ok()?;
+    Some((layout, front))
+}
+
+unsafe fn header<'a>(user: *mut u8) -> &'a mut Header {
+    unsafe { &mut *user.sub(RED_ZONE + HEADER_SIZE).cast::<Header>() }
+}
+
+/// Returns the offset of the first byte of `ptr[..len]` not equal to `byte`.
+unsafe fn find_mismatch(ptr: *const u8, len: usize, byte: u8) -> Option<usize> {
+    (0..len).find(|&i| unsafe { *ptr.add(i) } != byte)
+}
+
+pub(super) unsafe fn alloc(heap: &RawHeap, layout: Layout, caller: usize) -> *mut u8 {
+    // 加上头部和红区后放不下的请求按分配失败处理
+    let Some((outer, front)) = outer_layout(layout.size(), layout.align()) else {
+        return ptr::null_mut();
+    };
+    let base = unsafe { heap.alloc(outer) };
+    if base.is_null() {
+        return base;
+    }
+    unsafe {
+        let user = base.add(front);
+        ptr::write_bytes(user.sub(RED_ZONE), GUARD_BYTE, RED_ZONE);
+        ptr::write_bytes(user, ALLOC_POISON, layout.size());
+        ptr::write_bytes(user.add(layout.size()), GUARD_BYTE, RED_ZONE);
+        *header(user) = Header {
+            magic: MAGIC_LIVE,
+            size: layout.size(),
+            align: layout.align(),
+            front,
+            alloc_caller: caller,
+            free_caller: 0,
+        };
+        user
+    }
+}
+
+pub(super) unsafe fn dealloc(heap: &RawHeap, user: *mut u8, layout: Layout, caller: usize) {
+    let hdr = unsafe { header(user) };
+    match hdr.magic {
+        MAGIC_LIVE => {}
+        MAGIC_FREED => panic!(
+            "debug-alloc: double free of {user:p} ({layout:?}) by {caller:#x}, \
+             allocated by {:#x}, first freed by {:#x}",
+            hdr.alloc_caller, hdr.free_caller
+        ),
+        magic => panic!(
+            "debug-alloc: free of {user:p} ({layout:?}) by {caller:#x}: \
+             not a heap block or header overwritten (magic {magic:#x})"
+        ),
+    }
+    if hdr.size != layout.size() || hdr.align != layout.align() {
+        panic!(
+            "debug-alloc: free of {user:p} by {caller:#x} with {layout:?}, \
+             but it was allocated by {:#x} with size {}, align {}",
+            hdr.alloc_caller, hdr.size, hdr.align
+        );
+    }
+    let size = hdr.size;
+    if let Some(offset) = unsafe { find_mismatch(user.sub(RED_ZONE), RED_ZONE, GUARD_BYTE) } {
+        panic!(
+            "debug-alloc: buffer underflow in {user:p} ({layout:?}) at offset -{}, \
+             allocated by {:#x}, freed by {caller:#x}",
+            RED_ZONE - offset,
+            hdr.alloc_caller
+        );
+    }
+    if let Some(offset) = unsafe { find_mismatch(user.add(size), RED_ZONE, GUARD_BYTE) } {
+        panic!(
+            "debug-alloc: buffer overflow in {user:p} ({layout:?}) at offset {}, \
+             allocated by {:#x}, freed by {caller:#x}",
+            size + offset,
+            hdr.alloc_caller
+        );
+    }
+
+    hdr.magic = MAGIC_FREED;
+    hdr.free_caller = caller;
+    unsafe { ptr::write_bytes(user, FREE_POISON, size) };
+
+    let evicted = {
+        let mut quarantine = QUARANTINE.lock();
+        let slot = quarantine.next;
+        quarantine.next = (slot + 1) % QUARANTINE_LEN;
+        core::mem::replace(&mut quarantine.blocks[slot], user as usize)
+    };
+    if evicted != 0 {
+        unsafe { release(heap, evicted as *mut u8) };
+    }
+}
+
+/// Returns a quarantined block to the backend, after checking it was not
+/// written to since it was freed.
+unsafe fn release(heap: &RawHeap, user: *mut u8) {
+    let hdr = unsafe { header(user) };
+    if hdr.magic != MAGIC_FREED {
+        panic!(
+            "debug-alloc: header of freed block {user:p} overwritten (magic {:#x})",
+            hdr.magic
+        );
+    }
+    if let Some(offset) = unsafe { find_mismatch(user, hdr.size, FREE_POISON) } {
+        panic!(
+            "debug-alloc: use after free in {user:p} at offset {offset} (size {}, align {}), \
+             allocated by {:#x}, freed by {:#x}",
+            hdr.size, hdr.align, hdr.alloc_caller, hdr.free_caller
+        );
+    }
+    // 分配时已检查过同样的布局
+    let (outer, front) = outer_layout(hdr.size, hdr.align).unwrap_or_else(|| {
+        panic!(
+            "debug-alloc: header of freed block {user:p} overwritten (size {}, align {})",
+            hdr.size, hdr.align
+        )
+    });
+    unsafe { heap.dealloc(user.sub(front), outer) };
+}
@@ -0,0 +1,379 @@
+//! Global heap allocator.
+//!
+//! The heap starts from a small static bootstrap arena, so allocation works
+//! from the first instruction. Once the free RAM is known, [`init`] registers
+//! it with the heap, which hands the regions to the backend one at a time
+//! whenever the backend runs out of memory. The backend is chosen by cargo
+//! feature, see [`backend`].
+//!
+//! Every allocation goes through [`Heap`], which keeps the counters reported
+//! by [`stats`]. With the `debug-alloc` feature, blocks are also guarded and
+//! poisoned, see [`debug`]. With the `alloc-trace` feature, live allocations
+//! are recorded with their caller, see [`trace`].
+//!
+//! Small allocations are served from per-CPU caches, see [`slab`], except
+//! with `debug-alloc` where every block must be checked.
+
+mod backend;
+#[cfg(feature = "debug-alloc")]
+mod debug;
+#[cfg(not(feature = "debug-alloc"))]
+mod slab;
+mod stats;
+#[cfg(feature = "alloc-trace")]
+pub mod trace;
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::NonNull;
+
+use backend::{Backend, HeapBackend};
+
+use crate::cmdline::Size;
+use crate::config::{HEAP_BOOTSTRAP_SIZE, HEAP_MAX_SIZE};
+use crate::utils::arch;
+use crate::utils::mem::{MAX_RAM_REGIONS, MemRegionList, phys_to_virt};
+
+pub use stats::{HeapStats, SIZE_CLASSES, size_class, size_class_limit};
+
+kparam! {
+    /// Most free RAM given to the heap, e.g. `heap=64M`; the rest goes to the
+    /// frame allocator.
+    "heap" => pub static HEAP_SIZE: Size = Size(HEAP_MAX_SIZE);
+}
+
+/// Name of the heap backend selected at build time.
+pub const BACKEND_NAME: &str = Backend::NAME;
+/// Whether the heap backend merges adjacent free blocks.
+pub const BACKEND_COALESCES: bool = Backend::COALESCES;
+
+/// Room for the backend's metadata and alignment when choosing a region to
+/// claim.
+const CLAIM_OVERHEAD: usize = 256;
+
+static mut ARENA: [u8; HEAP_BOOTSTRAP_SIZE] = [0; HEAP_BOOTSTRAP_SIZE];
+
+/// Memory not handed to the backend yet.
+struct Growth {
+    bootstrap_claimed: bool,
+    /// Physical RAM regions registered by [`init`].
+    pending: MemRegionList<MAX_RAM_REGIONS>,
+    claimed: usize,
+}
+
+impl Growth {
+    /// Removes and returns the first pending region able to satisfy `layout`,
+    /// as a virtual range.
+    fn take_region(&mut self, layout: Layout) -> Option<(*mut u8, usize)> {
+        let needed = layout.size() + layout.align() + CLAIM_OVERHEAD;
+        let region = *self.pending.iter().find(|region| region.size() >= needed)?;
+        self.pending.remove(region);
+        Some((phys_to_virt(region.start) as *mut u8, region.size()))
+    }
+
+    fn pending_bytes(&self) -> usize {
+        let bootstrap = if self.bootstrap_claimed {
+            0
+        } else {
+            HEAP_BOOTSTRAP_SIZE
+        };
+        self.pending.total_size() + bootstrap
+    }
+}
+
+/// The backend plus the memory it can grow into.
+pub(super) struct RawHeap {
+    backend: Backend,
+    growth: spin::Mutex<Growth>,
+}
+
+impl RawHeap {
+    /// Gives the backend more memory: the bootstrap arena first, then the
+    /// first registered RAM region able to satisfy `layout`. Returns `false`
+    /// if there is none.
+    fn grow(&self, layout: Layout) -> bool {
+        let mut growth = self.growth.lock();
+        let (start, size) = if !growth.bootstrap_claimed {
+            growth.bootstrap_claimed = true;
+            // 堆在 init() 之前就可能被使用，先从静态的引导区分配
+            (
+                core::ptr::addr_of_mut!(ARENA).cast::<u8>(),
+                HEAP_BOOTSTRAP_SIZE,
+            )
+        } else {
+            match growth.take_region(layout) {
+                Some(region) => region,
+                None => return false,
+            }
+        };
+        unsafe { self.backend.add_memory(start, size) };
+        growth.claimed += size;
+        true
+    }
+
+    /// Allocates `blocks.len()` blocks of `layout` at once, growing the heap
+    /// if needed. Returns how many were allocated.
+    pub(super) unsafe fn alloc_batch(&self, layout: Layout, blocks: &mut [usize]) -> usize {
+        let mut count = 0;
+        loop {
+            count += unsafe { self.backend.alloc_batch(layout, &mut blocks[count..]) };
+            if count == blocks.len() || !self.grow(layout) {
+                return count;
+            }
+        }
+    }
+
+    pub(super) unsafe fn dealloc_batch(&self, layout: Layout, blocks: &[usize]) {
+        unsafe { self.backend.dealloc_batch(layout, blocks) }
+    }
+}
+
+unsafe impl GlobalAlloc for RawHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        loop {
+            if let Some(ptr) = unsafe { self.backend.alloc(layout) } {
+                return ptr.as_ptr();
+            }
+            if !self.grow(layout) {
+                return core::ptr::null_mut();
+            }
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.backend.dealloc(NonNull::new_unchecked(ptr), layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if unsafe {
+            self.backend
+                .resize_in_place(NonNull::new_unchecked(ptr), layout, new_size)
+        } {
+            return ptr;
+        }
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        let new_ptr = unsafe { self.alloc(new_layout) };
+        if !new_ptr.is_null() {
+            unsafe {
+                core::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+                self.dealloc(ptr, layout);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// The global allocator: the backend plus the statistics counters.
+pub struct Heap {
+    raw: RawHeap,
+    counters: stats::Counters,
+}
+
+impl Heap {
+    #[cfg(not(feature = "debug-alloc"))]
+    #[inline]
+    unsafe fn raw_alloc(&self, layout: Layout, _caller: usize) -> *mut u8 {
+        unsafe { slab::alloc(&self.raw, layout) }
+    }
+
+    #[cfg(not(feature = "debug-alloc"))]
+    #[inline]
+    unsafe fn raw_dealloc(&self, ptr: *mut u8, layout: Layout, _caller: usize) {
+        unsafe { slab::dealloc(&self.raw, ptr, layout) }
+    }
+
+    #[cfg(not(feature = "debug-alloc"))]
+    #[inline]
+    unsafe fn raw_realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+        _caller: usize,
+    ) -> *mut u8 {
+        unsafe { slab::realloc(&self.raw, ptr, layout, new_size) }
+    }
+
+    #[cfg(feature = "debug-alloc")]
+    unsafe fn raw_alloc(&self, layout: Layout, caller: usize) -> *mut u8 {
+        unsafe { debug::alloc(&self.raw, layout, caller) }
+    }
+
+    #[cfg(feature = "debug-alloc")]
+    unsafe fn raw_dealloc(&self, ptr: *mut u8, layout: Layout, caller: usize) {
+        unsafe { debug::dealloc(&self.raw, ptr, layout, caller) }
+    }
+
+    /// Always moves the block, so that stale pointers hit the quarantine.
+    #[cfg(feature = "debug-alloc")]
+    unsafe fn raw_realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+        caller: usize,
+    ) -> *mut u8 {
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        let new_ptr = unsafe { debug::alloc(&self.raw, new_layout, caller) };
+        if !new_ptr.is_null() {
+            unsafe {
+                core::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+                debug::dealloc(&self.raw, ptr, layout, caller);
+            }
+        }
+        new_ptr
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let caller = arch::return_address();
+        let ptr = unsafe { self.raw_alloc(layout, caller) };
+        if ptr.is_null() {
+            self.counters.on_failure();
+        } else {
+            self.counters.on_alloc(layout.size());
+            #[cfg(feature = "alloc-trace")]
+            trace::on_alloc(ptr, layout, caller);
+        }
+        ptr
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let caller = arch::return_address();
+        #[cfg(feature = "alloc-trace")]
+        trace::on_free(ptr);
+        unsafe { self.raw_dealloc(ptr, layout, caller) };
+        self.counters.on_free(layout.size());
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let caller = arch::return_address();
+        // 先取出旧块的记录：后端释放旧块后，其他 CPU 可能立即分配到同一地址并记录它
+        #[cfg(feature = "alloc-trace")]
+        let traced = trace::on_free(ptr);
+        let new_ptr = unsafe { self.raw_realloc(ptr, layout, new_size, caller) };
+        if new_ptr.is_null() {
+            self.counters.on_failure();
+            // 旧块仍然有效
+            #[cfg(feature = "alloc-trace")]
+            if let Some(entry) = traced {
+                trace::restore(entry);
+            }
+        } else {
+            self.counters.on_realloc(layout.size(), new_size);
+            #[cfg(feature = "alloc-trace")]
+            {
+                let new_layout =
+                    unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+                trace::on_alloc(new_ptr, new_layout, caller);
+            }
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Heap = Heap {
+    raw: RawHeap {
+        backend: Backend::new(),
+        growth: spin::Mutex::new(Growth {
+            bootstrap_claimed: false,
+            pending: MemRegionList::new(),
+            claimed: 0,
+        }),
+    },
+    counters: stats::Counters::new(),
+};
+
+/// Registers free RAM with the heap. Regions are claimed on demand.
+pub fn init<const N: usize>(regions: &MemRegionList<N>) {
+    let mut growth = ALLOCATOR.raw.growth.lock();
+    for region in regions.iter() {
+        if !growth.pending.add(*region) {
+            warn!(
+                "heap: too many RAM regions, ignoring [{:#x}, {:#x})",
+                region.start, region.end
+            );
+        }
+    }
+    let pending = growth.pending.total_size();
+    drop(growth);
+
+    info!(
+        "heap: {} backend, {} KiB bootstrap arena, {} MiB in {} RAM regions",
+        BACKEND_NAME,
+        HEAP_BOOTSTRAP_SIZE / 1024,
+        pending / (1024 * 1024),
+        regions.len()
+    );
+    for region in regions.iter() {
+        debug!("heap: region [{:#x}, {:#x})", region.start, region.end);
+    }
+}
+
+/// Fills in the parts of the statistics kept by the heap and the backend,
+/// unless `wait` is unset and their locks are held. The per-CPU caches are
+/// only counted if `wait` is set.
+fn collect_stats(wait: bool) -> HeapStats {
+    let mut stats = ALLOCATOR.counters.snapshot();
+    let growth = if wait {
+        Some(ALLOCATOR.raw.growth.lock())
+    } else {
+        ALLOCATOR.raw.growth.try_lock()
+    };
+    let Some(growth) = growth else {
+        return stats;
+    };
+    stats.claimed_bytes = growth.claimed;
+    stats.pending_bytes = growth.pending_bytes();
+    drop(growth);
+    // 缓存中的块不属于后端的空闲内存，必须在估计最大空闲块之前扣除
+    #[cfg(not(feature = "debug-alloc"))]
+    if wait {
+        stats.cached_bytes = slab::cached_bytes();
+    }
+    stats.largest_free_block = ALLOCATOR
+        .raw
+        .backend
+        .largest_free_block(stats.free_bytes(), wait);
+    stats
+}
+
+/// Returns the current heap statistics.
+pub fn stats() -> HeapStats {
+    collect_stats(true)
+}
+
+/// Like [`stats`], but never blocks: if the heap locks are held (e.g. when
+/// panicking inside the allocator), only the counters are filled in.
+pub fn try_stats() -> HeapStats {
+    collect_stats(false)
+}
+
+/// Enables or disables the per-CPU caches of small blocks. Disabling them
+/// returns the cached blocks to the backend.
+#[cfg(not(feature = "debug-alloc"))]
+pub fn set_cpu_caches_enabled(enabled: bool) {
+    slab::set_enabled(&ALLOCATOR.raw, enabled);
+}
+
+/// Returns the blocks held in the per-CPU caches to the backend.
+#[cfg(not(feature = "debug-alloc"))]
+pub fn flush_cpu_caches() {
+    slab::flush(&ALLOCATOR.raw);
+}
+
+/// Prints the heap statistics to the console.
+pub fn print_stats() {
+    console_print!("{}", stats());
+}
+
+#[alloc_error_handler]
+pub fn handle_alloc_error(layout: Layout) -> ! {
+    #[cfg(feature = "alloc-trace")]
+    trace::dump_live_allocations();
+    panic!("Heap allocation error, layout = {:?}", layout);
+}
@@ -0,0 +1,271 @@
+//! Per-CPU magazine caches for small allocations.
+//!
+//! Blocks of up to [`MAX_CACHED_SIZE`] bytes are rounded up to a power-of-two
+//! size class. Each CPU keeps two magazines (arrays of free blocks) per class
+//! and serves allocations and frees from them without touching the backend lock.
+//! When both magazines are empty (or full), whole magazines are exchanged
+//! with a per-class depot; only when the depot cannot help does the cache go
+//! to the backend, and then it allocates or frees half a magazine under one lock.
+//!
+//! Blocks of a class always come from the backend with the class layout, whether
+//! caching is enabled or not, so [`set_enabled`] can be toggled at any time.
+
+use core::alloc::Layout;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kspin::SpinNoIrq;
+
+use super::RawHeap;
+use crate::config::MAX_CPUS;
+use crate::utils::arch;
+
+/// Smallest size class.
+const MIN_CLASS_SIZE: usize = 16;
+/// Largest size served by the caches.
+pub const MAX_CACHED_SIZE: usize = 2048;
+/// Number of size classes: 16, 32, ..., 2048 bytes.
+const CLASSES: usize = 8;
+/// Alignment of all cached blocks; more aligned layouts bypass the caches.
+const CLASS_ALIGN: usize = 16;
+/// Blocks per magazine.
+const MAGAZINE_LEN: usize = 16;
+/// Full magazines kept per class in the depot.
+const DEPOT_LEN: usize = 8;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[derive(Clone, Copy)]
+struct Magazine {
+    blocks: [usize; MAGAZINE_LEN],
+    len: usize,
+}
+
+impl Magazine {
+    const EMPTY: Self = Self {
+        blocks: [0; MAGAZINE_LEN],
+        len: 0,
+    };
+
+    fn is_full(&self) -> bool {
+        self.len == MAGAZINE_LEN
+    }
+
+    fn pop(&mut self) -> Option<*mut u8> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.blocks[self.len] as *mut u8)
+    }
+
+    fn push(&mut self, block: *mut u8) {
+        self.blocks[self.len] = block as usize;
+        self.len += 1;
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ClassCache {
+    loaded: Magazine,
+    previous: Magazine,
+}
+
+/// Caches of one CPU, on their own cache lines.
+#[repr(align(64))]
+struct CpuCache {
+    classes: [ClassCache; CLASSES],
+}
+
+struct Depot {
+    full: [Magazine; DEPOT_LEN],
+    len: usize,
+}
+
+static CPU_CACHES: [SpinNoIrq<CpuCache>; MAX_CPUS] = [const {
+    SpinNoIrq::new(CpuCache {
+        classes: [ClassCache {
+            loaded: Magazine::EMPTY,
+            previous: Magazine::EMPTY,
+        }; CLASSES],
+    })
+}; MAX_CPUS];
+
+static DEPOTS: [SpinNoIrq<Depot>; CLASSES] = [const {
+    SpinNoIrq::new(Depot {
+        full: [Magazine::EMPTY; DEPOT_LEN],
+        len: 0,
+    })
+}; CLASSES];
+
+/// Returns the size class serving `layout`, if any.
+fn class_of(layout: Layout) -> Option<usize> {
+    if layout.size() > MAX_CACHED_SIZE || layout.align() > CLASS_ALIGN {
+        return None;
+    }
+    let size = layout.size().max(MIN_CLASS_SIZE).next_power_of_two();
+    Some((size.trailing_zeros() - MIN_CLASS_SIZE.trailing_zeros()) as usize)
+}
+
+fn class_layout(class: usize) -> Layout {
+    Layout::from_size_align(MIN_CLASS_SIZE << class, CLASS_ALIGN).unwrap()
+}
+
+fn cpu_cache() -> &'static SpinNoIrq<CpuCache> {
+    &CPU_CACHES[arch::cpu_index()]
+}
+
+/// Refills `magazine` with half a magazine of blocks from the backend and
+/// returns one more.
+fn refill(heap: &RawHeap, class: usize, magazine: &mut Magazine) -> *mut u8 {
+    let mut blocks = [0; MAGAZINE_LEN / 2 + 1];
+    let count = unsafe { heap.alloc_batch(class_layout(class), &mut blocks) };
+    if count == 0 {
+        return core::ptr::null_mut();
+    }
+    for &block in &blocks[1..count] {
+        magazine.push(block as *mut u8);
+    }
+    blocks[0] as *mut u8
+}
+
+/// Returns half of `magazine` to the backend.
+fn drain_half(heap: &RawHeap, class: usize, magazine: &mut Magazine) {
+    let half = MAGAZINE_LEN / 2;
+    unsafe { heap.dealloc_batch(class_layout(class), &magazine.blocks[half..magazine.len]) };
+    magazine.len = half;
+}
+
+pub(super) unsafe fn alloc(heap: &RawHeap, layout: Layout) -> *mut u8 {
+    let Some(class) = class_of(layout) else {
+        return unsafe { core::alloc::GlobalAlloc::alloc(heap, layout) };
+    };
+    if !ENABLED.load(Ordering::Relaxed) {
+        return unsafe { core::alloc::GlobalAlloc::alloc(heap, class_layout(class)) };
+    }
+
+    let mut cpu = cpu_cache().lock();
+    let cache = &mut cpu.classes[class];
+    if let Some(block) = cache.loaded.pop() {
+        return block;
+    }
+    if cache.previous.len > 0 {
+        core::mem::swap(&mut cache.loaded, &mut cache.previous);
+        return cache.loaded.pop().unwrap();
+    }
+    {
+        let mut depot = DEPOTS[class].lock();
+        if depot.len > 0 {
+            depot.len -= 1;
+            cache.loaded = depot.full[depot.len];
+            drop(depot);
+            return cache.loaded.pop().unwrap();
+        }
+    }
+    refill(heap, class, &mut cache.loaded)
+}
+
+pub(super) unsafe fn dealloc(heap: &RawHeap, ptr: *mut u8, layout: Layout) {
+    let Some(class) = class_of(layout) else {
+        return unsafe { core::alloc::GlobalAlloc::dealloc(heap, ptr, layout) };
+    };
+    if !ENABLED.load(Ordering::Relaxed) {
+        return unsafe { core::alloc::GlobalAlloc::dealloc(heap, ptr, class_layout(class)) };
+    }
+
+    let mut cpu = cpu_cache().lock();
+    let cache = &mut cpu.classes[class];
+    if !cache.loaded.is_full() {
+        return cache.loaded.push(ptr);
+    }
+    if !cache.previous.is_full() {
+        core::mem::swap(&mut cache.loaded, &mut cache.previous);
+        return cache.loaded.push(ptr);
+    }
+    {
+        let mut depot = DEPOTS[class].lock();
+        if depot.len < DEPOT_LEN {
+            let index = depot.len;
+            depot.full[index] = cache.previous;
+            depot.len += 1;
+            drop(depot);
+            cache.previous = cache.loaded;
+            cache.loaded = Magazine::EMPTY;
+            return cache.loaded.push(ptr);
+        }
+    }
+    drain_half(heap, class, &mut cache.loaded);
+    cache.loaded.push(ptr)
+}
+
+pub(super) unsafe fn realloc(
+    heap: &RawHeap,
+    ptr: *mut u8,
+    layout: Layout,
+    new_size: usize,
+) -> *mut u8 {
+    let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+    match (class_of(layout), class_of(new_layout)) {
+        (Some(old), Some(new)) if old == new => ptr,
+        (None, None) => unsafe { core::alloc::GlobalAlloc::realloc(heap, ptr, layout, new_size) },
+        _ => unsafe {
+            let new_ptr = alloc(heap, new_layout);
+            if !new_ptr.is_null() {
+                core::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+                dealloc(heap, ptr, layout);
+            }
+            new_ptr
+        },
+    }
+}
+
+/// Returns every cached block to the backend.
+pub fn flush(heap: &RawHeap) {
+    for cpu in &CPU_CACHES {
+        let mut cpu = cpu.lock();
+        for (class, cache) in cpu.classes.iter_mut().enumerate() {
+            for magazine in [&mut cache.loaded, &mut cache.previous] {
+                release(heap, class, magazine);
+            }
+        }
+    }
+    for (class, depot) in DEPOTS.iter().enumerate() {
+        let mut depot = depot.lock();
+        let len = depot.len;
+        for magazine in &mut depot.full[..len] {
+            release(heap, class, magazine);
+        }
+        depot.len = 0;
+    }
+}
+
+fn release(heap: &RawHeap, class: usize, magazine: &mut Magazine) {
+    while let Some(block) = magazine.pop() {
+        unsafe { core::alloc::GlobalAlloc::dealloc(heap, block, class_layout(class)) };
+    }
+}
+
+/// Enables or disables the caches. Disabling them flushes them.
+pub fn set_enabled(heap: &RawHeap, enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        flush(heap);
+    }
+}
+
+/// Returns the number of bytes held in the caches.
+pub fn cached_bytes() -> usize {
+    let magazine_bytes =
+        |class: usize, magazine: &Magazine| magazine.len * (MIN_CLASS_SIZE << class);
+    let mut total = 0;
+    for cpu in &CPU_CACHES {
+        let cpu = cpu.lock();
+        for (class, cache) in cpu.classes.iter().enumerate() {
+            total += magazine_bytes(class, &cache.loaded) + magazine_bytes(class, &cache.previous);
+        }
+    }
+    for (class, depot) in DEPOTS.iter().enumerate() {
+        let depot = depot.lock();
+        total += depot.full[..depot.len]
+            .iter()
+            .map(|magazine| magazine_bytes(class, magazine))
+            .sum::<usize>();
+    }
+    total
+}
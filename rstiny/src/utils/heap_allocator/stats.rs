@@ -0,0 +1,184 @@
+//! Heap statistics.
+//!
+//! Counters are updated with relaxed atomics on every allocation, so reading
+//! them never blocks. The largest free block is measured on demand by probing
+//! the backend, which needs its lock.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Number of power-of-two size classes: `<= 16` bytes, then up to `> 512 KiB`.
+pub const SIZE_CLASSES: usize = 17;
+/// Upper bound of the smallest size class.
+const MIN_CLASS_SIZE: usize = 16;
+
+/// Returns the size class of an allocation of `size` bytes.
+pub fn size_class(size: usize) -> usize {
+    if size <= MIN_CLASS_SIZE {
+        return 0;
+    }
+    let log2 = (usize::BITS - (size - 1).leading_zeros()) as usize;
+    (log2 - MIN_CLASS_SIZE.trailing_zeros() as usize).min(SIZE_CLASSES - 1)
+}
+
+/// Returns the largest size in class `class`, or `None` for the last, unbounded class.
+pub fn size_class_limit(class: usize) -> Option<usize> {
+    (class < SIZE_CLASSES - 1).then(|| MIN_CLASS_SIZE << class)
+}
+
+pub(super) struct Counters {
+    allocated_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    allocs: AtomicU64,
+    frees: AtomicU64,
+    reallocs: AtomicU64,
+    failures: AtomicU64,
+    histogram: [AtomicU64; SIZE_CLASSES],
+}
+
+impl Counters {
+    pub const fn new() -> Self {
+        Self {
+            allocated_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+            allocs: AtomicU64::new(0),
+            frees: AtomicU64::new(0),
+            reallocs: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            histogram: [const { AtomicU64::new(0) }; SIZE_CLASSES],
+        }
+    }
+
+    fn grow(&self, bytes: usize) {
+        let now = self.allocated_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak_bytes.fetch_max(now, Ordering::Relaxed);
+    }
+
+    pub fn on_alloc(&self, size: usize) {
+        self.allocs.fetch_add(1, Ordering::Relaxed);
+        self.histogram[size_class(size)].fetch_add(1, Ordering::Relaxed);
+        self.grow(size);
+    }
+
+    pub fn on_free(&self, size: usize) {
+        self.frees.fetch_add(1, Ordering::Relaxed);
+        self.allocated_bytes.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    pub fn on_realloc(&self, old_size: usize, new_size: usize) {
+        self.reallocs.fetch_add(1, Ordering::Relaxed);
+        if new_size >= old_size {
+            self.grow(new_size - old_size);
+        } else {
+            self.allocated_bytes
+                .fetch_sub(old_size - new_size, Ordering::Relaxed);
+        }
+    }
+
+    pub fn on_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HeapStats {
+        HeapStats {
+            allocated_bytes: self.allocated_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+            allocs: self.allocs.load(Ordering::Relaxed),
+            frees: self.frees.load(Ordering::Relaxed),
+            reallocs: self.reallocs.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            histogram: core::array::from_fn(|i| self.histogram[i].load(Ordering::Relaxed)),
+            claimed_bytes: 0,
+            pending_bytes: 0,
+            cached_bytes: 0,
+            largest_free_block: None,
+        }
+    }
+}
+
+/// A snapshot of the heap statistics.
+#[derive(Debug, Clone)]
+pub struct HeapStats {
+    /// Bytes currently allocated, as requested by the callers.
+    pub allocated_bytes: usize,
+    /// Highest value reached by `allocated_bytes`.
+    pub peak_bytes: usize,
+    pub allocs: u64,
+    pub frees: u64,
+    pub reallocs: u64,
+    /// Allocations that failed even after growing the heap.
+    pub failures: u64,
+    /// Number of allocations per size class, see [`size_class`].
+    pub histogram: [u64; SIZE_CLASSES],
+    /// Bytes of RAM handed to the backend so far.
+    pub claimed_bytes: usize,
+    /// Bytes of RAM registered but not claimed yet.
+    pub pending_bytes: usize,
+    /// Bytes of free blocks held in the per-CPU caches.
+    pub cached_bytes: usize,
+    /// Largest block that can be allocated without growing the heap, or
+    /// `None` if the heap lock was busy.
+    pub largest_free_block: Option<usize>,
+}
+
+impl HeapStats {
+    /// Bytes claimed but not allocated (backend metadata included), per-CPU
+    /// caches excluded.
+    pub fn free_bytes(&self) -> usize {
+        self.claimed_bytes
+            .saturating_sub(self.allocated_bytes)
+            .saturating_sub(self.cached_bytes)
+    }
+
+    /// Fragmentation estimate in percent: how much of the free memory is not
+    /// part of the largest free block.
+    pub fn fragmentation_percent(&self) -> Option<usize> {
+        let free = self.free_bytes();
+        let largest = self.largest_free_block?;
+        Some(if free == 0 {
+            0
+        } else {
+            100 - (largest.min(free) * 100 / free)
+        })
+    }
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "heap: {} bytes allocated (peak {}), {} claimed, {} pending, {} cached",
+            self.allocated_bytes,
+            self.peak_bytes,
+            self.claimed_bytes,
+            self.pending_bytes,
+            self.cached_bytes
+        )?;
+        writeln!(
+            f,
+            "heap: {} allocs, {} frees, {} reallocs, {} failures",
+            self.allocs, self.frees, self.reallocs, self.failures
+        )?;
+        match (self.largest_free_block, self.fragmentation_percent()) {
+            (Some(largest), Some(frag)) => writeln!(
+                f,
+                "heap: largest free block {largest} bytes, fragmentation {frag}%"
+            )?,
+            _ => writeln!(f, "heap: largest free block unknown (heap locked)")?,
+        }
+        for (class, &count) in self.histogram.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            match size_class_limit(class) {
+                Some(limit) => writeln!(f, "heap:   <= {limit:>7} B: {count}")?,
+                None => writeln!(
+                    f,
+                    "heap:    > {:>7} B: {count}",
+                    MIN_CLASS_SIZE << (class - 1)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+//! Allocation tracing, enabled by the `alloc-trace` feature.
+//!
+//! Every live allocation is recorded in a static hash table with its size,
+//! alignment, the return address of the allocator call and a sequence
+//! number. The table never allocates, so it can be used from the allocator
+//! itself and while the heap is exhausted.
+//!
+//! [`snapshot`] and [`Snapshot::diff`] report the allocations made after a
+//! given point and still live, which is how leaks are found in tests.
+
+use core::alloc::Layout;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use kspin::SpinNoIrq;
+
+/// Maximum number of live allocations tracked. Must be a power of two.
+const TABLE_LEN: usize = 4096;
+/// Maximum number of distinct callers listed by a dump.
+const MAX_CALLERS: usize = 64;
+
+/// The record of a live allocation.
+#[derive(Clone, Copy)]
+pub(super) struct Entry {
+    /// Address of the block, 0 for an empty slot.
+    ptr: usize,
+    size: usize,
+    align: usize,
+    caller: usize,
+    seq: u64,
+}
+
+const EMPTY: Entry = Entry {
+    ptr: 0,
+    size: 0,
+    align: 0,
+    caller: 0,
+    seq: 0,
+};
+
+struct Table {
+    entries: [Entry; TABLE_LEN],
+    len: usize,
+    /// Allocations not recorded because the table was full.
+    untracked: u64,
+}
+
+// 中断处理函数也可能分配内存，持锁期间必须屏蔽中断
+static TABLE: SpinNoIrq<Table> = SpinNoIrq::new(Table {
+    entries: [EMPTY; TABLE_LEN],
+    len: 0,
+    untracked: 0,
+});
+static SEQ: AtomicU64 = AtomicU64::new(1);
+
+fn slot_of(ptr: usize) -> usize {
+    ((ptr as u64 >> 4).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - TABLE_LEN.trailing_zeros()))
+        as usize
+}
+
+impl Table {
+    fn insert(&mut self, entry: Entry) {
+        // 保留一个空槽，保证查找总能终止
+        if self.len == TABLE_LEN - 1 {
+            self.untracked += 1;
+            return;
+        }
+        let mut slot = slot_of(entry.ptr);
+        while self.entries[slot].ptr != 0 {
+            slot = (slot + 1) % TABLE_LEN;
+        }
+        self.entries[slot] = entry;
+        self.len += 1;
+    }
+
+    /// Removes the entry of `ptr`, with backward-shift deletion so that no
+    /// tombstones are needed.
+    fn remove(&mut self, ptr: usize) -> Option<Entry> {
+        let mut slot = slot_of(ptr);
+        while self.entries[slot].ptr != ptr {
+            if self.entries[slot].ptr == 0 {
+                return None;
+            }
+            slot = (slot + 1) % TABLE_LEN;
+        }
+        let removed = self.entries[slot];
+        let mut hole = slot;
+        let mut next = (hole + 1) % TABLE_LEN;
+        while self.entries[next].ptr != 0 {
+            let home = slot_of(self.entries[next].ptr);
+            // 只有当 home 不在 (hole, next] 之间时才能前移
+            let movable = if hole <= next {
+                home <= hole || home > next
+            } else {
+                home <= hole && home > next
+            };
+            if movable {
+                self.entries[hole] = self.entries[next];
+                hole = next;
+            }
+            next = (next + 1) % TABLE_LEN;
+        }
+        self.entries[hole] = EMPTY;
+        self.len -= 1;
+        Some(removed)
+    }
+
+    fn live(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(|entry| entry.ptr != 0)
+    }
+}
+
+pub(super) fn on_alloc(ptr: *mut u8, layout: Layout, caller: usize) {
+    let seq = SEQ.fetch_add(1, Ordering::Relaxed);
+    TABLE.lock().insert(Entry {
+        ptr: ptr as usize,
+        size: layout.size(),
+        align: layout.align(),
+        caller,
+        seq,
+    });
+}
+
+/// Removes the record of `ptr` and returns it.
+pub(super) fn on_free(ptr: *mut u8) -> Option<Entry> {
+    TABLE.lock().remove(ptr as usize)
+}
+
+/// Puts back a record removed by [`on_free`], for a block that was not freed
+/// after all.
+pub(super) fn restore(entry: Entry) {
+    TABLE.lock().insert(entry);
+}
+
+/// Returns the layout and the caller of the live allocation at `ptr`.
+pub fn lookup(ptr: *const u8) -> Option<(Layout, usize)> {
+    let table = TABLE.lock();
+    let entry = table.live().find(|entry| entry.ptr == ptr as usize)?;
+    let layout = Layout::from_size_align(entry.size, entry.align).ok()?;
+    Some((layout, entry.caller))
+}
+
+/// Allocations of one caller.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CallerUsage {
+    pub caller: usize,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// Live allocations grouped by caller, largest first.
+struct CallerReport {
+    callers: [CallerUsage; MAX_CALLERS],
+    len: usize,
+    /// Allocations of the callers that did not fit in `callers`.
+    other: CallerUsage,
+    total: CallerUsage,
+    untracked: u64,
+}
+
+impl CallerReport {
+    /// Groups the live allocations with a sequence number of at least `since`.
+    fn collect(since: u64) -> Self {
+        let mut report = Self {
+            callers: [CallerUsage::default(); MAX_CALLERS],
+            len: 0,
+            other: CallerUsage::default(),
+            total: CallerUsage::default(),
+            untracked: 0,
+        };
+        let table = TABLE.lock();
+        report.untracked = table.untracked;
+        for entry in table.live().filter(|entry| entry.seq >= since) {
+            let usage = match report.callers[..report.len]
+                .iter()
+                .position(|usage| usage.caller == entry.caller)
+            {
+                Some(index) => &mut report.callers[index],
+                None if report.len < MAX_CALLERS => {
+                    report.len += 1;
+                    let usage = &mut report.callers[report.len - 1];
+                    usage.caller = entry.caller;
+                    usage
+                }
+                None => &mut report.other,
+            };
+            usage.count += 1;
+            usage.bytes += entry.size;
+            report.total.count += 1;
+            report.total.bytes += entry.size;
+        }
+        drop(table);
+        report.callers[..report.len].sort_unstable_by(|a, b| b.bytes.cmp(&a.bytes));
+        report
+    }
+
+    fn print(&self) {
+        for usage in &self.callers[..self.len] {
+            console_println!(
+                "  {:#018x}: {:>6} allocations, {:>10} bytes",
+                usage.caller,
+                usage.count,
+                usage.bytes
+            );
+        }
+        if self.other.count > 0 {
+            console_println!(
+                "  {:>18}: {:>6} allocations, {:>10} bytes",
+                "other callers",
+                self.other.count,
+                self.other.bytes
+            );
+        }
+        console_println!(
+            "  total: {} allocations, {} bytes",
+            self.total.count,
+            self.total.bytes
+        );
+        if self.untracked > 0 {
+            console_println!(
+                "  ({} allocations were not tracked: table full)",
+                self.untracked
+            );
+        }
+    }
+}
+
+/// Prints the live allocations, grouped by caller address.
+///
+/// The addresses can be resolved with `addr2line -e <kernel elf>`.
+pub fn dump_live_allocations() {
+    console_println!("live allocations by caller:");
+    CallerReport::collect(0).print();
+}
+
+/// Returns the number of live allocations and their total size.
+pub fn live_usage() -> (usize, usize) {
+    let table = TABLE.lock();
+    (table.len, table.live().map(|entry| entry.size).sum())
+}
+
+/// A point in time to compare the live allocations against.
+#[derive(Debug, Clone, Copy)]
+pub struct Snapshot {
+    seq: u64,
+    count: usize,
+    bytes: usize,
+}
+
+/// Records the current state of the heap.
+pub fn snapshot() -> Snapshot {
+    let (count, bytes) = live_usage();
+    Snapshot {
+        seq: SEQ.load(Ordering::Relaxed),
+        count,
+        bytes,
+    }
+}
+
+/// Difference between a [`Snapshot`] and the current state.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotDiff {
+    /// Change in the number of live allocations.
+    pub count_delta: isize,
+    /// Change in the number of live bytes.
+    pub bytes_delta: isize,
+    /// Allocations made since the snapshot and still live.
+    pub new_live: CallerUsage,
+}
+
+impl SnapshotDiff {
+    /// Returns whether nothing allocated since the snapshot is still live.
+    pub fn is_clean(&self) -> bool {
+        self.new_live.count == 0
+    }
+}
+
+impl Snapshot {
+    /// Compares the snapshot with the current state of the heap.
+    pub fn diff(&self) -> SnapshotDiff {
+        let (count, bytes) = live_usage();
+        let report = CallerReport::collect(self.seq);
+        SnapshotDiff {
+            count_delta: count as isize - self.count as isize,
+            bytes_delta: bytes as isize - self.bytes as isize,
+            new_live: report.total,
+        }
+    }
+
+    /// Prints the allocations made since the snapshot and still live,
+    /// grouped by caller.
+    pub fn print_diff(&self) {
+        let diff = self.diff();
+        console_println!(
+            "heap since snapshot: {:+} allocations, {:+} bytes; still live:",
+            diff.count_delta,
+            diff.bytes_delta
+        );
+        CallerReport::collect(self.seq).print();
+    }
+}
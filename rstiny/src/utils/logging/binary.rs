@@ -0,0 +1,362 @@
+//! Compact binary log format.
+//!
+//! With the `binary-log` feature, the `binfo!`-family macros do not format
+//! anything on the target. Each call site stores its level, location and
+//! format string once in the read-only `.rstiny_log` section, and a log
+//! call only sends the address of that descriptor, a timestamp and the
+//! encoded arguments. `tools/klog-decode` reads the descriptors back from the
+//! rstiny ELF and turns the stream into readable lines. Without the feature,
+//! the macros expand to ordinary `log` records.
+//!
+//! With the feature, records of the `log` macros are sent as frames too, see
+//! [`record_frame`]. Their message is formatted on the target, so only the
+//! framing is saved.
+//!
+//! # Wire format
+//!
+//! A frame is `0x00`, the [COBS]-encoded payload, `0x00`. Text output sent
+//! between frames never contains `0x00`, so both can share the UART.
+//!
+//! The payload is a sequence of LEB128 varints:
+//!
+//! - descriptor address;
+//! - timestamp in microseconds since boot;
+//! - CPU id;
+//! - one tagged value per argument, see [`Tag`].
+//!
+//! A descriptor is `<level letter> 0x1f <file>:<line> 0x1f <format> 0x00`.
+//! Placeholders in the location are filled by the first arguments, before
+//! those of the format.
+//!
+//! [COBS]: https://en.wikipedia.org/wiki/Consistent_Overhead_Byte_Stuffing
+
+use core::fmt::Write;
+
+use log::Record;
+
+use super::RecordContext;
+use crate::utils::arch;
+use crate::utils::text::TruncatingWriter;
+
+/// Maximum size of a payload; arguments that do not fit are dropped.
+const MAX_PAYLOAD: usize = 250;
+
+/// Length of each descriptor in [`RECORD_SITES`].
+const RECORD_SITE_LEN: usize = 11;
+
+/// Descriptors of the records of the `log` macros, indexed by `Level as
+/// usize - 1`. Their location is sent as arguments.
+#[used]
+#[unsafe(link_section = ".rstiny_log")]
+static RECORD_SITES: [[u8; RECORD_SITE_LEN]; 5] = [
+    *b"E\x1f{}:{}\x1f{}\0",
+    *b"W\x1f{}:{}\x1f{}\0",
+    *b"I\x1f{}:{}\x1f{}\0",
+    *b"D\x1f{}:{}\x1f{}\0",
+    *b"T\x1f{}:{}\x1f{}\0",
+];
+
+/// Type tag preceding every encoded argument.
+#[repr(u8)]
+pub enum Tag {
+    /// Unsigned integer, varint.
+    Unsigned = 1,
+    /// Signed integer, zigzag varint.
+    Signed = 2,
+    /// Boolean, one byte.
+    Bool = 3,
+    /// UTF-8 string, varint length then bytes.
+    Str = 4,
+    /// Unicode scalar value, varint.
+    Char = 5,
+    /// `f64`, 8 bytes little-endian.
+    Float = 6,
+    /// Marks a payload whose arguments were cut off.
+    Truncated = 0x7f,
+}
+
+/// Copies a descriptor string into an array, so it can be stored in the
+/// `.rstiny_log` section.
+pub const fn site_bytes<const N: usize>(site: &str) -> [u8; N] {
+    let bytes = site.as_bytes();
+    let mut out = [0; N];
+    let mut i = 0;
+    while i < N {
+        out[i] = bytes[i];
+        i += 1;
+    }
+    out
+}
+
+/// A binary log record being encoded.
+pub struct Frame {
+    buf: [u8; MAX_PAYLOAD],
+    len: usize,
+    truncated: bool,
+}
+
+impl Frame {
+    /// Starts a record for the call site whose descriptor is at `site`.
+    pub fn new(site: usize) -> Self {
+        Self::with_context(site, arch::monotonic_nanos(), arch::cpu_id())
+    }
+
+    fn with_context(site: usize, nanos: u64, cpu_id: usize) -> Self {
+        let mut frame = Self {
+            buf: [0; MAX_PAYLOAD],
+            len: 0,
+            truncated: false,
+        };
+        frame.put_varint(site as u64);
+        frame.put_varint(nanos / 1_000);
+        frame.put_varint(cpu_id as u64);
+        frame
+    }
+
+    fn put_bytes(&mut self, bytes: &[u8]) -> bool {
+        if self.truncated || self.len + bytes.len() > MAX_PAYLOAD - 1 {
+            self.truncated = true;
+            return false;
+        }
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        true
+    }
+
+    fn put_varint(&mut self, mut value: u64) {
+        let mut bytes = [0u8; 10];
+        let mut n = 0;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes[n] = byte;
+                n += 1;
+                break;
+            }
+            bytes[n] = byte | 0x80;
+            n += 1;
+        }
+        self.put_bytes(&bytes[..n]);
+    }
+
+    /// Appends a tagged argument. The whole argument is dropped if it does not fit.
+    fn put_arg(&mut self, tag: Tag, f: impl FnOnce(&mut Self)) {
+        let saved = self.len;
+        self.put_bytes(&[tag as u8]);
+        f(self);
+        if self.truncated {
+            self.len = saved;
+        }
+    }
+
+    /// Frames the payload into `out`, returning the frame length.
+    fn finish(&mut self, out: &mut [u8; MAX_FRAME]) -> usize {
+        if self.truncated {
+            // 预留了 1 字节，截断标记总能写入
+            self.buf[self.len] = Tag::Truncated as u8;
+            self.len += 1;
+            self.truncated = false;
+        }
+        let n = cobs_encode(&self.buf[..self.len], &mut out[1..]);
+        out[0] = 0;
+        out[n + 1] = 0;
+        n + 2
+    }
+
+    /// Sends the record to the console.
+    pub fn send(mut self) {
+        let mut out = [0u8; MAX_FRAME];
+        let n = self.finish(&mut out);
+        crate::console::write_raw(&out[..n]);
+    }
+
+    /// Sends the record to the console, unless the console lock is taken.
+    /// Returns whether the record was sent.
+    pub fn try_send(mut self) -> bool {
+        let mut out = [0u8; MAX_FRAME];
+        let n = self.finish(&mut out);
+        crate::console::try_write_raw(&out[..n])
+    }
+}
+
+/// Size of an encoded frame: COBS adds a byte per 254, plus the delimiters.
+const MAX_FRAME: usize = MAX_PAYLOAD + MAX_PAYLOAD / 254 + 3;
+
+/// Encodes a record of the `log` macros: its file and line, then its
+/// formatted message.
+pub fn record_frame(record: &Record, ctx: &RecordContext) -> Frame {
+    let site = &RECORD_SITES[record.level() as usize - 1];
+    let mut frame = Frame::with_context(site.as_ptr() as usize, ctx.nanos, ctx.cpu_id);
+    record.file().unwrap_or("?").encode(&mut frame);
+    record.line().unwrap_or(0).encode(&mut frame);
+    // 截短消息使其放得下：标签、2 字节长度和截断标记
+    let room = MAX_PAYLOAD.saturating_sub(frame.len + 4);
+    let mut buf = [0; MAX_PAYLOAD];
+    let mut text = TruncatingWriter::new(&mut buf);
+    let _ = text.write_fmt(*record.args());
+    let written = text.written();
+    let mut len = written.min(room);
+    // 只在字符边界截断，因此总是合法的 UTF-8
+    let message = core::str::from_utf8(&buf[..written]).unwrap();
+    while !message.is_char_boundary(len) {
+        len -= 1;
+    }
+    message[..len].encode(&mut frame);
+    frame.truncated |= len < message.len();
+    frame
+}
+
+/// COBS-encodes `input` into `output`, returning the encoded length.
+fn cobs_encode(input: &[u8], output: &mut [u8]) -> usize {
+    let mut code_pos = 0;
+    let mut out = 1;
+    let mut code = 1u8;
+    for &b in input {
+        if b == 0 {
+            output[code_pos] = code;
+            code_pos = out;
+            out += 1;
+            code = 1;
+        } else {
+            output[out] = b;
+            out += 1;
+            code += 1;
+            if code == 0xff {
+                output[code_pos] = code;
+                code_pos = out;
+                out += 1;
+                code = 1;
+            }
+        }
+    }
+    output[code_pos] = code;
+    out
+}
+
+/// A value that can be sent as a binary log argument.
+pub trait Encode {
+    fn encode(&self, frame: &mut Frame);
+}
+
+macro_rules! impl_encode_unsigned {
+    ($($ty:ty),*) => {$(
+        impl Encode for $ty {
+            fn encode(&self, frame: &mut Frame) {
+                frame.put_arg(Tag::Unsigned, |f| f.put_varint(*self as u64));
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_encode_signed {
+    ($($ty:ty),*) => {$(
+        impl Encode for $ty {
+            fn encode(&self, frame: &mut Frame) {
+                let v = *self as i64;
+                frame.put_arg(Tag::Signed, |f| f.put_varint(((v << 1) ^ (v >> 63)) as u64));
+            }
+        }
+    )*};
+}
+
+impl_encode_unsigned!(u8, u16, u32, u64, usize);
+impl_encode_signed!(i8, i16, i32, i64, isize);
+
+impl Encode for bool {
+    fn encode(&self, frame: &mut Frame) {
+        frame.put_arg(Tag::Bool, |f| {
+            f.put_bytes(&[*self as u8]);
+        });
+    }
+}
+
+impl Encode for char {
+    fn encode(&self, frame: &mut Frame) {
+        frame.put_arg(Tag::Char, |f| f.put_varint(*self as u64));
+    }
+}
+
+impl Encode for f64 {
+    fn encode(&self, frame: &mut Frame) {
+        frame.put_arg(Tag::Float, |f| {
+            f.put_bytes(&self.to_le_bytes());
+        });
+    }
+}
+
+impl Encode for f32 {
+    fn encode(&self, frame: &mut Frame) {
+        (*self as f64).encode(frame);
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, frame: &mut Frame) {
+        frame.put_arg(Tag::Str, |f| {
+            f.put_varint(self.len() as u64);
+            f.put_bytes(self.as_bytes());
+        });
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, frame: &mut Frame) {
+        (**self).encode(frame);
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __blog {
+    ($letter:literal, $level:expr, $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        #[cfg(feature = "binary-log")]
+        if ::log::log_enabled!($level) {
+            const SITE: &str =
+                concat!($letter, "\x1f", file!(), ":", line!(), "\x1f", $fmt, "\0");
+            // 目标端不读取描述符，只发送它的地址
+            #[used]
+            #[unsafe(link_section = ".rstiny_log")]
+            static SITE_BYTES: [u8; SITE.len()] =
+                $crate::utils::logging::binary::site_bytes(SITE);
+            #[allow(unused_mut)]
+            let mut frame = $crate::utils::logging::binary::Frame::new(
+                ::core::ptr::addr_of!(SITE_BYTES) as usize,
+            );
+            $($crate::utils::logging::binary::Encode::encode(&$arg, &mut frame);)*
+            frame.send();
+        }
+        #[cfg(not(feature = "binary-log"))]
+        ::log::log!($level, $fmt $(, $arg)*);
+    }};
+}
+
+/// Logs an error in the binary format (see [`binary`](crate::utils::logging::binary)).
+#[macro_export]
+macro_rules! berror {
+    ($($arg:tt)+) => ($crate::__blog!("E", ::log::Level::Error, $($arg)+));
+}
+
+/// Logs a warning in the binary format.
+#[macro_export]
+macro_rules! bwarn {
+    ($($arg:tt)+) => ($crate::__blog!("W", ::log::Level::Warn, $($arg)+));
+}
+
+/// Logs an informational message in the binary format.
+#[macro_export]
+macro_rules! binfo {
+    ($($arg:tt)+) => ($crate::__blog!("I", ::log::Level::Info, $($arg)+));
+}
+
+/// Logs a debug message in the binary format.
+#[macro_export]
+macro_rules! bdebug {
+    ($($arg:tt)+) => ($crate::__blog!("D", ::log::Level::Debug, $($arg)+));
+}
+
+/// Logs a trace message in the binary format.
+#[macro_export]
+macro_rules! btrace {
+    ($($arg:tt)+) => ($crate::__blog!("T", ::log::Level::Trace, $($arg)+));
+}
@@ -0,0 +1,219 @@
+//! Deferred logging for interrupt and exception contexts.
+//!
+//! Printing to the console spins on the console lock, which a handler must not
+//! do: the context it interrupted may hold the lock. Records logged from a
+//! handler, i.e. between [`enter_handler`] and [`exit_handler`] (called by the
+//! IRQ entry, see `crate::trap`), or while the console lock is taken, are
+//! formatted into a per-CPU lock-free queue instead. They are printed by
+//! [`drain`] from a normal context: before the next printed record, at the end
+//! of boot and in the shell idle loop. When a queue is full, records are
+//! dropped and counted.
+
+use core::cell::UnsafeCell;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use log::Record;
+
+use super::{RecordContext, level_from_u8, write_record};
+use crate::config::MAX_CPUS;
+use crate::utils::arch;
+use crate::utils::text::TruncatingWriter;
+
+/// Number of records each CPU can queue.
+const QUEUE_ENTRIES: usize = 32;
+/// Maximum length of a queued message; longer messages are truncated.
+const MESSAGE_LEN: usize = 128;
+/// How long [`drain`] waits for the console lock before leaving the remaining
+/// records queued, in nanoseconds.
+const PRINT_TIMEOUT_NANOS: u64 = 100_000_000;
+
+#[derive(Clone, Copy)]
+struct QueuedRecord {
+    /// `Level as u8`, so that the queues live in `.bss`.
+    level: u8,
+    file: Option<&'static str>,
+    line: Option<u32>,
+    ctx: RecordContext,
+    len: usize,
+    text: [u8; MESSAGE_LEN],
+}
+
+struct Slot {
+    /// `pos + 1` once the record reserved at position `pos` is complete.
+    ready: AtomicU64,
+    record: UnsafeCell<QueuedRecord>,
+}
+
+/// Bounded multi-producer queue. Producers are the contexts running on one
+/// CPU (possibly nested); the consumer is whoever holds [`DRAINING`].
+struct CpuQueue {
+    slots: [Slot; QUEUE_ENTRIES],
+    head: AtomicU64,
+    tail: AtomicU64,
+    dropped: AtomicU64,
+    /// Value of `dropped` last reported by [`drain`].
+    reported: AtomicU64,
+}
+
+// SAFETY: 生产者通过 CAS 推进 `head` 独占槽位，消费者由 `DRAINING` 保证唯一
+unsafe impl Sync for CpuQueue {}
+
+static QUEUES: [CpuQueue; MAX_CPUS] = [const {
+    CpuQueue {
+        slots: [const {
+            Slot {
+                ready: AtomicU64::new(0),
+                record: UnsafeCell::new(QueuedRecord {
+                    level: 0,
+                    file: None,
+                    line: None,
+                    ctx: RecordContext {
+                        nanos: 0,
+                        cpu_id: 0,
+                        task: None,
+                    },
+                    len: 0,
+                    text: [0; MESSAGE_LEN],
+                }),
+            }
+        }; QUEUE_ENTRIES],
+        head: AtomicU64::new(0),
+        tail: AtomicU64::new(0),
+        dropped: AtomicU64::new(0),
+        reported: AtomicU64::new(0),
+    }
+}; MAX_CPUS];
+
+static DRAINING: AtomicBool = AtomicBool::new(false);
+/// Handler nesting depth of each CPU.
+static HANDLER_DEPTH: [AtomicUsize; MAX_CPUS] = [const { AtomicUsize::new(0) }; MAX_CPUS];
+
+/// Marks the start of an interrupt or exception handler on the current CPU.
+/// Must be paired with [`exit_handler`] before the handler returns or
+/// switches away from its exception frame.
+pub fn enter_handler() {
+    HANDLER_DEPTH[arch::cpu_index()].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Marks the end of the handler started by [`enter_handler`].
+pub fn exit_handler() {
+    HANDLER_DEPTH[arch::cpu_index()].fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Forgets the handlers left without [`exit_handler`] on the current CPU, when
+/// a panic unwound out of them back to a normal context.
+pub fn reset_handlers() {
+    HANDLER_DEPTH[arch::cpu_index()].store(0, Ordering::Relaxed);
+}
+
+/// Returns whether the current CPU runs an interrupt or exception handler.
+pub fn in_handler() -> bool {
+    HANDLER_DEPTH[arch::cpu_index()].load(Ordering::Relaxed) != 0
+}
+
+/// Queues a record on the current CPU's queue. Never blocks.
+pub fn push(record: &Record, ctx: &RecordContext) {
+    let queue = &QUEUES[arch::cpu_index()];
+    let mut pos = queue.head.load(Ordering::Relaxed);
+    loop {
+        if pos - queue.tail.load(Ordering::Acquire) >= QUEUE_ENTRIES as u64 {
+            queue.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        match queue.head.compare_exchange_weak(
+            pos,
+            pos + 1,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => break,
+            Err(current) => pos = current,
+        }
+    }
+
+    let slot = &queue.slots[(pos % QUEUE_ENTRIES as u64) as usize];
+    let queued = unsafe { &mut *slot.record.get() };
+    let mut writer = TruncatingWriter::new(&mut queued.text);
+    let _ = writer.write_fmt(*record.args());
+    queued.len = writer.written();
+    queued.level = record.level() as u8;
+    queued.file = record.file_static();
+    queued.line = record.line();
+    queued.ctx = *ctx;
+    slot.ready.store(pos + 1, Ordering::Release);
+}
+
+/// Prints the queued records of every CPU. Does nothing from a handler, or if
+/// another CPU is already draining. Stops early, leaving the records queued,
+/// if the console lock stays taken.
+pub fn drain() {
+    if in_handler()
+        || DRAINING
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+    {
+        return;
+    }
+    'queues: for (cpu, queue) in QUEUES.iter().enumerate() {
+        let mut tail = queue.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &queue.slots[(tail % QUEUE_ENTRIES as u64) as usize];
+            if slot.ready.load(Ordering::Acquire) != tail + 1 {
+                break;
+            }
+            let queued = unsafe { *slot.record.get() };
+            // 控制台锁可能被当前 CPU 自己持有，不能无限等待；未打印的记录留在队列中
+            if !print(&queued) {
+                break 'queues;
+            }
+            queue.tail.store(tail + 1, Ordering::Release);
+            tail += 1;
+        }
+
+        let dropped = queue.dropped.load(Ordering::Relaxed);
+        let reported = queue.reported.swap(dropped, Ordering::Relaxed);
+        if dropped > reported {
+            warn!(
+                "{} deferred log records dropped on CPU {cpu}",
+                dropped - reported
+            );
+        }
+    }
+    DRAINING.store(false, Ordering::Release);
+}
+
+/// Prints a queued record, waiting up to [`PRINT_TIMEOUT_NANOS`] for the
+/// console lock. Returns `false` if it was not printed.
+fn print(queued: &QueuedRecord) -> bool {
+    let text = core::str::from_utf8(&queued.text[..queued.len]).unwrap_or("<invalid utf-8>");
+    let deadline = arch::monotonic_nanos() + PRINT_TIMEOUT_NANOS;
+    // `format_args!` 的临时值只在当前语句内有效；这里不在处理函数中，可以等待控制台锁
+    while !write_record(
+        &Record::builder()
+            .args(format_args!("{text}"))
+            .level(level_from_u8(queued.level))
+            .file_static(queued.file)
+            .line(queued.line)
+            .build(),
+        &queued.ctx,
+    ) {
+        if arch::monotonic_nanos() >= deadline {
+            return false;
+        }
+        core::hint::spin_loop();
+    }
+    true
+}
+
+/// Returns the number of records waiting in the queue of `cpu`, an index
+/// returned by [`arch::cpu_index`].
+pub fn pending(cpu: usize) -> u64 {
+    let queue = &QUEUES[cpu];
+    queue.head.load(Ordering::Acquire) - queue.tail.load(Ordering::Acquire)
+}
+
+/// Returns the number of records dropped because a queue was full, per CPU.
+pub fn dropped() -> [u64; MAX_CPUS] {
+    core::array::from_fn(|cpu| QUEUES[cpu].dropped.load(Ordering::Relaxed))
+}
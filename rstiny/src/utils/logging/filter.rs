@@ -0,0 +1,123 @@
+//! env_logger-style log filter.
+//!
+//! A filter spec is a comma-separated list of directives:
+//!
+//! - `level` sets the default level, e.g. `info`;
+//! - `target=level` sets the level for a module path and its submodules,
+//!   e.g. `rstiny::vga=trace`;
+//! - `target` alone enables every level for that module path.
+//!
+//! When several directives match a record, the one with the longest target wins.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use core::str::FromStr;
+
+use log::LevelFilter;
+
+/// A directive of the filter spec that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidDirective(pub String);
+
+impl fmt::Display for InvalidDirective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid log directive `{}`", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Directive {
+    target: String,
+    level: LevelFilter,
+}
+
+impl Directive {
+    fn matches(&self, target: &str) -> bool {
+        match target.strip_prefix(self.target.as_str()) {
+            Some(rest) => rest.is_empty() || rest.starts_with("::"),
+            None => false,
+        }
+    }
+}
+
+/// Per-target log level filter.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    default: LevelFilter,
+    /// Sorted by descending target length, so the first match is the most specific.
+    directives: Vec<Directive>,
+}
+
+impl Filter {
+    /// Creates a filter applying `default` to every target.
+    pub const fn new(default: LevelFilter) -> Self {
+        Self {
+            default,
+            directives: Vec::new(),
+        }
+    }
+
+    /// Parses a filter spec such as `info,rstiny::vga=trace`.
+    pub fn parse(spec: &str) -> Result<Self, InvalidDirective> {
+        let mut filter = Self::new(LevelFilter::Off);
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let invalid = || InvalidDirective(directive.into());
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        return Err(invalid());
+                    }
+                    let level = LevelFilter::from_str(level.trim()).map_err(|_| invalid())?;
+                    filter.set_target_level(target, level);
+                }
+                None => match LevelFilter::from_str(directive) {
+                    Ok(level) => filter.default = level,
+                    Err(_) => filter.set_target_level(directive, LevelFilter::Trace),
+                },
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Sets the level of `target`, replacing any directive for the same target.
+    pub fn set_target_level(&mut self, target: &str, level: LevelFilter) {
+        self.directives.retain(|d| d.target != target);
+        let pos = self
+            .directives
+            .partition_point(|d| d.target.len() >= target.len());
+        self.directives.insert(
+            pos,
+            Directive {
+                target: target.into(),
+                level,
+            },
+        );
+    }
+
+    /// Returns the maximum level enabled for `target`.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .find(|d| d.matches(target))
+            .map_or(self.default, |d| d.level)
+    }
+
+    /// Returns the most verbose level enabled for any target.
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|d| d.level)
+            .fold(self.default, LevelFilter::max)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_ascii_lowercase())?;
+        for d in self.directives.iter().rev() {
+            write!(f, ",{}={}", d.target, d.level.as_str().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+//! Log line layouts.
+
+use core::fmt::{self, Display};
+
+use log::{Level, Record};
+
+use crate::cmdline::ParamValue;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorCode {
+    Red = 31,
+    Green = 32,
+    Yellow = 33,
+    Cyan = 36,
+    BrightBlack = 90,
+    BrightRed = 91,
+    BrightGreen = 92,
+    BrightYellow = 93,
+    BrightCyan = 96,
+}
+
+impl Display for ColorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\u{1B}[{}m", *self as u8)
+    }
+}
+
+const COLOR_RESET: &str = "\u{1B}[0m";
+
+/// Layout of a log line.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `[    1.234567 0 I] message`, colored.
+    Compact,
+    /// `[    1.234567 0:task INFO  file.rs:12] message`, colored.
+    Verbose,
+    /// Same as [`LogFormat::Verbose`] without ANSI escapes, for log capture.
+    NoColor,
+}
+
+impl LogFormat {
+    pub(super) fn from_u8(raw: u8) -> Self {
+        match raw {
+            0 => Self::Compact,
+            1 => Self::Verbose,
+            _ => Self::NoColor,
+        }
+    }
+}
+
+impl ParamValue for LogFormat {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        match value {
+            "compact" => Ok(Self::Compact),
+            "verbose" => Ok(Self::Verbose),
+            "nocolor" | "no-color" => Ok(Self::NoColor),
+            _ => Err("expected `compact`, `verbose` or `nocolor`"),
+        }
+    }
+}
+
+/// Context captured when a record is logged.
+#[derive(Debug, Clone, Copy)]
+pub struct RecordContext {
+    /// Time since boot in nanoseconds, from the generic timer.
+    pub nanos: u64,
+    pub cpu_id: usize,
+    pub task: Option<&'static str>,
+}
+
+/// Timestamp formatted as `seconds.microseconds`.
+struct Timestamp(u64);
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = self.0 / 1_000;
+        write!(f, "{:>5}.{:06}", micros / 1_000_000, micros % 1_000_000)
+    }
+}
+
+fn level_colors(level: Level) -> (ColorCode, ColorCode) {
+    match level {
+        Level::Error => (ColorCode::BrightRed, ColorCode::Red),
+        Level::Warn => (ColorCode::BrightYellow, ColorCode::Yellow),
+        Level::Info => (ColorCode::BrightGreen, ColorCode::Green),
+        Level::Debug => (ColorCode::BrightCyan, ColorCode::Cyan),
+        Level::Trace => (ColorCode::BrightBlack, ColorCode::BrightBlack),
+    }
+}
+
+/// A record displayed as one line (without the trailing newline).
+pub struct FormattedRecord<'a> {
+    record: &'a Record<'a>,
+    format: LogFormat,
+    ctx: &'a RecordContext,
+}
+
+impl<'a> FormattedRecord<'a> {
+    pub fn new(record: &'a Record<'a>, format: LogFormat, ctx: &'a RecordContext) -> Self {
+        Self {
+            record,
+            format,
+            ctx,
+        }
+    }
+}
+
+impl Display for FormattedRecord<'_> {
+    fn fmt(&self, w: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            record,
+            format,
+            ctx,
+        } = *self;
+        let level = record.level();
+        let args = record.args();
+        let ts = Timestamp(ctx.nanos);
+        let cpu = ctx.cpu_id;
+        let (level_color, args_color) = level_colors(level);
+
+        match format {
+            LogFormat::Compact => {
+                let letter = &level.as_str()[..1];
+                write!(
+                    w,
+                    "[{ts} {cpu} {level_color}{letter}{COLOR_RESET}] {args_color}{args}{COLOR_RESET}"
+                )
+            }
+            LogFormat::Verbose | LogFormat::NoColor => {
+                let file = record.file().unwrap_or("none");
+                let line = record.line().unwrap_or(0);
+                write!(w, "[{ts} {cpu}")?;
+                if let Some(task) = ctx.task {
+                    write!(w, ":{task}")?;
+                }
+                if format == LogFormat::NoColor {
+                    write!(w, " {level:<5} {file}:{line}] {args}")
+                } else {
+                    // 彩色输出格式：[时间 CPU:任务 级别 文件:行号] 消息
+                    write!(
+                        w,
+                        " {level_color}{level:<5}{COLOR_RESET} {file}:{line}] {args_color}{args}{COLOR_RESET}"
+                    )
+                }
+            }
+        }
+    }
+}
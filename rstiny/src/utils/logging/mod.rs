@@ -0,0 +1,147 @@
+pub mod binary;
+pub mod deferred;
+mod filter;
+mod format;
+pub mod ring;
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use spin::{Once, RwLock};
+
+use crate::console;
+use crate::utils::{arch, pstore};
+
+pub use filter::{Filter, InvalidDirective};
+pub use format::{ColorCode, FormattedRecord, LogFormat, RecordContext};
+
+pub struct SimpleLogger;
+
+static FILTER: RwLock<Filter> = RwLock::new(Filter::new(LevelFilter::Off));
+static FORMAT: AtomicU8 = AtomicU8::new(LogFormat::Verbose as u8);
+static TASK_NAME: Once<fn() -> Option<&'static str>> = Once::new();
+
+kparam! {
+    /// Log filter, e.g. `log=info,fdt=debug`, instead of `LOG` at build time.
+    "log" => pub static LOG_FILTER: Option<&'static str> = None;
+}
+
+kparam! {
+    /// Layout of log lines, e.g. `log_format=compact`.
+    "log_format" => pub static LOG_FORMAT: LogFormat = LogFormat::Verbose;
+}
+
+/// Installs the logger, with the filter spec given by `log=` on the command
+/// line or by `LOG` at build time.
+pub fn log_init() {
+    log::set_logger(&SimpleLogger).unwrap();
+    let spec = LOG_FILTER
+        .get()
+        .or(option_env!("LOG"))
+        .unwrap_or("off");
+    if let Err(err) = set_filter(spec) {
+        console_println!("log: {err}");
+    }
+    set_format(LOG_FORMAT.get());
+}
+
+/// Replaces the log filter with the one described by `spec`,
+/// e.g. `info,rstiny::vga=trace`.
+///
+/// The current filter is kept if `spec` is invalid.
+pub fn set_filter(spec: &str) -> Result<(), InvalidDirective> {
+    let filter = Filter::parse(spec)?;
+    log::set_max_level(filter.max_level());
+    *FILTER.write() = filter;
+    Ok(())
+}
+
+/// Sets the maximum level of a single target, keeping the other directives.
+pub fn set_target_level(target: &str, level: LevelFilter) {
+    let mut filter = FILTER.write();
+    filter.set_target_level(target, level);
+    log::set_max_level(filter.max_level());
+}
+
+/// Returns the current filter.
+pub fn filter() -> Filter {
+    FILTER.read().clone()
+}
+
+/// Selects the layout of log lines.
+pub fn set_format(format: LogFormat) {
+    FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+/// Returns the current layout of log lines.
+pub fn format() -> LogFormat {
+    LogFormat::from_u8(FORMAT.load(Ordering::Relaxed))
+}
+
+/// Registers the function returning the name of the running task, shown in
+/// verbose log lines. Only the first registration takes effect.
+pub fn set_task_name_fn(f: fn() -> Option<&'static str>) {
+    TASK_NAME.call_once(|| f);
+}
+
+/// Converts `Level as u8` back to a level, for records stored in raw memory.
+pub fn level_from_u8(raw: u8) -> Level {
+    match raw {
+        1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+fn record_context() -> RecordContext {
+    RecordContext {
+        nanos: arch::monotonic_nanos(),
+        cpu_id: arch::cpu_id(),
+        task: TASK_NAME.get().and_then(|f| f()),
+    }
+}
+
+/// Writes a record to the console, as text or, with the `binary-log`
+/// feature, as a binary frame. Returns `false` if the console lock is taken.
+fn write_record(record: &Record, ctx: &RecordContext) -> bool {
+    if cfg!(feature = "binary-log") {
+        binary::record_frame(record, ctx).try_send()
+    } else {
+        console::try_print(format_args!(
+            "{}\n",
+            FormattedRecord::new(record, format(), ctx)
+        ))
+    }
+}
+
+impl Log for SimpleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= FILTER.read().level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let ctx = record_context();
+        let seq = ring::push(record.level(), ctx.nanos, ctx.cpu_id, *record.args());
+        pstore::record_log(seq, record.level(), ctx.nanos, ctx.cpu_id, *record.args());
+
+        // 中断或异常上下文中不能等待控制台锁，先放入每 CPU 队列
+        if deferred::in_handler() {
+            deferred::push(record, &ctx);
+            return;
+        }
+        deferred::drain();
+        if !write_record(record, &ctx) {
+            deferred::push(record, &ctx);
+        }
+    }
+
+    fn flush(&self) {
+        deferred::drain();
+    }
+}
@@ -0,0 +1,235 @@
+//! In-memory kernel log ring buffer (`dmesg`).
+//!
+//! Every log record is stored in a fixed-size ring of slots, tagged with a
+//! monotonically increasing sequence number. Writers never block: a slot is
+//! claimed by bumping the global sequence counter and protected by a per-slot
+//! sequence lock, so readers can detect entries that were overwritten while
+//! they were copying them.
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Display, Write};
+use core::sync::atomic::{AtomicU64, Ordering, fence};
+
+use log::Level;
+
+use crate::utils::text::TruncatingWriter;
+
+/// Number of records kept in the ring.
+pub const RING_ENTRIES: usize = 512;
+/// Maximum length of a stored message; longer messages are truncated.
+pub const ENTRY_TEXT_LEN: usize = 160;
+
+/// A log record copied out of the ring.
+#[derive(Clone, Copy)]
+pub struct Entry {
+    pub seq: u64,
+    /// `Level as u8`, so that an empty ring lives in `.bss`.
+    level: u8,
+    pub nanos: u64,
+    pub cpu_id: usize,
+    len: usize,
+    text: [u8; ENTRY_TEXT_LEN],
+}
+
+impl Entry {
+    const EMPTY: Self = Self {
+        seq: 0,
+        level: 0,
+        nanos: 0,
+        cpu_id: 0,
+        len: 0,
+        text: [0; ENTRY_TEXT_LEN],
+    };
+
+    /// Returns the level of the record.
+    pub fn level(&self) -> Level {
+        super::level_from_u8(self.level)
+    }
+
+    /// Returns the message text.
+    pub fn text(&self) -> &str {
+        // 写入时只在字符边界截断，因此总是合法的 UTF-8
+        core::str::from_utf8(&self.text[..self.len]).unwrap_or("<invalid utf-8>")
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = self.nanos / 1_000;
+        write!(
+            f,
+            "[{:>5}.{:06} {}] {:<5} {}",
+            micros / 1_000_000,
+            micros % 1_000_000,
+            self.cpu_id,
+            self.level(),
+            self.text()
+        )
+    }
+}
+
+struct Slot {
+    /// `2 * seq + 1` while entry `seq` is being written, `2 * seq + 2` once it
+    /// is complete, 0 if the slot was never used.
+    stamp: AtomicU64,
+    entry: UnsafeCell<Entry>,
+}
+
+struct LogRing {
+    slots: [Slot; RING_ENTRIES],
+    /// Sequence number of the next record.
+    head: AtomicU64,
+}
+
+// SAFETY: 槽位内容由 `stamp` 序列锁保护，读者会丢弃被并发改写的副本
+unsafe impl Sync for LogRing {}
+
+static RING: LogRing = LogRing {
+    slots: [const {
+        Slot {
+            stamp: AtomicU64::new(0),
+            entry: UnsafeCell::new(Entry::EMPTY),
+        }
+    }; RING_ENTRIES],
+    head: AtomicU64::new(0),
+};
+
+/// Appends a record to the ring and returns its sequence number. Never blocks.
+pub fn push(level: Level, nanos: u64, cpu_id: usize, args: fmt::Arguments) -> u64 {
+    let seq = RING.head.fetch_add(1, Ordering::Relaxed);
+    let slot = &RING.slots[(seq % RING_ENTRIES as u64) as usize];
+    slot.stamp.store(2 * seq + 1, Ordering::Relaxed);
+    fence(Ordering::Release);
+
+    let entry = unsafe { &mut *slot.entry.get() };
+    let mut writer = TruncatingWriter::new(&mut entry.text);
+    let _ = writer.write_fmt(args);
+    entry.len = writer.written();
+    entry.seq = seq;
+    entry.level = level as u8;
+    entry.nanos = nanos;
+    entry.cpu_id = cpu_id;
+
+    slot.stamp.store(2 * seq + 2, Ordering::Release);
+    seq
+}
+
+/// Result of reading one sequence number.
+enum ReadResult {
+    Entry(Entry),
+    /// The entry was overwritten by a newer one.
+    Lost,
+    /// The entry does not exist yet or is still being written.
+    NotReady,
+}
+
+fn read(seq: u64) -> ReadResult {
+    let slot = &RING.slots[(seq % RING_ENTRIES as u64) as usize];
+    let expected = 2 * seq + 2;
+    let before = slot.stamp.load(Ordering::Acquire);
+    if before != expected {
+        return if before > expected {
+            ReadResult::Lost
+        } else {
+            ReadResult::NotReady
+        };
+    }
+    let entry = unsafe { core::ptr::read_volatile(slot.entry.get()) };
+    fence(Ordering::Acquire);
+    if slot.stamp.load(Ordering::Relaxed) != before {
+        return ReadResult::Lost;
+    }
+    ReadResult::Entry(entry)
+}
+
+/// Returns the sequence number the next record will get.
+pub fn head() -> u64 {
+    RING.head.load(Ordering::Acquire)
+}
+
+/// Returns the sequence number of the oldest record still in the ring.
+pub fn oldest() -> u64 {
+    head().saturating_sub(RING_ENTRIES as u64)
+}
+
+/// A reading position in the ring.
+///
+/// Records overwritten before the cursor reached them are skipped and counted
+/// in [`Cursor::lost`].
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor {
+    next: u64,
+    lost: u64,
+}
+
+impl Cursor {
+    /// Creates a cursor starting at sequence number `seq`.
+    pub const fn at(seq: u64) -> Self {
+        Self { next: seq, lost: 0 }
+    }
+
+    /// Creates a cursor at the oldest record still in the ring.
+    pub fn from_oldest() -> Self {
+        Self::at(oldest())
+    }
+
+    /// Creates a cursor positioned `n` records before the newest one.
+    pub fn from_tail(n: usize) -> Self {
+        Self::at(head().saturating_sub(n as u64).max(oldest()))
+    }
+
+    /// Returns the sequence number of the next record to read.
+    pub fn position(&self) -> u64 {
+        self.next
+    }
+
+    /// Returns how many records were skipped because they were overwritten.
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+}
+
+impl Iterator for Cursor {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        loop {
+            if self.next >= head() {
+                return None;
+            }
+            match read(self.next) {
+                ReadResult::Entry(entry) => {
+                    self.next += 1;
+                    return Some(entry);
+                }
+                ReadResult::Lost => {
+                    let oldest = oldest().max(self.next + 1);
+                    self.lost += oldest - self.next;
+                    self.next = oldest;
+                }
+                ReadResult::NotReady => return None,
+            }
+        }
+    }
+}
+
+/// Prints the records from `cursor` on to the console. Used by the `dmesg`
+/// shell command.
+pub fn dump(mut cursor: Cursor) {
+    for entry in &mut cursor {
+        console_println!("{entry}");
+    }
+    if cursor.lost() > 0 {
+        console_println!("[dmesg: {} records lost while reading]", cursor.lost());
+    }
+}
+
+/// Prints the last `n` records without taking the console lock. Used by the
+/// panic handler.
+pub fn dump_emergency(n: usize) {
+    crate::console::emergency_print(format_args!("--- last {n} log records ---\n"));
+    for entry in Cursor::from_tail(n) {
+        crate::console::emergency_print(format_args!("{entry}\n"));
+    }
+    crate::console::emergency_print(format_args!("--- end of log ---\n"));
+}
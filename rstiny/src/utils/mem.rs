@@ -1,3 +1,10 @@
+use crate::config::{LINEAR_MAP_LIMIT, PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
+use crate::utils::pstore;
+use crate::{acpi, fdt, vga};
+
+/// Maximum number of free RAM regions tracked.
+pub const MAX_RAM_REGIONS: usize = 32;
+
 unsafe extern "C" {
     fn _sbss();
     fn _ebss();
@@ -18,3 +25,205 @@ pub fn clear_bss() {
         .fill(0);
     }
 }
+
+/// Converts a physical address to a virtual address in the linear mapping.
+pub const fn phys_to_virt(paddr: usize) -> usize {
+    paddr + PHYS_VIRT_OFFSET
+}
+
+/// Converts a virtual address in the linear mapping to a physical address.
+pub const fn virt_to_phys(vaddr: usize) -> usize {
+    vaddr - PHYS_VIRT_OFFSET
+}
+
+/// A physical memory range `[start, end)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemRegion {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl MemRegion {
+    pub const EMPTY: Self = Self { start: 0, end: 0 };
+
+    pub const fn new(start: usize, size: usize) -> Self {
+        Self {
+            start,
+            end: start.saturating_add(size),
+        }
+    }
+
+    pub const fn size(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+}
+
+/// A fixed-capacity set of disjoint physical memory regions, usable before
+/// the heap is ready.
+pub struct MemRegionList<const N: usize> {
+    regions: [MemRegion; N],
+    len: usize,
+}
+
+impl<const N: usize> MemRegionList<N> {
+    pub const fn new() -> Self {
+        Self {
+            regions: [MemRegion::EMPTY; N],
+            len: 0,
+        }
+    }
+
+    /// Adds a region. Returns `false` if the list is full.
+    pub fn add(&mut self, region: MemRegion) -> bool {
+        if region.is_empty() {
+            return true;
+        }
+        if self.len == N {
+            return false;
+        }
+        self.regions[self.len] = region;
+        self.len += 1;
+        true
+    }
+
+    /// Removes `hole` from every region, splitting regions when needed.
+    /// Returns `false` if a split was dropped because the list is full.
+    pub fn remove(&mut self, hole: MemRegion) -> bool {
+        let mut complete = true;
+        let mut i = 0;
+        while i < self.len {
+            let region = self.regions[i];
+            if hole.is_empty() || hole.end <= region.start || hole.start >= region.end {
+                i += 1;
+                continue;
+            }
+            let below = MemRegion {
+                start: region.start,
+                end: hole.start.max(region.start),
+            };
+            let above = MemRegion {
+                start: hole.end.min(region.end),
+                end: region.end,
+            };
+            // 用后面的区域覆盖当前区域，再把两段剩余部分加回来
+            self.len -= 1;
+            self.regions[i] = self.regions[self.len];
+            for part in [below, above] {
+                if !part.is_empty() {
+                    complete &= self.add(part);
+                }
+            }
+        }
+        complete
+    }
+
+    /// Keeps only the parts of the regions inside `window`.
+    pub fn clip(&mut self, window: MemRegion) {
+        self.remove(MemRegion {
+            start: 0,
+            end: window.start,
+        });
+        self.remove(MemRegion {
+            start: window.end,
+            end: usize::MAX,
+        });
+    }
+
+    /// Moves the lowest `size` bytes out of the list, splitting a region if
+    /// needed, and returns them.
+    pub fn take_front(&mut self, size: usize) -> Self {
+        self.regions[..self.len].sort_unstable_by_key(|region| region.start);
+        let mut taken = Self::new();
+        let mut remaining = size;
+        let mut i = 0;
+        while i < self.len && remaining > 0 {
+            let region = &mut self.regions[i];
+            let part = MemRegion::new(region.start, region.size().min(remaining));
+            taken.add(part);
+            remaining -= part.size();
+            region.start = part.end;
+            i += 1;
+        }
+        // 去掉已被完全取走的区域
+        let used = self.regions[..i]
+            .iter()
+            .filter(|region| region.is_empty())
+            .count();
+        self.regions.copy_within(used..self.len, 0);
+        self.len -= used;
+        taken
+    }
+
+    /// Returns the total size of the regions.
+    pub fn total_size(&self) -> usize {
+        self.iter().map(MemRegion::size).sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MemRegion> {
+        self.regions[..self.len].iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Computes the free RAM regions: the `/memory` nodes of the device tree (or
+/// the configured RAM if there is none), minus the kernel image, reserved
+/// memory, the device tree itself, the ACPI and UEFI tables, the framebuffer
+/// and the persistent store.
+pub fn free_ram_regions() -> MemRegionList<MAX_RAM_REGIONS> {
+    unsafe extern "C" {
+        fn _skernel();
+        fn _ekernel();
+    }
+
+    let mut regions = MemRegionList::new();
+    match fdt::get() {
+        Some(fdt) if fdt.memory_regions().next().is_some() => {
+            for (start, size) in fdt.memory_regions() {
+                regions.add(MemRegion::new(start as usize, size as usize));
+            }
+        }
+        _ => {
+            regions.add(MemRegion::new(PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE));
+        }
+    }
+
+    let kernel_start = virt_to_phys(_skernel as *const () as usize);
+    let kernel_end = virt_to_phys(_ekernel as *const () as usize);
+    regions.remove(MemRegion {
+        start: kernel_start,
+        end: kernel_end,
+    });
+    if let Some(fdt) = fdt::get() {
+        for (start, size) in fdt.reserved_regions() {
+            regions.remove(MemRegion::new(start as usize, size as usize));
+        }
+        let blob = virt_to_phys(fdt.as_ptr() as usize);
+        regions.remove(MemRegion::new(blob, fdt.total_size()));
+    }
+    for &region in acpi::table_regions() {
+        regions.remove(region);
+    }
+    if let Some((fb_paddr, fb_size)) = vga::framebuffer_region() {
+        regions.remove(MemRegion::new(fb_paddr, fb_size));
+    }
+    if let Some((paddr, size)) = pstore::region() {
+        regions.remove(MemRegion::new(paddr, size));
+    }
+    // 只有线性映射覆盖的内存才能直接访问
+    regions.clip(MemRegion {
+        start: 0,
+        end: LINEAR_MAP_LIMIT,
+    });
+    regions
+}
@@ -1,3 +1,11 @@
+pub mod arch;
+#[cfg(feature = "coverage")]
+pub mod coverage;
+pub mod frame_allocator;
 pub mod heap_allocator;
 pub mod logging;
 pub mod mem;
+pub mod pstore;
+#[cfg(feature = "ktest")]
+pub mod semihosting;
+pub mod text;
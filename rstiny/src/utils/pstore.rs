@@ -0,0 +1,306 @@
+//! Persistent crash log kept in reserved RAM across warm reboots.
+//!
+//! The region is taken from a `/reserved-memory` node compatible with
+//! `ramoops` in the device tree, or from [`PSTORE_PADDR`]/[`PSTORE_SIZE`].
+//! It holds:
+//!
+//! - the message of the last panic;
+//! - a copy of the most recent log records, one checksummed slot per record,
+//!   written as they are logged so the tail survives a hang as well.
+//!
+//! Every write is cleaned to the point of coherency, so the data is in DRAM
+//! when the board is reset. On the next boot, [`init`] collects the valid
+//! records, prints them, keeps them available through [`last_crash`] and
+//! clears the region.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use lazyinit::LazyInit;
+use log::Level;
+
+use crate::config::{PSTORE_PADDR, PSTORE_SIZE};
+use crate::fdt;
+use crate::utils::{arch, logging};
+use crate::utils::mem::phys_to_virt;
+use crate::utils::text::TruncatingWriter;
+
+const PSTORE_MAGIC: u32 = 0x5253_5053; // "RSPS"
+const PSTORE_VERSION: u32 = 1;
+
+const PANIC_TEXT_LEN: usize = 1024;
+const SLOT_TEXT_LEN: usize = 200;
+
+#[repr(C)]
+struct Header {
+    magic: u32,
+    version: u32,
+    /// Number of log slots following the panic record.
+    slots: u32,
+    /// Checksum of the three fields above.
+    checksum: u32,
+}
+
+#[repr(C)]
+struct PanicRecord {
+    magic: u32,
+    checksum: u32,
+    nanos: u64,
+    cpu_id: u32,
+    len: u32,
+    text: [u8; PANIC_TEXT_LEN],
+}
+
+#[repr(C)]
+struct LogSlot {
+    magic: u32,
+    checksum: u32,
+    seq: u64,
+    nanos: u64,
+    cpu_id: u16,
+    level: u8,
+    _reserved: u8,
+    len: u32,
+    text: [u8; SLOT_TEXT_LEN],
+}
+
+/// CRC-32 (IEEE 802.3), bitwise; records are small and written rarely enough.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Returns the bytes of `value` following its first two `u32` fields
+/// (magic and checksum), which are excluded from the checksum.
+fn payload<T>(value: &T) -> &[u8] {
+    let bytes = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>())
+    };
+    &bytes[8..]
+}
+
+fn header_checksum(header: &Header) -> u32 {
+    let mut bytes = [0u8; 12];
+    bytes[..4].copy_from_slice(&header.magic.to_le_bytes());
+    bytes[4..8].copy_from_slice(&header.version.to_le_bytes());
+    bytes[8..].copy_from_slice(&header.slots.to_le_bytes());
+    crc32(&bytes)
+}
+
+fn text(bytes: &[u8], len: u32) -> String {
+    String::from_utf8_lossy(&bytes[..(len as usize).min(bytes.len())]).into_owned()
+}
+
+/// A log record recovered from the previous boot.
+#[derive(Debug, Clone)]
+pub struct StoredRecord {
+    pub seq: u64,
+    pub nanos: u64,
+    pub cpu_id: usize,
+    pub level: Level,
+    pub text: String,
+}
+
+impl fmt::Display for StoredRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = self.nanos / 1_000;
+        write!(
+            f,
+            "[{:>5}.{:06} {}] {:<5} {}",
+            micros / 1_000_000,
+            micros % 1_000_000,
+            self.cpu_id,
+            self.level,
+            self.text
+        )
+    }
+}
+
+/// What was left in the persistent store by the previous boot.
+#[derive(Debug, Clone)]
+pub struct CrashRecord {
+    /// The panic message, if the previous boot panicked.
+    pub panic: Option<String>,
+    /// The tail of the log, oldest first.
+    pub log: Vec<StoredRecord>,
+}
+
+/// Location of the persistent store in the linear mapping.
+struct Region {
+    header: *mut Header,
+    panic: *mut PanicRecord,
+    slots: *mut LogSlot,
+    slot_count: usize,
+}
+
+// SAFETY: 区域位于保留内存中，只通过本模块访问
+unsafe impl Send for Region {}
+unsafe impl Sync for Region {}
+
+impl Region {
+    fn new(paddr: usize, size: usize) -> Option<Self> {
+        let fixed = size_of::<Header>() + size_of::<PanicRecord>();
+        let slot_count = size.checked_sub(fixed)? / size_of::<LogSlot>();
+        if slot_count == 0 {
+            return None;
+        }
+        let base = phys_to_virt(paddr);
+        Some(Self {
+            header: base as *mut Header,
+            panic: (base + size_of::<Header>()) as *mut PanicRecord,
+            slots: (base + fixed) as *mut LogSlot,
+            slot_count,
+        })
+    }
+
+    fn slot(&self, index: usize) -> *mut LogSlot {
+        unsafe { self.slots.add(index) }
+    }
+
+    fn byte_len(&self) -> usize {
+        size_of::<Header>() + size_of::<PanicRecord>() + self.slot_count * size_of::<LogSlot>()
+    }
+
+    fn is_formatted(&self) -> bool {
+        let header = unsafe { &*self.header };
+        header.magic == PSTORE_MAGIC
+            && header.version == PSTORE_VERSION
+            && header.slots as usize == self.slot_count
+            && header.checksum == header_checksum(header)
+    }
+
+    fn recover(&self) -> Option<CrashRecord> {
+        if !self.is_formatted() {
+            return None;
+        }
+        let panic = unsafe { &*self.panic };
+        let panic = (panic.magic == PSTORE_MAGIC && panic.checksum == crc32(payload(panic)))
+            .then(|| text(&panic.text, panic.len));
+
+        let mut log: Vec<StoredRecord> = (0..self.slot_count)
+            .map(|i| unsafe { &*self.slot(i) })
+            .filter(|slot| slot.magic == PSTORE_MAGIC && slot.checksum == crc32(payload(*slot)))
+            .map(|slot| StoredRecord {
+                seq: slot.seq,
+                nanos: slot.nanos,
+                cpu_id: slot.cpu_id as usize,
+                level: logging::level_from_u8(slot.level),
+                text: text(&slot.text, slot.len),
+            })
+            .collect();
+        log.sort_unstable_by_key(|record| record.seq);
+
+        (panic.is_some() || !log.is_empty()).then_some(CrashRecord { panic, log })
+    }
+
+    /// Erases the region and writes a fresh header.
+    fn format(&self) {
+        unsafe {
+            core::ptr::write_bytes(self.header as *mut u8, 0, self.byte_len());
+            let header = &mut *self.header;
+            header.magic = PSTORE_MAGIC;
+            header.version = PSTORE_VERSION;
+            header.slots = self.slot_count as u32;
+            header.checksum = header_checksum(header);
+        }
+        arch::clean_dcache_range(self.header as usize, self.byte_len());
+    }
+}
+
+static REGION: LazyInit<Region> = LazyInit::new();
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+static LAST_CRASH: LazyInit<Option<CrashRecord>> = LazyInit::new();
+
+/// Returns the physical range of the persistent store: a `ramoops`
+/// reserved-memory node, or the configured region.
+pub fn region() -> Option<(usize, usize)> {
+    let from_fdt = fdt::get()
+        .and_then(|fdt| fdt.find_node("/reserved-memory"))
+        .and_then(|reserved| {
+            reserved
+                .children()
+                .find(|node| node.is_compatible("ramoops"))
+        })
+        .and_then(|node| node.reg().next())
+        .map(|(paddr, size)| (paddr as usize, size as usize));
+    from_fdt.or(PSTORE_SIZE.map(|size| (PSTORE_PADDR, size)))
+}
+
+/// Recovers the records left by the previous boot, prints them, then clears
+/// the store and starts recording for this boot.
+pub fn init() {
+    let Some(region) = region().and_then(|(paddr, size)| Region::new(paddr, size)) else {
+        LAST_CRASH.init_once(None);
+        return;
+    };
+
+    let crash = region.recover();
+    if let Some(crash) = &crash {
+        console_println!("=== pstore: records from the previous boot ===");
+        if let Some(panic) = &crash.panic {
+            console_println!("{panic}");
+        }
+        for record in &crash.log {
+            console_println!("{record}");
+        }
+        console_println!("=== pstore: end ===");
+    }
+    LAST_CRASH.init_once(crash);
+
+    region.format();
+    REGION.init_once(region);
+    ACTIVE.store(true, Ordering::Release);
+}
+
+/// Returns what the previous boot left in the persistent store.
+pub fn last_crash() -> Option<&'static CrashRecord> {
+    LAST_CRASH.get()?.as_ref()
+}
+
+/// Copies a log record into the persistent store.
+pub fn record_log(seq: u64, level: Level, nanos: u64, cpu_id: usize, args: fmt::Arguments) {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return;
+    }
+    let region = &*REGION;
+    let index = NEXT_SLOT.fetch_add(1, Ordering::Relaxed) % region.slot_count;
+    let slot = unsafe { &mut *region.slot(index) };
+
+    // 先使槽位失效，写完后再恢复 magic，避免复位时留下半条记录
+    slot.magic = 0;
+    let mut writer = TruncatingWriter::new(&mut slot.text);
+    let _ = writer.write_fmt(args);
+    slot.len = writer.written() as u32;
+    slot.seq = seq;
+    slot.nanos = nanos;
+    slot.cpu_id = cpu_id as u16;
+    slot.level = level as u8;
+    slot.checksum = crc32(payload(&*slot));
+    slot.magic = PSTORE_MAGIC;
+    arch::clean_dcache_range(slot as *mut LogSlot as usize, size_of::<LogSlot>());
+}
+
+/// Stores the panic message. Called from the panic handler.
+pub fn record_panic(info: &core::panic::PanicInfo) {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return;
+    }
+    let panic = unsafe { &mut *REGION.panic };
+    panic.magic = 0;
+    let mut writer = TruncatingWriter::new(&mut panic.text);
+    let _ = write!(writer, "{info}");
+    panic.len = writer.written() as u32;
+    panic.nanos = arch::monotonic_nanos();
+    panic.cpu_id = arch::cpu_id() as u32;
+    panic.checksum = crc32(payload(&*panic));
+    panic.magic = PSTORE_MAGIC;
+    arch::clean_dcache_range(panic as *mut PanicRecord as usize, size_of::<PanicRecord>());
+}
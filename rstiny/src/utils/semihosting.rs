@@ -0,0 +1,29 @@
+//! ARM semihosting calls, served by an attached debugger or by QEMU started
+//! with `-semihosting`.
+//!
+//! Without a semihosting host the `hlt` instruction raises an exception, so
+//! these calls must only be made when one is known to be present.
+
+use core::arch::asm;
+
+/// `SYS_EXIT` operation number.
+const SYS_EXIT: usize = 0x18;
+/// `ADP_Stopped_ApplicationExit` reason code of `SYS_EXIT`.
+const ADP_STOPPED_APPLICATION_EXIT: usize = 0x20026;
+
+/// Issues semihosting operation `op` with parameter `arg`.
+unsafe fn call(op: usize, arg: usize) -> usize {
+    let ret;
+    unsafe { asm!("hlt #0xf000", inout("x0") op => ret, in("x1") arg, options(nostack)) };
+    ret
+}
+
+/// Terminates the emulator with exit status `code`.
+pub fn exit(code: u32) -> ! {
+    // AArch64 的 SYS_EXIT 参数是 (原因, 退出码) 参数块的地址
+    let block = [ADP_STOPPED_APPLICATION_EXIT, code as usize];
+    unsafe { call(SYS_EXIT, block.as_ptr() as usize) };
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,35 @@
+//! Formatting into fixed-size buffers.
+
+use core::fmt::{self, Write};
+
+/// `fmt::Write` into a fixed buffer, silently truncating at a char boundary,
+/// so the written bytes are always valid UTF-8. Never allocates, so it can be
+/// used from the logger, the allocator and the panic handler.
+pub struct TruncatingWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> TruncatingWriter<'a> {
+    /// Starts writing at the beginning of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    /// Returns the number of bytes written.
+    pub fn written(&self) -> usize {
+        self.len
+    }
+}
+
+impl Write for TruncatingWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut n = s.len().min(self.buf.len() - self.len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-// 从启动日志获取的帧缓冲信息
+// 从启动日志获取的帧缓冲信息，可以用 `fb=` 参数覆盖
 const FB_ADDR: usize = 0xecd20000;
 const FB_WIDTH: usize = 1920;
 const FB_HEIGHT: usize = 1200;
@@ -9,6 +9,9 @@ use core::ptr::NonNull;
 
 use font8x8::{UnicodeFonts, BASIC_FONTS};
 
+use crate::cmdline::ParamValue;
+use crate::utils::mem::phys_to_virt;
+
 // 颜色定义 (根据 Linux 日志: shift=24:16:8:0，格式为 0xAARRGGBB)
 // Alpha在最高字节(24-31位), Red(16-23位), Green(8-15位), Blue(0-7位)
 const COLOR_BLACK: u32 = 0x00000000;
@@ -20,6 +23,70 @@ const COLOR_YELLOW: u32 = 0x00FFFF00;
 const COLOR_CYAN: u32 = 0x0000FFFF;
 const COLOR_MAGENTA: u32 = 0x00FF00FF;
 
+/// 帧缓冲的位置和尺寸
+#[derive(Debug, Clone, Copy)]
+pub struct FbInfo {
+    pub paddr: usize,
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize, // 每行字节数
+}
+
+/// `fb=off` 或 `fb=<物理地址>,<宽>x<高>[,<每行字节数>]`，省略时每行字节数为 宽 * 4
+#[derive(Debug, Clone, Copy)]
+pub struct FbParam(pub Option<FbInfo>);
+
+impl ParamValue for FbParam {
+    fn parse(value: &'static str) -> Result<Self, &'static str> {
+        if value == "off" {
+            return Ok(Self(None));
+        }
+        let mut fields = value.split(',');
+        let (Some(paddr), Some(size)) = (fields.next(), fields.next()) else {
+            return Err("expected `off` or `<paddr>,<width>x<height>[,<stride>]`");
+        };
+        let (width, height) = size.split_once('x').ok_or("expected `<width>x<height>`")?;
+        let paddr = usize::parse(paddr)?;
+        let width = usize::parse(width)?;
+        let height = usize::parse(height)?;
+        let row = width
+            .checked_mul(BYTES_PER_PIXEL)
+            .ok_or("framebuffer width too large")?;
+        let stride = match fields.next() {
+            Some(stride) => usize::parse(stride)?,
+            None => row,
+        };
+        if fields.next().is_some() {
+            return Err("unexpected field after the stride");
+        }
+        if width == 0 || height == 0 || stride < row {
+            return Err("invalid framebuffer geometry");
+        }
+        // 帧缓冲的大小和结束地址都不能溢出
+        stride
+            .checked_mul(height)
+            .and_then(|size| paddr.checked_add(size))
+            .ok_or("framebuffer too large")?;
+        Ok(Self(Some(FbInfo { paddr, width, height, stride })))
+    }
+}
+
+kparam! {
+    /// Framebuffer location and geometry, or `off` without a display.
+    "fb" => pub static FB: FbParam = FbParam(Some(FbInfo {
+        paddr: FB_ADDR,
+        width: FB_WIDTH,
+        height: FB_HEIGHT,
+        stride: FB_STRIDE,
+    }));
+}
+
+/// 帧缓冲占用的物理内存范围 (起始地址, 字节数)，`fb=off` 时为 None
+pub fn framebuffer_region() -> Option<(usize, usize)> {
+    // 解析 `fb=` 时已检查乘法不会溢出
+    FB.get().0.map(|info| (info.paddr, info.stride * info.height))
+}
+
 pub struct FrameBuffer {
     base: *mut u32,
     width:  usize,
@@ -38,12 +105,12 @@ unsafe impl Send for FrameBuffer {}
 unsafe impl Sync for FrameBuffer {}
 
 impl FrameBuffer {
-    pub fn new(base_addr: NonNull<usize>) -> Self {
+    pub fn new(base_addr: NonNull<usize>, info: &FbInfo) -> Self {
         Self {
             base: base_addr.as_ptr() as *mut u32,
-            width: FB_WIDTH,
-            height: FB_HEIGHT,
-            stride_pixels: FB_STRIDE / BYTES_PER_PIXEL,
+            width: info.width,
+            height: info.height,
+            stride_pixels: info.stride / BYTES_PER_PIXEL,
             cursor_x: 0,
             cursor_y: 0,
             char_width: 8,
@@ -183,13 +250,13 @@ use lazyinit::LazyInit;
 
 static FRAMEBUFFER: LazyInit<SpinNoIrq<FrameBuffer>> = LazyInit::new();
 
-/// 初始化全局 Framebuffer（在 main 函数中调用一次）
+/// 初始化全局 Framebuffer（在 main 函数中调用一次），`fb=off` 时不初始化
 pub fn init() {
-    use core::ptr::NonNull;
-    
-    const FB_PADDR: usize = 0xecd20000;
-    let base_addr = NonNull::new(FB_PADDR as *mut usize).expect("Invalid framebuffer address");
-    let fb = FrameBuffer::new(base_addr);
+    let Some(info) = FB.get().0 else {
+        return;
+    };
+    let base_addr = NonNull::new(phys_to_virt(info.paddr) as *mut usize).expect("Invalid framebuffer address");
+    let fb = FrameBuffer::new(base_addr, &info);
     FRAMEBUFFER.init_once(SpinNoIrq::new(fb));
 }
 
@@ -247,10 +314,13 @@ pub fn show_text() -> ! {
     }
 }
 
-pub fn show_img() -> ! {
-   const FB_PADDR: usize = 0xffff0000ecd20000;
-    let base_addr = NonNull::new(FB_PADDR as *mut usize).expect("Invalid framebuffer address");
-    let mut fb = FrameBuffer::new(base_addr);
+pub fn show_img() {
+    // 没有帧缓冲时直接返回
+    let Some(info) = FB.get().0 else {
+        return;
+    };
+    let base_addr = NonNull::new(phys_to_virt(info.paddr) as *mut usize).expect("Invalid framebuffer address");
+    let mut fb = FrameBuffer::new(base_addr, &info);
     
     // 清屏为黑色
     fb.clear(COLOR_BLACK);
@@ -314,8 +384,4 @@ pub fn show_img() -> ! {
             }
         }
     }
-    
-    loop {
-        core::hint::spin_loop();
-    }
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+//! Parsing of the coverage dump printed by `rstiny/src/utils/coverage.rs`.
+
+/// Prefix of the marker lines around a dump.
+const MARKER: &str = "#### kcov";
+
+/// Contents of the counter and MC/DC bitmap sections at the end of the run.
+pub struct Dump {
+    pub counters: Vec<u8>,
+    pub bitmap: Vec<u8>,
+}
+
+/// Returns the value of `key=` among the fields of a marker line.
+fn field<'a>(fields: &'a str, key: &str) -> Result<&'a str, String> {
+    fields
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))
+        .ok_or_else(|| format!("no `{key}` in `{MARKER} {}`", fields.trim()))
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parses the last complete dump in `log`. Lines of other output between the
+/// markers are skipped.
+pub fn parse(log: &str) -> Result<Dump, String> {
+    let begin_marker = format!("{MARKER} begin ");
+    let start = log
+        .rfind(&begin_marker)
+        .ok_or("no coverage dump; was rstiny built with `make COVERAGE=y`?")?;
+    let mut lines = log[start + begin_marker.len()..].lines();
+    let begin = lines.next().unwrap_or_default();
+    let size = |key| {
+        field(begin, key)?
+            .parse::<usize>()
+            .map_err(|_| format!("invalid `{key}` in the begin marker"))
+    };
+    let mut dump = Dump {
+        counters: vec![0; size("counters")?],
+        bitmap: vec![0; size("bitmap")?],
+    };
+
+    let mut data_lines = 0;
+    let end = loop {
+        let line = lines.next().ok_or("the coverage dump is cut off")?;
+        if let Some(end) = line.strip_prefix(&format!("{MARKER} end ")) {
+            break end;
+        }
+        let mut parts = line.split_whitespace();
+        let section = match parts.next() {
+            Some("c") => &mut dump.counters,
+            Some("b") => &mut dump.bitmap,
+            _ => continue,
+        };
+        let (Some(offset), Some(hex), None) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+        let (Ok(offset), Some(bytes)) = (usize::from_str_radix(offset, 16), parse_hex(hex)) else {
+            continue;
+        };
+        section
+            .get_mut(offset..offset + bytes.len())
+            .ok_or_else(|| format!("dump line out of bounds: `{line}`"))?
+            .copy_from_slice(&bytes);
+        data_lines += 1;
+    };
+
+    let expected_lines: usize = field(end, "lines")?
+        .parse()
+        .map_err(|_| "invalid `lines` in the end marker")?;
+    let expected_crc = u32::from_str_radix(field(end, "crc32")?, 16)
+        .map_err(|_| "invalid `crc32` in the end marker")?;
+    if data_lines != expected_lines {
+        return Err(format!(
+            "the dump has {data_lines} data lines, the end marker {expected_lines}"
+        ));
+    }
+    let mut crc = Crc32::new();
+    crc.update(&dump.counters);
+    crc.update(&dump.bitmap);
+    if crc.finish() != expected_crc {
+        return Err("CRC mismatch; the dump was corrupted on the serial line".into());
+    }
+    Ok(dump)
+}
+
+/// CRC-32 (IEEE 802.3), bitwise, as computed on the target.
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Self {
+        Self(!0)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.0 ^= byte as u32;
+            for _ in 0..8 {
+                self.0 = (self.0 >> 1) ^ (0xedb8_8320 & (self.0 & 1).wrapping_neg());
+            }
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.0
+    }
+}
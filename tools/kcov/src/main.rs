@@ -0,0 +1,205 @@
+//! Host-side converter for rstiny coverage dumps.
+//!
+//! Usage: `kcov [options] <rstiny-elf> [<serial-log>]`
+//!
+//! Reads the serial output of a `make COVERAGE=y` kernel (a capture file, or
+//! stdin if omitted), extracts the counter dump printed at the end of the
+//! test run (see `rstiny/src/utils/coverage.rs`) and writes a `.profraw`
+//! file. With `--lcov`, also merges it with `rust-profdata` and exports an
+//! lcov report with `rust-cov` (from `cargo install cargo-binutils`),
+//! restricted by default to the vga, logging and allocator code, and prints
+//! the line coverage of each file.
+
+mod dump;
+mod profraw;
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+const USAGE: &str = "\
+usage: kcov [options] <rstiny-elf> [<serial-log>]
+
+options:
+  -o <file>           .profraw output (default rstiny.profraw)
+  --lcov <file>       also write an lcov report
+  --all               report every source file, not only the default ones
+  --profdata <path>   llvm-profdata binary (default rust-profdata)
+  --cov <path>        llvm-cov binary (default rust-cov)";
+
+/// Sources reported by default, relative to the repository root.
+const DEFAULT_SOURCES: &[&str] = &[
+    "rstiny/src/vga.rs",
+    "rstiny/src/utils/logging",
+    "rstiny/src/utils/heap_allocator",
+    "rstiny/src/utils/frame_allocator.rs",
+];
+
+struct Options {
+    elf: PathBuf,
+    log: Option<PathBuf>,
+    profraw: PathBuf,
+    lcov: Option<PathBuf>,
+    all_sources: bool,
+    profdata_tool: String,
+    cov_tool: String,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut profraw = PathBuf::from("rstiny.profraw");
+    let mut lcov = None;
+    let mut all_sources = false;
+    let mut profdata_tool = String::from("rust-profdata");
+    let mut cov_tool = String::from("rust-cov");
+    let mut paths = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{name} needs a value"));
+        match arg.as_str() {
+            "-o" => profraw = value("-o")?.into(),
+            "--lcov" => lcov = Some(value("--lcov")?.into()),
+            "--all" => all_sources = true,
+            "--profdata" => profdata_tool = value("--profdata")?,
+            "--cov" => cov_tool = value("--cov")?,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let mut paths = paths.into_iter();
+    let (Some(elf), log, None) = (paths.next(), paths.next(), paths.next()) else {
+        return Err("expected an ELF and at most one log".into());
+    };
+    Ok(Options {
+        elf,
+        log,
+        profraw,
+        lcov,
+        all_sources,
+        profdata_tool,
+        cov_tool,
+    })
+}
+
+/// Root of the repository, which the default sources are relative to.
+fn repo_root() -> PathBuf {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    root.canonicalize().unwrap_or(root)
+}
+
+fn run_tool(command: &mut Command) -> Result<Vec<u8>, String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|e| format!("cannot run {program}: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{program} failed ({}):\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Prints the line coverage of each file of an lcov report.
+fn print_summary(lcov: &str) {
+    let root = repo_root();
+    let (mut file, mut found, mut hit) = (String::new(), 0, 0);
+    let (mut total_found, mut total_hit) = (0u64, 0u64);
+    let percent = |hit: u64, found: u64| {
+        if found == 0 {
+            100.0
+        } else {
+            hit as f64 * 100.0 / found as f64
+        }
+    };
+    for line in lcov.lines() {
+        if let Some(path) = line.strip_prefix("SF:") {
+            file = Path::new(path)
+                .strip_prefix(&root)
+                .map_or(path.into(), |p| p.display().to_string());
+        } else if let Some(n) = line.strip_prefix("LF:") {
+            found = n.parse().unwrap_or(0);
+        } else if let Some(n) = line.strip_prefix("LH:") {
+            hit = n.parse().unwrap_or(0);
+        } else if line == "end_of_record" {
+            println!("{:>6.1}% {hit:>5}/{found:<5} {file}", percent(hit, found));
+            total_found += found;
+            total_hit += hit;
+        }
+    }
+    println!(
+        "{:>6.1}% {total_hit:>5}/{total_found:<5} total",
+        percent(total_hit, total_found)
+    );
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let elf = std::fs::read(&options.elf)
+        .map_err(|e| format!("cannot read {}: {e}", options.elf.display()))?;
+    let log = match &options.log {
+        Some(path) => {
+            std::fs::read(path).map_err(|e| format!("cannot read {}: {e}", path.display()))?
+        }
+        None => {
+            let mut log = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut log)
+                .map_err(|e| format!("cannot read stdin: {e}"))?;
+            log
+        }
+    };
+    let dump = dump::parse(&String::from_utf8_lossy(&log))?;
+    let profraw = profraw::build(&elf, &dump)?;
+    std::fs::write(&options.profraw, profraw)
+        .map_err(|e| format!("cannot write {}: {e}", options.profraw.display()))?;
+    println!("wrote {}", options.profraw.display());
+
+    let Some(lcov_path) = &options.lcov else {
+        return Ok(());
+    };
+    let profdata = options.profraw.with_extension("profdata");
+    run_tool(
+        Command::new(&options.profdata_tool)
+            .args(["merge", "-sparse"])
+            .arg(&options.profraw)
+            .arg("-o")
+            .arg(&profdata),
+    )?;
+    let mut export = Command::new(&options.cov_tool);
+    export
+        .args(["export", "-format=lcov", "-instr-profile"])
+        .arg(&profdata)
+        .arg(&options.elf);
+    if !options.all_sources {
+        let root = repo_root();
+        export.args(DEFAULT_SOURCES.iter().map(|source| root.join(source)));
+    }
+    let lcov = run_tool(&mut export)?;
+    std::fs::write(lcov_path, &lcov)
+        .map_err(|e| format!("cannot write {}: {e}", lcov_path.display()))?;
+    println!("wrote {}", lcov_path.display());
+    print_summary(&String::from_utf8_lossy(&lcov));
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("kcov: {err}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("kcov: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
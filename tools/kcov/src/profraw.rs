@@ -0,0 +1,295 @@
+//! Rebuilding an LLVM `.profraw` file from the rstiny ELF and a dump.
+//!
+//! This is what `__llvm_profile_write_file` of the LLVM profiler runtime
+//! writes: a header, the per-function records of `__llvm_prf_data`, the
+//! counters, the MC/DC bitmaps and the function names, each padded to 8
+//! bytes. Only the counters and bitmaps change at run time; the rest comes
+//! from the ELF. Raw versions 8 to 10 (LLVM 15 and later) are supported.
+
+use object::{Object, ObjectSection, ObjectSymbol};
+
+use crate::dump::Dump;
+
+/// `\xfflprofr\x81`, for 64-bit targets.
+const MAGIC: u64 = 0xff6c_7072_6f66_7281;
+/// Version written by the profiler runtime of LLVM 19 and later, used when
+/// the ELF does not define `__llvm_profile_raw_version`.
+const DEFAULT_VERSION: u64 = 10;
+const VERSION_MASK: u64 = 0xffff_ffff;
+const VARIANT_MASK_BYTE_COVERAGE: u64 = 1 << 60;
+
+/// Profile sections read from the ELF.
+struct Sections<'a> {
+    data: &'a [u8],
+    data_addr: u64,
+    names: &'a [u8],
+    names_addr: u64,
+    counters_size: u64,
+    counters_addr: u64,
+    bitmap_size: u64,
+    bitmap_addr: u64,
+}
+
+fn load_sections<'a>(file: &object::File<'a>) -> Result<Sections<'a>, String> {
+    let section = |name: &str| {
+        file.section_by_name(name)
+            .ok_or_else(|| format!("no {name} section; was rstiny built with `make COVERAGE=y`?"))
+    };
+    let contents = |section: &object::Section<'a, '_>| {
+        section
+            .data()
+            .map_err(|e| format!("cannot read {}: {e}", section.name().unwrap_or("?")))
+    };
+    let data = section("__llvm_prf_data")?;
+    let names = section("__llvm_prf_names")?;
+    let counters = section("__llvm_prf_cnts")?;
+    // 没有 MC/DC 插桩时不存在位图段
+    let bitmap = file.section_by_name("__llvm_prf_bits");
+    Ok(Sections {
+        data: contents(&data)?,
+        data_addr: data.address(),
+        names: contents(&names)?,
+        names_addr: names.address(),
+        counters_size: counters.size(),
+        counters_addr: counters.address(),
+        bitmap_size: bitmap.as_ref().map_or(0, |s| s.size()),
+        bitmap_addr: bitmap.as_ref().map_or(0, |s| s.address()),
+    })
+}
+
+/// Reads `__llvm_profile_raw_version` if the instrumentation defined it.
+fn raw_version(file: &object::File) -> Result<Option<u64>, String> {
+    let Some(symbol) = file
+        .symbols()
+        .find(|s| s.name() == Ok("__llvm_profile_raw_version"))
+    else {
+        return Ok(None);
+    };
+    let section = symbol
+        .section_index()
+        .and_then(|index| file.section_by_index(index).ok())
+        .ok_or("__llvm_profile_raw_version is not in a section")?;
+    let offset = (symbol.address() - section.address()) as usize;
+    let bytes = section
+        .data()
+        .ok()
+        .and_then(|data| data.get(offset..offset + 8))
+        .ok_or("cannot read __llvm_profile_raw_version")?;
+    Ok(Some(u64::from_le_bytes(bytes.try_into().unwrap())))
+}
+
+fn padding(len: usize) -> usize {
+    len.next_multiple_of(8) - len
+}
+
+/// Builds the `.profraw` contents for `dump` taken from the kernel in `elf`.
+pub fn build(elf: &[u8], dump: &Dump) -> Result<Vec<u8>, String> {
+    let file = object::File::parse(elf).map_err(|e| format!("cannot parse ELF: {e}"))?;
+    if !file.is_little_endian() || !file.is_64() {
+        return Err("only 64-bit little-endian kernels are supported".into());
+    }
+    let sections = load_sections(&file)?;
+    let version = raw_version(&file)?.unwrap_or(DEFAULT_VERSION);
+    assemble(&sections, version, dump)
+}
+
+/// Lays out the header and the sections of a raw profile of `version`.
+fn assemble(sections: &Sections, version: u64, dump: &Dump) -> Result<Vec<u8>, String> {
+    // 每个版本的函数记录大小和头部字段
+    let (record_size, has_bitmap, has_vtables, value_kind_last) = match version & VERSION_MASK {
+        8 => (48, false, false, 1),
+        9 => (64, true, false, 1),
+        10 => (64, true, true, 2),
+        v => return Err(format!("unsupported raw profile version {v}")),
+    };
+    let counter_size = if version & VARIANT_MASK_BYTE_COVERAGE != 0 {
+        1
+    } else {
+        8
+    };
+
+    if dump.counters.len() as u64 != sections.counters_size
+        || dump.bitmap.len() as u64 != sections.bitmap_size
+    {
+        return Err(format!(
+            "the dump has {} counter and {} bitmap bytes, the ELF {} and {}; \
+             is it the kernel that produced the dump?",
+            dump.counters.len(),
+            dump.bitmap.len(),
+            sections.counters_size,
+            sections.bitmap_size
+        ));
+    }
+    if !sections.data.len().is_multiple_of(record_size) {
+        return Err("__llvm_prf_data does not match the raw profile version".into());
+    }
+    if !has_bitmap && !dump.bitmap.is_empty() {
+        return Err("MC/DC bitmaps need raw profile version 9 or later".into());
+    }
+
+    let mut header = vec![
+        MAGIC,
+        version,
+        0, // BinaryIdsSize
+        (sections.data.len() / record_size) as u64,
+        0, // PaddingBytesBeforeCounters
+        (dump.counters.len() / counter_size) as u64,
+        padding(dump.counters.len()) as u64,
+    ];
+    if has_bitmap {
+        header.extend([dump.bitmap.len() as u64, padding(dump.bitmap.len()) as u64]);
+    }
+    header.extend([
+        sections.names.len() as u64,
+        sections.counters_addr.wrapping_sub(sections.data_addr),
+    ]);
+    if has_bitmap {
+        header.push(sections.bitmap_addr.wrapping_sub(sections.data_addr));
+    }
+    header.push(sections.names_addr);
+    if has_vtables {
+        header.extend([0, 0]); // NumVTables, VNamesSize
+    }
+    header.push(value_kind_last);
+
+    let mut out: Vec<u8> = header.iter().flat_map(|v| v.to_le_bytes()).collect();
+    for part in [sections.data, &dump.counters, &dump.bitmap, sections.names] {
+        out.extend_from_slice(part);
+        out.resize(out.len() + padding(part.len()), 0);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA_ADDR: u64 = 0x4008_0000;
+    const COUNTERS_ADDR: u64 = 0x4008_1000;
+    const BITMAP_ADDR: u64 = 0x4008_2000;
+    const NAMES_ADDR: u64 = 0x4008_3000;
+    /// Uncompressed names blob: name length, compressed length 0, names
+    const NAMES: &[u8] = b"\x0a\x00kmain:test";
+
+    /// Two function records, three 8-byte counters, a 3-byte bitmap and a
+    /// 12-byte names blob
+    fn sections(data: &[u8]) -> Sections<'_> {
+        Sections {
+            data,
+            data_addr: DATA_ADDR,
+            names: NAMES,
+            names_addr: NAMES_ADDR,
+            counters_size: 24,
+            counters_addr: COUNTERS_ADDR,
+            bitmap_size: 3,
+            bitmap_addr: BITMAP_ADDR,
+        }
+    }
+
+    fn dump() -> Dump {
+        Dump {
+            counters: (1..=3u64).flat_map(u64::to_le_bytes).collect(),
+            bitmap: vec![0xa5, 0x5a, 0x01],
+        }
+    }
+
+    fn header(profraw: &[u8], len: usize) -> Vec<u64> {
+        profraw[..len * 8]
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn version_10_layout() {
+        let data: Vec<u8> = (0..128).collect();
+        let dump = dump();
+        let profraw = assemble(&sections(&data), 10, &dump).unwrap();
+
+        let fields = header(&profraw, 16);
+        assert_eq!(
+            fields,
+            [
+                MAGIC,
+                10,
+                0,
+                2, // NumData
+                0,
+                3, // NumCounters
+                0,
+                3, // NumBitmapBytes
+                5,
+                12, // NamesSize
+                COUNTERS_ADDR - DATA_ADDR,
+                BITMAP_ADDR - DATA_ADDR,
+                NAMES_ADDR,
+                0,
+                0,
+                2, // ValueKindLast
+            ]
+        );
+
+        // 各段依次排列并填充到 8 字节
+        let data_start = 16 * 8;
+        let counters_start = data_start + 128;
+        let bitmap_start = counters_start + 24;
+        let names_start = bitmap_start + 8;
+        assert_eq!(&profraw[data_start..counters_start], &data[..]);
+        assert_eq!(&profraw[counters_start..bitmap_start], &dump.counters[..]);
+        assert_eq!(
+            &profraw[bitmap_start..names_start],
+            [0xa5, 0x5a, 0x01, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(&profraw[names_start..names_start + 12], NAMES);
+        assert_eq!(profraw.len(), names_start + 16);
+    }
+
+    #[test]
+    fn version_8_layout() {
+        let data = [0; 96];
+        let mut sections = sections(&data);
+        sections.bitmap_size = 0;
+        let dump = Dump {
+            bitmap: Vec::new(),
+            ..dump()
+        };
+        // 字节覆盖率：每个计数器一个字节
+        let version = 8 | VARIANT_MASK_BYTE_COVERAGE;
+        let profraw = assemble(&sections, version, &dump).unwrap();
+
+        let fields = header(&profraw, 11);
+        assert_eq!(
+            fields,
+            [
+                MAGIC,
+                version,
+                0,
+                2,
+                0,
+                24,
+                0,
+                12,
+                COUNTERS_ADDR - DATA_ADDR,
+                NAMES_ADDR,
+                1,
+            ]
+        );
+        let names_start = 11 * 8 + 96 + 24;
+        assert_eq!(&profraw[names_start..names_start + 12], NAMES);
+    }
+
+    #[test]
+    fn mismatched_dump_is_refused() {
+        let data = [0; 128];
+        let dump = Dump {
+            counters: vec![0; 16],
+            ..dump()
+        };
+        let err = assemble(&sections(&data), 10, &dump).unwrap_err();
+        assert!(err.contains("16 counter and 3 bitmap bytes"), "{err}");
+        let err = assemble(&sections(&data[..100]), 10, &self::dump()).unwrap_err();
+        assert!(err.contains("raw profile version"), "{err}");
+        let err = assemble(&sections(&data), 7, &self::dump()).unwrap_err();
+        assert_eq!(err, "unsupported raw profile version 7");
+    }
+}
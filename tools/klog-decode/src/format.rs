@@ -0,0 +1,160 @@
+//! Minimal `core::fmt`-style formatting of decoded arguments.
+//!
+//! Supports `{}`, `{:?}`, `{{`/`}}` and the specs used in rstiny: alternate
+//! form (`#`), zero padding, width, alignment and the `x`, `X`, `o`, `b`,
+//! `e` types. Positional and named arguments are not supported.
+
+use std::fmt::Write;
+
+/// A decoded argument.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Unsigned(u64),
+    Signed(i64),
+    Bool(bool),
+    Str(String),
+    Char(char),
+    Float(f64),
+    /// The target ran out of frame space before this argument.
+    Truncated,
+}
+
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    ty: String,
+}
+
+fn parse_spec(spec: &str) -> Spec {
+    let mut out = Spec::default();
+    let mut chars: Vec<char> = spec.chars().collect();
+    if chars.len() >= 2 && matches!(chars[1], '<' | '^' | '>') {
+        out.fill = Some(chars[0]);
+        out.align = Some(chars[1]);
+        chars.drain(..2);
+    } else if chars.first().is_some_and(|c| matches!(c, '<' | '^' | '>')) {
+        out.align = Some(chars[0]);
+        chars.remove(0);
+    }
+    let mut rest: &[char] = &chars;
+    if let Some(('+' | '-', tail)) = rest.split_first().map(|(c, t)| (*c, t)) {
+        rest = tail;
+    }
+    if rest.first() == Some(&'#') {
+        out.alternate = true;
+        rest = &rest[1..];
+    }
+    if rest.first() == Some(&'0') {
+        out.zero = true;
+        rest = &rest[1..];
+    }
+    let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+    out.width = rest[..digits].iter().collect::<String>().parse().unwrap_or(0);
+    rest = &rest[digits..];
+    if rest.first() == Some(&'.') {
+        let digits = rest[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+        out.precision = rest[1..1 + digits].iter().collect::<String>().parse().ok();
+        rest = &rest[1 + digits..];
+    }
+    out.ty = rest.iter().collect();
+    out
+}
+
+fn format_value(value: &Value, spec: &Spec) -> String {
+    let debug = spec.ty.ends_with('?');
+    let radix = |v: u64, negative: bool| {
+        let (prefix, digits) = match spec.ty.trim_end_matches('?') {
+            "x" => ("0x", format!("{v:x}")),
+            "X" => ("0x", format!("{v:X}")),
+            "o" => ("0o", format!("{v:o}")),
+            "b" => ("0b", format!("{v:b}")),
+            _ => ("", v.to_string()),
+        };
+        let sign = if negative { "-" } else { "" };
+        let prefix = if spec.alternate { prefix } else { "" };
+        if spec.zero {
+            let pad = spec
+                .width
+                .saturating_sub(sign.len() + prefix.len() + digits.len());
+            format!("{sign}{prefix}{}{digits}", "0".repeat(pad))
+        } else {
+            format!("{sign}{prefix}{digits}")
+        }
+    };
+    let body = match value {
+        Value::Unsigned(v) => radix(*v, false),
+        Value::Signed(v) => radix(v.unsigned_abs(), *v < 0),
+        Value::Bool(v) => v.to_string(),
+        Value::Str(s) if debug => format!("{s:?}"),
+        Value::Str(s) => s.clone(),
+        Value::Char(c) if debug => format!("{c:?}"),
+        Value::Char(c) => c.to_string(),
+        Value::Float(v) if spec.ty == "e" => format!("{v:e}"),
+        Value::Float(v) => match spec.precision {
+            Some(p) => format!("{v:.p$}"),
+            None => v.to_string(),
+        },
+        Value::Truncated => "<truncated>".into(),
+    };
+
+    let len = body.chars().count();
+    if len >= spec.width || spec.zero && matches!(value, Value::Unsigned(_) | Value::Signed(_)) {
+        return body;
+    }
+    let pad = spec.width - len;
+    let fill = spec.fill.unwrap_or(' ').to_string();
+    let default_align = match value {
+        Value::Unsigned(_) | Value::Signed(_) | Value::Float(_) => '>',
+        _ => '<',
+    };
+    match spec.align.unwrap_or(default_align) {
+        '>' => format!("{}{body}", fill.repeat(pad)),
+        '^' => format!("{}{body}{}", fill.repeat(pad / 2), fill.repeat(pad - pad / 2)),
+        _ => format!("{body}{}", fill.repeat(pad)),
+    }
+}
+
+/// Returns the number of `{}` placeholders in `format`.
+pub fn placeholders(format: &str) -> usize {
+    format.replace("{{", "").matches('{').count()
+}
+
+/// Substitutes `args` into the `{}` placeholders of `format`.
+pub fn format_message(format: &str, args: &[Value]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let placeholder: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let spec = placeholder.split_once(':').map_or("", |(_, spec)| spec);
+                match args.next() {
+                    Some(value) => out.push_str(&format_value(value, &parse_spec(spec))),
+                    None => {
+                        let _ = write!(out, "{{{placeholder}}}");
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    // 截断标记紧跟在已解码的参数之后
+    if args.any(|value| matches!(value, Value::Truncated)) {
+        out.push_str(" <truncated>");
+    }
+    out
+}
@@ -0,0 +1,419 @@
+//! Host-side decoder for rstiny binary log frames.
+//!
+//! Usage: `klog-decode [--no-color] <rstiny-elf> [<input>]`
+//!
+//! Reads the serial stream from `<input>` (a capture file or a serial device,
+//! stdin if omitted), prints text output unchanged and replaces binary log
+//! frames by readable lines, using the call site descriptors stored in the
+//! `.rstiny_log` section of the ELF. See `rstiny/src/utils/logging/binary.rs`
+//! for the wire format.
+
+mod format;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process::ExitCode;
+
+use object::{Object, ObjectSection};
+
+use format::{Value, format_message, placeholders};
+
+const LOG_SECTION: &str = ".rstiny_log";
+
+const TAG_UNSIGNED: u8 = 1;
+const TAG_SIGNED: u8 = 2;
+const TAG_BOOL: u8 = 3;
+const TAG_STR: u8 = 4;
+const TAG_CHAR: u8 = 5;
+const TAG_FLOAT: u8 = 6;
+const TAG_TRUNCATED: u8 = 0x7f;
+
+/// A call site descriptor: `<level> 0x1f <file>:<line> 0x1f <format>`. The
+/// location may hold placeholders, filled by the first arguments.
+struct Site {
+    level: char,
+    location: String,
+    format: String,
+}
+
+/// Loads every call site descriptor, keyed by address.
+fn load_sites(elf: &[u8]) -> Result<HashMap<u64, Site>, String> {
+    let file = object::File::parse(elf).map_err(|e| format!("cannot parse ELF: {e}"))?;
+    let section = file
+        .section_by_name(LOG_SECTION)
+        .ok_or_else(|| format!("no {LOG_SECTION} section; was rstiny built with `binary-log`?"))?;
+    let data = section
+        .data()
+        .map_err(|e| format!("cannot read {LOG_SECTION}: {e}"))?;
+
+    let mut sites = HashMap::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let end = data[offset..]
+            .iter()
+            .position(|&b| b == 0)
+            .map_or(data.len(), |n| offset + n);
+        let text = String::from_utf8_lossy(&data[offset..end]);
+        let mut parts = text.splitn(3, '\x1f');
+        if let (Some(level), Some(location), Some(format)) =
+            (parts.next(), parts.next(), parts.next())
+        {
+            sites.insert(
+                section.address() + offset as u64,
+                Site {
+                    level: level.chars().next().unwrap_or('?'),
+                    location: location.into(),
+                    format: format.into(),
+                },
+            );
+        }
+        offset = end + 1;
+    }
+    Ok(sites)
+}
+
+fn cobs_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        let code = input[i] as usize;
+        if code == 0 {
+            return None;
+        }
+        out.extend_from_slice(input.get(i + 1..i + code)?);
+        i += code;
+        if code != 0xff && i < input.len() {
+            out.push(0);
+        }
+    }
+    Some(out)
+}
+
+struct Payload<'a> {
+    data: &'a [u8],
+}
+
+impl Payload<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&b, rest) = self.data.split_first()?;
+        self.data = rest;
+        Some(b)
+    }
+
+    fn bytes(&mut self, n: usize) -> Option<&[u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(n);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            value |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Decodes the next argument. `Ok(None)` marks the end of the arguments.
+    fn value(&mut self) -> Result<Option<Value>, ()> {
+        let Some(tag) = self.byte() else {
+            return Ok(None);
+        };
+        let value = match tag {
+            TAG_UNSIGNED => Value::Unsigned(self.varint().ok_or(())?),
+            TAG_SIGNED => {
+                let v = self.varint().ok_or(())?;
+                Value::Signed((v >> 1) as i64 ^ -((v & 1) as i64))
+            }
+            TAG_BOOL => Value::Bool(self.byte().ok_or(())? != 0),
+            TAG_STR => {
+                let len = self.varint().ok_or(())? as usize;
+                Value::Str(String::from_utf8_lossy(self.bytes(len).ok_or(())?).into_owned())
+            }
+            TAG_CHAR => Value::Char(
+                char::from_u32(self.varint().ok_or(())? as u32).unwrap_or(char::REPLACEMENT_CHARACTER),
+            ),
+            TAG_FLOAT => Value::Float(f64::from_le_bytes(
+                self.bytes(8).ok_or(())?.try_into().unwrap(),
+            )),
+            TAG_TRUNCATED => Value::Truncated,
+            _ => return Err(()),
+        };
+        Ok(Some(value))
+    }
+}
+
+struct Decoder {
+    sites: HashMap<u64, Site>,
+    color: bool,
+}
+
+impl Decoder {
+    fn decode_frame(&self, frame: &[u8]) -> String {
+        let Some(payload) = cobs_decode(frame) else {
+            return "<corrupted frame>".into();
+        };
+        let mut payload = Payload { data: &payload };
+        let (Some(site), Some(micros), Some(cpu)) =
+            (payload.varint(), payload.varint(), payload.varint())
+        else {
+            return "<truncated frame>".into();
+        };
+        let Some(site) = self.sites.get(&site) else {
+            return format!("<unknown call site {site:#x}; ELF does not match the target?>");
+        };
+
+        let mut args = Vec::new();
+        loop {
+            match payload.value() {
+                Ok(Some(value)) => args.push(value),
+                Ok(None) => break,
+                Err(()) => {
+                    args.push(Value::Truncated);
+                    break;
+                }
+            }
+        }
+
+        let (level, level_color, args_color) = match site.level {
+            'E' => ("ERROR", 91, 31),
+            'W' => ("WARN", 93, 33),
+            'I' => ("INFO", 92, 32),
+            'D' => ("DEBUG", 96, 36),
+            _ => ("TRACE", 90, 90),
+        };
+        let split = placeholders(&site.location).min(args.len());
+        let location = format_message(&site.location, &args[..split]);
+        let message = format_message(&site.format, &args[split..]);
+        let ts = format!("{:>5}.{:06}", micros / 1_000_000, micros % 1_000_000);
+        if self.color {
+            format!(
+                "[{ts} {cpu} \x1b[{level_color}m{level:<5}\x1b[0m {location}] \x1b[{args_color}m{message}\x1b[0m"
+            )
+        } else {
+            format!("[{ts} {cpu} {level:<5} {location}] {message}")
+        }
+    }
+
+    fn run(&self, input: impl Read, mut output: impl Write) -> io::Result<()> {
+        let mut frame: Option<Vec<u8>> = None;
+        for byte in BufReader::new(input).bytes() {
+            let byte = byte?;
+            match (&mut frame, byte) {
+                (None, 0) => {
+                    output.flush()?;
+                    frame = Some(Vec::new());
+                }
+                (None, b) => {
+                    output.write_all(&[b])?;
+                    if b == b'\n' {
+                        output.flush()?;
+                    }
+                }
+                // 连续两个 0：上一帧的结束紧接着下一帧的开始
+                (Some(buf), 0) if buf.is_empty() => {}
+                (Some(buf), 0) => {
+                    let line = self.decode_frame(buf);
+                    writeln!(output, "{line}")?;
+                    output.flush()?;
+                    frame = None;
+                }
+                (Some(buf), b) => buf.push(b),
+            }
+        }
+        output.flush()
+    }
+}
+
+fn main() -> ExitCode {
+    let mut color = true;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--no-color" => color = false,
+            "-h" | "--help" => {
+                println!("usage: klog-decode [--no-color] <rstiny-elf> [<input>]");
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let (elf_path, input_path) = match paths.as_slice() {
+        [elf] => (elf, None),
+        [elf, input] => (elf, Some(input)),
+        _ => {
+            eprintln!("usage: klog-decode [--no-color] <rstiny-elf> [<input>]");
+            return ExitCode::from(2);
+        }
+    };
+
+    let sites = match std::fs::read(elf_path)
+        .map_err(|e| format!("cannot read {elf_path}: {e}"))
+        .and_then(|elf| load_sites(&elf))
+    {
+        Ok(sites) => sites,
+        Err(err) => {
+            eprintln!("klog-decode: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let decoder = Decoder { sites, color };
+
+    let stdout = BufWriter::new(io::stdout().lock());
+    let result = match input_path {
+        Some(path) => File::open(path).and_then(|file| decoder.run(file, stdout)),
+        None => decoder.run(io::stdin().lock(), stdout),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("klog-decode: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// COBS encoder, as on the target.
+    fn cobs_encode(input: &[u8]) -> Vec<u8> {
+        let mut out = vec![0];
+        let mut code_pos = 0;
+        for &b in input {
+            if b != 0 {
+                out.push(b);
+            }
+            if b == 0 || out.len() - code_pos == 0xff {
+                out[code_pos] = (out.len() - code_pos) as u8;
+                code_pos = out.len();
+                out.push(0);
+            }
+        }
+        out[code_pos] = (out.len() - code_pos) as u8;
+        out
+    }
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte);
+                return out;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    fn zigzag(value: i64) -> u64 {
+        ((value << 1) ^ (value >> 63)) as u64
+    }
+
+    #[test]
+    fn cobs_decodes_zeros_and_long_runs() {
+        assert_eq!(cobs_decode(&[0x01, 0x01]).unwrap(), [0]);
+        assert_eq!(
+            cobs_decode(&[0x03, 0x11, 0x22, 0x02, 0x33]).unwrap(),
+            [0x11, 0x22, 0, 0x33]
+        );
+        let long: Vec<u8> = (1..=255).collect();
+        assert_eq!(cobs_decode(&cobs_encode(&long)).unwrap(), long);
+        assert_eq!(cobs_decode(&[0x02, 0x11, 0x00]), None, "Zero code accepted");
+        assert_eq!(cobs_decode(&[0x05, 0x11]), None, "Short block accepted");
+    }
+
+    #[test]
+    fn varints_and_zigzag() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let bytes = varint(value);
+            let mut payload = Payload { data: &bytes };
+            assert_eq!(payload.varint(), Some(value));
+            assert!(payload.data.is_empty());
+        }
+        assert_eq!(varint(300), [0xac, 0x02]);
+        assert_eq!(
+            Payload { data: &[0x80] }.varint(),
+            None,
+            "Unterminated varint"
+        );
+
+        for value in [0, -1, 1, -64, i64::MIN, i64::MAX] {
+            let mut bytes = vec![TAG_SIGNED];
+            bytes.extend(varint(zigzag(value)));
+            match (Payload { data: &bytes }).value() {
+                Ok(Some(Value::Signed(decoded))) => assert_eq!(decoded, value),
+                other => panic!("{value} decoded as {other:?}"),
+            }
+        }
+        assert_eq!(zigzag(-1), 1);
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let site = |location: &str, format: &str| Site {
+            level: 'W',
+            location: location.into(),
+            format: format.into(),
+        };
+        let decoder = Decoder {
+            sites: HashMap::from([
+                (0x1000, site("main.rs:7", "{} of {:#x}: {:?} {}")),
+                (0x2000, site("{}:{}", "{}")),
+            ]),
+            color: false,
+        };
+
+        let mut payload = [varint(0x1000), varint(1_500_000), varint(2)].concat();
+        payload.push(TAG_SIGNED);
+        payload.extend(varint(zigzag(-3)));
+        payload.push(TAG_UNSIGNED);
+        payload.extend(varint(0xff00));
+        payload.push(TAG_STR);
+        payload.extend(varint(2));
+        payload.extend(b"ok");
+        payload.extend([TAG_BOOL, 1]);
+        assert_eq!(
+            decoder.decode_frame(&cobs_encode(&payload)),
+            "[    1.500000 2 WARN  main.rs:7] -3 of 0xff00: \"ok\" true"
+        );
+
+        // `log` 记录：位置由前两个参数填充
+        let mut payload = [varint(0x2000), varint(0), varint(0)].concat();
+        payload.push(TAG_STR);
+        payload.extend(varint(6));
+        payload.extend(b"fdt.rs");
+        payload.push(TAG_UNSIGNED);
+        payload.extend(varint(42));
+        payload.push(TAG_STR);
+        payload.extend(varint(2));
+        payload.extend(b"hi");
+        payload.push(TAG_TRUNCATED);
+        assert_eq!(
+            decoder.decode_frame(&cobs_encode(&payload)),
+            "[    0.000000 0 WARN  fdt.rs:42] hi <truncated>"
+        );
+
+        let mut input = b"text\n\0".to_vec();
+        input.extend(cobs_encode(
+            &[varint(0x3000), varint(0), varint(0)].concat(),
+        ));
+        input.push(0);
+        let mut output = Vec::new();
+        decoder.run(input.as_slice(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "text\n<unknown call site 0x3000; ELF does not match the target?>\n"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Host-side QEMU test runner for rstiny and arceos-shell.
+//!
+//! Usage: `qemu-test [options] [rstiny|arceos-shell]...`
+//!
+//! Builds the QEMU `virt` flavor of each app (`make build PLAT=qemu-virt`)
+//! and boots it in `qemu-system-aarch64` with a time limit:
+//!
+//! - rstiny is built with the `ktest` feature and exits QEMU through
+//!   semihosting after its tests; the TAP report between the `#### ktest`
+//!   markers gives the verdict.
+//! - arceos-shell is driven by a script of commands and expected output (see
+//!   [`script`]), which must end with the system exiting.
+//!
+//! With `--coverage`, rstiny is built with `COVERAGE=y` and dumps its coverage
+//! counters after the tests, for `tools/kcov`.
+//!
+//! The serial output of each run is saved to `target/qemu-test/<app>.log`.
+//! Exits with status 0 if every app passed, 1 otherwise and 2 on usage errors.
+
+mod qemu;
+mod report;
+mod script;
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use std::time::{Duration, Instant};
+
+use qemu::{Qemu, QemuConfig, WaitError};
+use report::MARKER;
+
+const USAGE: &str = "\
+usage: qemu-test [options] [rstiny|arceos-shell]...
+
+options:
+  --no-build          boot the images of a previous build
+  --debug             build in debug mode instead of release
+  --coverage          build rstiny with coverage instrumentation
+  --timeout <secs>    time limit of each run (default 120)
+  --filter <pats>     rstiny tests to run, as with `test=` on its command line
+  --script <file>     arceos-shell script (default scripts/arceos-shell.script)
+  --qemu <path>       QEMU binary (default qemu-system-aarch64)
+  --smp <n>           number of CPUs (default 1)
+  -v, --verbose       copy the serial output to stdout";
+
+const TARGET: &str = "aarch64-unknown-none-softfloat";
+/// Text of the arceos-shell prompt before the current directory.
+const SHELL_PROMPT: &str = "arceos:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum App {
+    Rstiny,
+    ArceosShell,
+}
+
+impl App {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Rstiny => "rstiny",
+            Self::ArceosShell => "arceos-shell",
+        }
+    }
+}
+
+struct Options {
+    apps: Vec<App>,
+    build: bool,
+    mode: &'static str,
+    coverage: bool,
+    timeout: Duration,
+    filter: Option<String>,
+    script: PathBuf,
+    qemu: String,
+    smp: usize,
+    verbose: bool,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut options = Options {
+        apps: Vec::new(),
+        build: true,
+        mode: "release",
+        coverage: false,
+        timeout: Duration::from_secs(120),
+        filter: None,
+        script: manifest_dir.join("scripts/arceos-shell.script"),
+        qemu: "qemu-system-aarch64".into(),
+        smp: 1,
+        verbose: false,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{name} needs a value"));
+        match arg.as_str() {
+            "--no-build" => options.build = false,
+            "--debug" => options.mode = "debug",
+            "--coverage" => options.coverage = true,
+            "--timeout" => {
+                let secs = value("--timeout")?;
+                let secs = secs
+                    .parse()
+                    .map_err(|_| format!("invalid timeout `{secs}`"))?;
+                options.timeout = Duration::from_secs(secs);
+            }
+            "--filter" => options.filter = Some(value("--filter")?),
+            "--script" => options.script = value("--script")?.into(),
+            "--qemu" => options.qemu = value("--qemu")?,
+            "--smp" => {
+                let smp = value("--smp")?;
+                options.smp = smp
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("invalid CPU count `{smp}`"))?;
+            }
+            "-v" | "--verbose" => options.verbose = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            "rstiny" => options.apps.push(App::Rstiny),
+            "arceos-shell" => options.apps.push(App::ArceosShell),
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+    if options.apps.is_empty() {
+        options.apps = vec![App::Rstiny, App::ArceosShell];
+    }
+    Ok(options)
+}
+
+/// Root of the repository, which holds the top-level Makefile.
+fn repo_root() -> PathBuf {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    root.canonicalize().unwrap_or(root)
+}
+
+fn build(options: &Options, app: App) -> Result<PathBuf, String> {
+    let root = repo_root();
+    if options.build {
+        let mut make = Command::new("make");
+        make.current_dir(&root).args([
+            "build".into(),
+            format!("APP={}", app.name()),
+            "PLAT=qemu-virt".into(),
+            format!("MODE={}", options.mode),
+        ]);
+        if app == App::Rstiny {
+            make.arg("FEATURES=ktest");
+            if options.coverage {
+                make.arg("COVERAGE=y");
+            }
+        }
+        let status = make.status().map_err(|e| format!("cannot run make: {e}"))?;
+        if !status.success() {
+            return Err(format!("build failed ({status})"));
+        }
+    }
+    let kernel = root
+        .join("target")
+        .join(TARGET)
+        .join(options.mode)
+        .join(format!("{}.bin", app.name()));
+    if !kernel.exists() {
+        return Err(format!("{} not found", kernel.display()));
+    }
+    Ok(kernel)
+}
+
+fn spawn(
+    options: &Options,
+    kernel: &Path,
+    cmdline: &str,
+    semihosting: bool,
+) -> Result<Qemu, String> {
+    let config = QemuConfig {
+        binary: &options.qemu,
+        kernel,
+        cmdline,
+        smp: options.smp,
+        semihosting,
+    };
+    Qemu::spawn(&config, options.verbose).map_err(|e| format!("cannot start {}: {e}", options.qemu))
+}
+
+fn describe(err: WaitError, what: &str) -> String {
+    match err {
+        WaitError::Timeout => format!("timed out waiting for {what}"),
+        WaitError::Exited => format!("QEMU exited before {what}"),
+    }
+}
+
+fn run_rstiny(qemu: &mut Qemu, deadline: Instant) -> Result<String, String> {
+    qemu.wait_for(&format!("{MARKER} begin"), deadline)
+        .map_err(|e| describe(e, "the test report"))?;
+    let body = qemu
+        .wait_for(&format!("{MARKER} end"), deadline)
+        .map_err(|e| describe(e, "the end of the test report"))?;
+    let end_fields = qemu
+        .wait_for("\n", deadline)
+        .map_err(|e| describe(e, "the end of the test report"))?;
+    let summary = report::parse(&body, &end_fields)?;
+
+    let status = qemu
+        .wait_exit(deadline)
+        .map_err(|e| format!("cannot wait for QEMU: {e}"))?
+        .ok_or("QEMU did not exit after the tests; was it built with semihosting exit?")?;
+    for failure in &summary.failures {
+        eprintln!("{failure}");
+    }
+    report::verdict(&summary, status)
+}
+
+/// Waits for the next shell prompt and returns the output before it, or all
+/// remaining output and `true` if QEMU exits first.
+fn read_until_prompt(qemu: &mut Qemu, deadline: Instant) -> Result<(String, bool), String> {
+    match qemu.wait_for(SHELL_PROMPT, deadline) {
+        Ok(text) => {
+            qemu.wait_for("$ ", deadline)
+                .map_err(|e| describe(e, "the end of the prompt"))?;
+            Ok((text[..text.len() - SHELL_PROMPT.len()].into(), false))
+        }
+        Err(WaitError::Exited) => {
+            let text = qemu.wait_for_close(deadline).unwrap_or_default();
+            Ok((text, true))
+        }
+        Err(WaitError::Timeout) => Err(describe(WaitError::Timeout, "the shell prompt")),
+    }
+}
+
+fn run_shell(options: &Options, qemu: &mut Qemu, deadline: Instant) -> Result<String, String> {
+    let text = std::fs::read_to_string(&options.script)
+        .map_err(|e| format!("cannot read {}: {e}", options.script.display()))?;
+    let steps = script::parse(&text).map_err(|e| format!("{}: {e}", options.script.display()))?;
+
+    let (_, exited) = read_until_prompt(qemu, deadline)?;
+    if exited {
+        return Err("QEMU exited before the first prompt".into());
+    }
+    let mut exited = false;
+    for step in &steps {
+        if exited {
+            return Err(format!(
+                "QEMU exited before line {} (`{}`)",
+                step.line, step.command
+            ));
+        }
+        qemu.send_line(&step.command)
+            .map_err(|e| format!("cannot write to QEMU: {e}"))?;
+        let output;
+        (output, exited) = read_until_prompt(qemu, deadline)
+            .map_err(|e| format!("line {} (`{}`): {e}", step.line, step.command))?;
+        // 第一行是 shell 回显的命令本身
+        let output = output.split_once('\n').map_or("", |(_, rest)| rest);
+        if let Some(missing) = script::check(step, output) {
+            return Err(format!(
+                "line {} (`{}`): expected `{missing}` in\n{output}",
+                step.line, step.command
+            ));
+        }
+    }
+
+    let status = qemu
+        .wait_exit(deadline)
+        .map_err(|e| format!("cannot wait for QEMU: {e}"))?
+        .ok_or("the script did not make the system exit")?;
+    if !status.success() {
+        return Err(format!("QEMU exited with {status}"));
+    }
+    Ok(format!("{} commands", steps.len()))
+}
+
+fn save_log(app: App, output: &[u8]) -> Option<PathBuf> {
+    let dir = repo_root().join("target/qemu-test");
+    let path = dir.join(format!("{}.log", app.name()));
+    std::fs::create_dir_all(&dir).ok()?;
+    std::fs::write(&path, output).ok()?;
+    Some(path)
+}
+
+fn run(options: &Options, app: App) -> Result<String, String> {
+    let kernel = build(options, app)?;
+    let deadline = Instant::now() + options.timeout;
+    let mut qemu = match app {
+        App::Rstiny => {
+            let mut cmdline = String::from("test_exit=semihosting test_format=tap");
+            if let Some(filter) = &options.filter {
+                cmdline += &format!(" test={filter}");
+            }
+            spawn(options, &kernel, &cmdline, true)?
+        }
+        App::ArceosShell => spawn(options, &kernel, "", false)?,
+    };
+    let result = match app {
+        App::Rstiny => run_rstiny(&mut qemu, deadline),
+        App::ArceosShell => run_shell(options, &mut qemu, deadline),
+    };
+    match save_log(app, qemu.output()) {
+        Some(path) => result.map_err(|e| format!("{e}\nserial output: {}", path.display())),
+        None => result,
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("qemu-test: {err}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let mut failed = false;
+    for &app in &options.apps {
+        match run(&options, app) {
+            Ok(summary) => println!("{}: ok ({summary})", app.name()),
+            Err(err) => {
+                println!("{}: FAILED: {err}", app.name());
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,178 @@
+//! A QEMU process with its serial port on stdin/stdout.
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Instant;
+
+/// How to start QEMU.
+pub struct QemuConfig<'a> {
+    pub binary: &'a str,
+    pub kernel: &'a Path,
+    pub cmdline: &'a str,
+    pub smp: usize,
+    pub semihosting: bool,
+}
+
+/// Why [`Qemu::wait_for`] gave up.
+#[derive(Debug)]
+pub enum WaitError {
+    Timeout,
+    /// QEMU closed its serial output, i.e. it exited.
+    Exited,
+}
+
+pub struct Qemu {
+    child: Child,
+    stdin: ChildStdin,
+    chunks: Receiver<Vec<u8>>,
+    /// Everything read from the serial port, with `\r` removed.
+    output: Vec<u8>,
+    /// Start of the output not yet returned by [`Qemu::wait_for`].
+    cursor: usize,
+    closed: bool,
+    echo: bool,
+}
+
+impl Qemu {
+    /// Boots `config.kernel` on a QEMU `virt` machine. With `echo`, the serial
+    /// output is copied to stdout as it arrives.
+    pub fn spawn(config: &QemuConfig, echo: bool) -> io::Result<Self> {
+        let mut command = Command::new(config.binary);
+        command
+            .args(["-machine", "virt", "-cpu", "max", "-m", "1G"])
+            .args(["-smp", &config.smp.to_string()])
+            .args(["-display", "none", "-monitor", "none", "-serial", "stdio"])
+            .arg("-kernel")
+            .arg(config.kernel)
+            .args(["-append", config.cmdline]);
+        if config.semihosting {
+            command.arg("-semihosting");
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+
+        let (sender, chunks) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0; 4096];
+            while let Ok(n @ 1..) = stdout.read(&mut buf) {
+                if sender.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            chunks,
+            output: Vec::new(),
+            cursor: 0,
+            closed: false,
+            echo,
+        })
+    }
+
+    /// Returns the whole serial output so far.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Types `line` followed by a carriage return.
+    pub fn send_line(&mut self, line: &str) -> io::Result<()> {
+        self.stdin.write_all(line.as_bytes())?;
+        self.stdin.write_all(b"\r")?;
+        self.stdin.flush()
+    }
+
+    /// Receives the next chunk of serial output, waiting until `deadline`.
+    fn receive(&mut self, deadline: Instant) -> Result<(), WaitError> {
+        if self.closed {
+            return Err(WaitError::Exited);
+        }
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.chunks.recv_timeout(timeout) {
+            Ok(chunk) => {
+                if self.echo {
+                    let mut stdout = io::stdout().lock();
+                    let _ = stdout.write_all(&chunk);
+                    let _ = stdout.flush();
+                }
+                self.output
+                    .extend(chunk.into_iter().filter(|&b| b != b'\r'));
+                Ok(())
+            }
+            Err(RecvTimeoutError::Timeout) => Err(WaitError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                self.closed = true;
+                Err(WaitError::Exited)
+            }
+        }
+    }
+
+    /// Waits for `pattern` in the output not returned yet, and returns the
+    /// output up to and including it.
+    pub fn wait_for(&mut self, pattern: &str, deadline: Instant) -> Result<String, WaitError> {
+        let pattern = pattern.as_bytes();
+        loop {
+            if let Some(pos) = self.output[self.cursor..]
+                .windows(pattern.len())
+                .position(|window| window == pattern)
+            {
+                let end = self.cursor + pos + pattern.len();
+                let text = String::from_utf8_lossy(&self.output[self.cursor..end]).into_owned();
+                self.cursor = end;
+                return Ok(text);
+            }
+            self.receive(deadline)?;
+        }
+    }
+
+    /// Returns the output not returned yet, after waiting for QEMU to close
+    /// its serial output.
+    pub fn wait_for_close(&mut self, deadline: Instant) -> Result<String, WaitError> {
+        loop {
+            match self.receive(deadline) {
+                Ok(()) => {}
+                Err(WaitError::Exited) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        let text = String::from_utf8_lossy(&self.output[self.cursor..]).into_owned();
+        self.cursor = self.output.len();
+        Ok(text)
+    }
+
+    /// Waits for QEMU to exit, killing it at `deadline`. Returns `None` if it
+    /// had to be killed.
+    pub fn wait_exit(&mut self, deadline: Instant) -> io::Result<Option<ExitStatus>> {
+        // 先读完串口输出，QEMU 退出时会关闭它
+        let _ = self.wait_for_close(deadline);
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                return Ok(Some(status));
+            }
+            if Instant::now() >= deadline {
+                self.child.kill()?;
+                self.child.wait()?;
+                return Ok(None);
+            }
+            thread::sleep(std::time::Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for Qemu {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
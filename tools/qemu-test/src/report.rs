@@ -0,0 +1,176 @@
+//! Parsing of the rstiny test report, printed in TAP format between marker
+//! lines by `rstiny/src/test/report.rs`.
+
+use std::process::ExitStatus;
+
+/// Prefix of the marker lines around the report.
+pub const MARKER: &str = "#### ktest";
+
+pub struct Summary {
+    /// Verdict given by the end marker.
+    pub ok: bool,
+    pub passed: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+    /// `not ok` lines of the TAP report, each with its diagnostic block.
+    pub failures: Vec<String>,
+}
+
+/// Parses the TAP `body` between the markers and the fields of the end
+/// marker line (`result=ok passed=12 failed=0 timed_out=0`). Timed-out tests
+/// are `not ok` lines too.
+pub fn parse(body: &str, end_fields: &str) -> Result<Summary, String> {
+    let field = |key: &str| {
+        end_fields
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))
+            .ok_or_else(|| format!("no `{key}` in the end marker `{}`", end_fields.trim()))
+    };
+    let count = |key: &str| {
+        field(key)?
+            .parse::<usize>()
+            .map_err(|_| format!("invalid `{key}` in the end marker"))
+    };
+    let summary_ok = field("result")? == "ok";
+    let passed = count("passed")?;
+    let failed = count("failed")?;
+    let timed_out = count("timed_out")?;
+
+    let mut failures: Vec<String> = Vec::new();
+    let mut tap_passed = 0;
+    for line in body.lines() {
+        if line.starts_with("not ok ") {
+            failures.push(line.into());
+        } else if line.starts_with("ok ") {
+            tap_passed += 1;
+        } else if line.starts_with("  ")
+            && let Some(failure) = failures.last_mut()
+        {
+            // 失败测试的 YAML 诊断块
+            failure.push('\n');
+            failure.push_str(line);
+        }
+    }
+    if tap_passed != passed || failures.len() != failed + timed_out {
+        return Err(format!(
+            "report lists {} passed and {} failed or timed-out tests, the end marker {} and {}",
+            tap_passed,
+            failures.len(),
+            passed,
+            failed + timed_out
+        ));
+    }
+    Ok(Summary {
+        ok: summary_ok && failed == 0 && timed_out == 0,
+        passed,
+        failed,
+        timed_out,
+        failures,
+    })
+}
+
+/// Judges a run from its report and the exit status of QEMU. rstiny exits
+/// with status 1 through semihosting when a test fails, so a passing report
+/// with a failing status means the report and the exit path disagree.
+pub fn verdict(summary: &Summary, status: ExitStatus) -> Result<String, String> {
+    if !summary.ok {
+        return Err(format!(
+            "{} passed, {} failed, {} timed out",
+            summary.passed, summary.failed, summary.timed_out
+        ));
+    }
+    if !status.success() {
+        return Err(format!("report passed but QEMU exited with {status}"));
+    }
+    Ok(format!("{} passed", summary.passed))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+
+    const BODY: &str = "\
+TAP version 13
+1..3
+ok 1 - frame_allocator::alloc_free
+not ok 2 - heap::stats
+  ---
+  message: 'assertion failed'
+  ...
+ok 3 - fdt::parse
+";
+
+    fn exited(code: i32) -> ExitStatus {
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[test]
+    fn failures_keep_their_diagnostics() {
+        let summary = parse(BODY, " result=failed passed=2 failed=1 timed_out=0\n").unwrap();
+        assert!(!summary.ok);
+        assert_eq!(
+            (summary.passed, summary.failed, summary.timed_out),
+            (2, 1, 0)
+        );
+        assert_eq!(
+            summary.failures,
+            ["not ok 2 - heap::stats\n  ---\n  message: 'assertion failed'\n  ..."]
+        );
+    }
+
+    #[test]
+    fn counts_must_match_the_end_marker() {
+        let err = parse(BODY, "result=failed passed=3 failed=1 timed_out=0")
+            .err()
+            .unwrap();
+        assert!(err.contains("2 passed and 1 failed"), "{err}");
+        let err = parse(BODY, "result=failed passed=2 failed=1")
+            .err()
+            .unwrap();
+        assert!(err.contains("no `timed_out`"), "{err}");
+        let err = parse(BODY, "result=failed passed=two failed=1 timed_out=0")
+            .err()
+            .unwrap();
+        assert!(err.contains("invalid `passed`"), "{err}");
+    }
+
+    #[test]
+    fn result_ok_needs_zero_failures() {
+        let body = "ok 1 - a\nok 2 - b\n";
+        assert!(
+            parse(body, "result=ok passed=2 failed=0 timed_out=0")
+                .unwrap()
+                .ok
+        );
+        assert!(
+            !parse(body, "result=failed passed=2 failed=0 timed_out=0")
+                .unwrap()
+                .ok
+        );
+        let timed_out = parse(
+            "not ok 1 - c # timeout\n",
+            "result=ok passed=0 failed=0 timed_out=1",
+        );
+        assert!(!timed_out.unwrap().ok);
+    }
+
+    #[test]
+    fn exit_status_mapping() {
+        let passed = parse("ok 1 - a\n", "result=ok passed=1 failed=0 timed_out=0").unwrap();
+        assert_eq!(verdict(&passed, exited(0)), Ok("1 passed".into()));
+        let err = verdict(&passed, exited(1)).unwrap_err();
+        assert!(
+            err.starts_with("report passed but QEMU exited with"),
+            "{err}"
+        );
+
+        let failed = parse(BODY, "result=failed passed=2 failed=1 timed_out=0").unwrap();
+        assert_eq!(
+            verdict(&failed, exited(1)),
+            Err("2 passed, 1 failed, 0 timed out".into())
+        );
+        assert!(verdict(&failed, exited(0)).is_err());
+    }
+}
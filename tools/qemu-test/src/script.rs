@@ -0,0 +1,56 @@
+//! Shell session scripts.
+//!
+//! ```text
+//! # Comment
+//! $ echo hello
+//! hello
+//! $ exit
+//! Bye~
+//! ```
+//!
+//! A line starting with `$ ` is typed at the shell prompt. The lines after it
+//! must appear, in that order, in the output of that command (each as a
+//! substring of the output, not necessarily a whole line). Blank lines and
+//! lines starting with `#` are ignored.
+
+/// A command and the output expected from it.
+pub struct Step {
+    /// Line number of the command in the script, for reports.
+    pub line: usize,
+    pub command: String,
+    pub expected: Vec<String>,
+}
+
+pub fn parse(text: &str) -> Result<Vec<Step>, String> {
+    let mut steps: Vec<Step> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(command) = line.strip_prefix("$ ") {
+            steps.push(Step {
+                line: index + 1,
+                command: command.into(),
+                expected: Vec::new(),
+            });
+        } else {
+            let step = steps
+                .last_mut()
+                .ok_or_else(|| format!("line {}: expected output before any command", index + 1))?;
+            step.expected.push(line.into());
+        }
+    }
+    Ok(steps)
+}
+
+/// Returns the first expected line missing from `output`, in order.
+pub fn check<'a>(step: &'a Step, output: &str) -> Option<&'a str> {
+    let mut rest = output;
+    for expected in &step.expected {
+        match rest.find(expected.as_str()) {
+            Some(pos) => rest = &rest[pos + expected.len()..],
+            None => return Some(expected),
+        }
+    }
+    None
+}